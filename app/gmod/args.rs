@@ -1,6 +1,7 @@
 use clap::{ArgAction, Parser};
 use derive_getters::Getters;
 use galaxy_ops::infra::DfxArgsGetter;
+use galaxy_ops::project::ValueArgs;
 
 #[derive(Debug, Parser)] // requires `derive` feature
 #[command(name = "gmod")]
@@ -34,6 +35,44 @@ pub enum GxModCmd {
         long_about = "Generate localized configuration files for the module based on environment-specific values. Useful for adapting modules to different deployment environments."
     )]
     Localize(LocalArgs),
+    /// Package module into a distributable bundle
+    #[command(
+        about = "Package module into name-version.tar.gz",
+        long_about = "Build a versioned module bundle from version.txt. The archive holds the mod/ tree plus a manifest listing the module name, version, targets, vars and checksums."
+    )]
+    Package(PackageArgs),
+    /// Manage module targets
+    #[command(
+        subcommand,
+        about = "Add or remove module targets",
+        long_about = "Scaffold a new arch-os-spc target inside the module, cloned from an existing target or from the host/k8s templates, or remove an existing target."
+    )]
+    Target(TargetCmd),
+    /// Validate module project
+    #[command(
+        about = "Validate module targets without localizing",
+        long_about = "Check every target under mod/: artifact.yml, depends.yml, vars.yml and setting.yml must parse, templates under spec/ must compile with the configured delimiters, referenced vars must be declared in vars.yml and declared vars should be used. Problems are reported with file and line."
+    )]
+    Lint(LintArgs),
+    /// Interactively set variable values
+    #[command(
+        about = "Interactively set variable values",
+        long_about = "Prompt for each target variable by type: a list for enums, yes/no for booleans, validated input for numbers and a hidden prompt for sensitive or encrypted values. Variables already set in the target values/value.yml are skipped unless --all is given. A summary of changes is shown before saving."
+    )]
+    Setting(SettingArgs),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TargetCmd {
+    /// Add a new target
+    #[command(
+        about = "Add a new target, e.g. x86-ubt24-k8s",
+        long_about = "Add a new target to the module. With --from the target is cloned from an existing one and its artifact.yml is rewritten for the new platform; otherwise the host/k8s template is used. vars.yml is kept in sync with the existing targets."
+    )]
+    Add(TargetAddArgs),
+    /// Remove an existing target
+    #[command(about = "Remove an existing target")]
+    Remove(TargetRemoveArgs),
 }
 
 #[derive(Debug, Args, Getters)]
@@ -80,6 +119,15 @@ pub struct UpdateArgs {
         help = "Force update: skip confirmation, overwrite existing files"
     )]
     pub force: usize,
+
+    /// Max number of depends fetched concurrently
+    #[arg(
+        short = 'j',
+        long = "jobs",
+        default_value = "0",
+        help = "Concurrent fetch limit: 0=default (GALAXY_UPDATE_JOBS or 4)"
+    )]
+    pub jobs: usize,
 }
 impl DfxArgsGetter for UpdateArgs {
     fn debug_level(&self) -> usize {
@@ -105,17 +153,136 @@ pub struct LocalArgs {
     #[arg(long = "log", help = "Log level: error, warn, info, debug, trace")]
     pub log: Option<String>,
 
-    /// Path to values file for localization
+    /// Value files, overrides and environment overlay
+    #[command(flatten)]
+    pub values: ValueArgs,
+    /// Render into memory and print a diff against local/ without writing
+    #[arg(long = "dry-run", visible_alias = "diff", default_value = "false", action = ArgAction::SetTrue, help = "Print a unified diff against local/ instead of writing files")]
+    pub dry_run: bool,
+    /// Fail when templates reference undefined variables
+    #[arg(long = "strict", default_value = "false", action = ArgAction::SetTrue, help = "Fail on undefined template variables, listing file, line and name")]
+    pub strict: bool,
+}
+impl DfxArgsGetter for LocalArgs {
+    fn debug_level(&self) -> usize {
+        self.debug
+    }
+
+    fn log_setting(&self) -> Option<String> {
+        self.log.clone()
+    }
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct PackageArgs {
+    /// Enable debug output with specified level (0-3)
     #[arg(
-        long = "value",
-        help = "Path to YAML/JSON file containing environment-specific values"
+        short = 'd',
+        long = "debug",
+        default_value = "0",
+        help = "Debug level: 0=off, 1=basic, 2=verbose, 3=trace"
     )]
-    pub value: Option<String>,
-    /// Use default values instead of user-provided value.yml
-    #[arg(long = "default", default_value = "false" , action = ArgAction::SetTrue, help = "Use default values instead of user-provided value.yml")]
-    pub use_default_value: bool,
+    pub debug: usize,
+    /// Set logging level and format
+    #[arg(long = "log", help = "Log level: error, warn, info, debug, trace")]
+    pub log: Option<String>,
+
+    /// Output directory of the bundle
+    #[arg(
+        short = 'o',
+        long = "out",
+        default_value = "./",
+        help = "Directory to write name-version.tar.gz into"
+    )]
+    pub out: String,
 }
-impl DfxArgsGetter for LocalArgs {
+impl DfxArgsGetter for PackageArgs {
+    fn debug_level(&self) -> usize {
+        self.debug
+    }
+
+    fn log_setting(&self) -> Option<String> {
+        self.log.clone()
+    }
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct LintArgs {
+    /// Enable debug output with specified level (0-3)
+    #[arg(
+        short = 'd',
+        long = "debug",
+        default_value = "0",
+        help = "Debug level: 0=off, 1=basic, 2=verbose, 3=trace"
+    )]
+    pub debug: usize,
+    /// Set logging level and format
+    #[arg(long = "log", help = "Log level: error, warn, info, debug, trace")]
+    pub log: Option<String>,
+}
+impl DfxArgsGetter for LintArgs {
+    fn debug_level(&self) -> usize {
+        self.debug
+    }
+
+    fn log_setting(&self) -> Option<String> {
+        self.log.clone()
+    }
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct TargetAddArgs {
+    /// Enable debug output with specified level (0-3)
+    #[arg(
+        short = 'd',
+        long = "debug",
+        default_value = "0",
+        help = "Debug level: 0=off, 1=basic, 2=verbose, 3=trace"
+    )]
+    pub debug: usize,
+    /// Set logging level and format
+    #[arg(long = "log", help = "Log level: error, warn, info, debug, trace")]
+    pub log: Option<String>,
+
+    /// Target to add
+    #[arg(help = "Target in arch-os-spc form, e.g. x86-ubt24-k8s")]
+    pub model: String,
+    /// Existing target to clone from
+    #[arg(
+        long = "from",
+        help = "Clone from an existing target instead of the host/k8s template"
+    )]
+    pub from: Option<String>,
+}
+impl DfxArgsGetter for TargetAddArgs {
+    fn debug_level(&self) -> usize {
+        self.debug
+    }
+
+    fn log_setting(&self) -> Option<String> {
+        self.log.clone()
+    }
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct TargetRemoveArgs {
+    /// Enable debug output with specified level (0-3)
+    #[arg(
+        short = 'd',
+        long = "debug",
+        default_value = "0",
+        help = "Debug level: 0=off, 1=basic, 2=verbose, 3=trace"
+    )]
+    pub debug: usize,
+    /// Set logging level and format
+    #[arg(long = "log", help = "Log level: error, warn, info, debug, trace")]
+    pub log: Option<String>,
+
+    /// Target to remove
+    #[arg(help = "Target in arch-os-spc form, e.g. x86-ubt24-k8s")]
+    pub model: String,
+}
+impl DfxArgsGetter for TargetRemoveArgs {
     fn debug_level(&self) -> usize {
         self.debug
     }
@@ -134,3 +301,30 @@ impl DfxArgsGetter for SpecArgs {
         self.log.clone()
     }
 }
+
+#[derive(Debug, Args, Getters)]
+pub struct SettingArgs {
+    /// Enable debug output with specified level (0-3)
+    #[arg(
+        short = 'd',
+        long = "debug",
+        default_value = "0",
+        help = "Debug level: 0=off, 1=basic, 2=verbose, 3=trace"
+    )]
+    pub debug: usize,
+    /// Set logging level and format
+    #[arg(long = "log", help = "Log level: error, warn, info, debug, trace")]
+    pub log: Option<String>,
+    /// Prompt for every variable, including ones that already have a value
+    #[arg(long = "all", default_value = "false", action = ArgAction::SetTrue, help = "Also prompt for variables that already have a value")]
+    pub all: bool,
+}
+impl DfxArgsGetter for SettingArgs {
+    fn debug_level(&self) -> usize {
+        self.debug
+    }
+
+    fn log_setting(&self) -> Option<String> {
+        self.log.clone()
+    }
+}
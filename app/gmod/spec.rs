@@ -1,14 +1,16 @@
-use galaxy_ops::error::MainResult;
+use galaxy_ops::error::{MainReason, MainResult, ModReason, ToErr};
 use galaxy_ops::infra::configure_dfx_logging;
+use galaxy_ops::module::ModelSTD;
+use galaxy_ops::module::lint::lint_module;
 use galaxy_ops::module::proj::ModProject;
-use galaxy_ops::module::spec::make_mod_spec_example;
-use galaxy_ops::project::load_project_global_value;
-use galaxy_ops::types::{Localizable, LocalizeOptions};
+use galaxy_ops::module::spec::{TargetSource, make_mod_spec_example};
+use galaxy_ops::types::Localizable;
 use orion_common::serde::Persistable;
 use orion_error::{ErrorConv, ErrorOwe};
 use orion_variate::update::UpdateOptions;
 use orion_variate::vars::ValueDict;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use crate::args::{self};
 
@@ -30,16 +32,67 @@ pub async fn do_mod_cmd(cmd: args::GxModCmd) -> MainResult<()> {
             configure_dfx_logging(&dfx);
             let spec = ModProject::load(&current_dir).err_conv()?;
             let options = UpdateOptions::from((dfx.force, ValueDict::default()));
-            spec.update(&options).await.err_conv()?;
+            spec.update(&options, dfx.jobs).await.err_conv()?;
         }
         args::GxModCmd::Localize(args) => {
             configure_dfx_logging(&args);
             let spec = ModProject::load(&current_dir).err_conv()?;
-            let dict = load_project_global_value(spec.root_local(), args.value())?;
-            spec.localize(None, LocalizeOptions::new(dict, args.use_default_value))
-                .await
+            let options = args
+                .values
+                .localize_options(spec.root_local())?
+                .with_dry_run(args.dry_run)
+                .with_strict(args.strict);
+            spec.localize(None, options).await.err_conv()?;
+        }
+        args::GxModCmd::Package(args) => {
+            configure_dfx_logging(&args);
+            let spec = ModProject::load(&current_dir).err_conv()?;
+            let pkg_path = spec.package(&current_dir.join(args.out())).err_conv()?;
+            println!("package: {}", pkg_path.display());
+        }
+        args::GxModCmd::Lint(args) => {
+            configure_dfx_logging(&args);
+            let report = lint_module(&current_dir)?;
+            report.print();
+            if report.error_count() > 0 {
+                return MainReason::from(ModReason::Lint(format!(
+                    "{} error(s)",
+                    report.error_count()
+                )))
+                .err_result();
+            }
+        }
+        args::GxModCmd::Setting(args) => {
+            configure_dfx_logging(&args);
+            let spec = ModProject::load(&current_dir).err_conv()?;
+            spec.ia_setting(args.all)?;
+        }
+        args::GxModCmd::Target(args::TargetCmd::Add(args)) => {
+            configure_dfx_logging(&args);
+            let spec = ModProject::load(&current_dir).err_conv()?;
+            let mut mod_spec = spec.mod_spec().clone();
+            let source = match args.from() {
+                Some(from) => TargetSource::Clone(parse_model(from)?),
+                None => TargetSource::Template,
+            };
+            mod_spec
+                .add_target(parse_model(args.model())?, source)
+                .err_conv()?;
+        }
+        args::GxModCmd::Target(args::TargetCmd::Remove(args)) => {
+            configure_dfx_logging(&args);
+            let spec = ModProject::load(&current_dir).err_conv()?;
+            let mut mod_spec = spec.mod_spec().clone();
+            mod_spec
+                .remove_target(&parse_model(args.model())?)
                 .err_conv()?;
         }
     }
     Ok(())
 }
+
+fn parse_model(model: &str) -> MainResult<ModelSTD> {
+    ModelSTD::from_str(model).map_err(|e| {
+        MainReason::from(ModReason::Target(format!("bad target {model}: {e}"))).to_err()
+    })
+}
@@ -1,6 +1,7 @@
-use clap::{ArgAction, Args, Parser};
+use clap::{ArgAction, Args, Parser, Subcommand};
 use derive_getters::Getters;
 use galaxy_ops::infra::DfxArgsGetter;
+use galaxy_ops::project::ValueArgs;
 
 #[derive(Debug, Parser)] // requires `derive` feature
 #[command(name = "gops")]
@@ -32,10 +33,165 @@ pub enum GInsCmd {
     ///
     /// 管理系统级别的配置设置
     Setting(SettingArgs),
+    /// 加密值管理
+    ///
+    /// 在 value 文件中写入/读取加密项, 或更换密钥
+    #[command(subcommand)]
+    Secret(SecretCmd),
+    /// 系统值管理
+    ///
+    /// 非交互地读取/写入/删除 value 文件中的变量, 变量须在 sys/vars.yml 中声明
+    #[command(subcommand)]
+    Value(ValueCmd),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ValueCmd {
+    /// 读取变量取值
+    Get(ValueGetArgs),
+    /// 写入变量取值
+    ///
+    /// 数字与布尔按类型解析, --string 时始终作为字符串
+    /// 类型须与声明一致, sensitive 变量以项目密钥加密后写入
+    Set(ValueSetArgs),
+    /// 删除变量取值
+    Unset(ValueUnsetArgs),
+    /// 列出变量
+    ///
+    /// 输出 sys/vars.yml 声明的变量及其状态: set/default/undeclared
+    List(ValueListArgs),
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct ValueGetArgs {
+    /// 变量名
+    #[arg(help = "变量名")]
+    pub key: String,
+    /// 系统名称
+    ///
+    /// 读取 values/<sys>/value.yml, 省略时读取项目全局 values/value.yml
+    #[arg(short = 's', long = "sys")]
+    pub sys: Option<String>,
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct ValueSetArgs {
+    /// 变量名
+    #[arg(help = "变量名")]
+    pub key: String,
+    /// 变量值
+    #[arg(help = "变量值")]
+    pub value: String,
+    /// 按字符串处理
+    #[arg(long = "string", default_value = "false", action = ArgAction::SetTrue, help = "值始终作为字符串")]
+    pub string: bool,
+    /// 系统名称
+    ///
+    /// 写入 values/<sys>/value.yml 并按该系统的 sys/vars.yml 校验,
+    /// 省略时写入项目全局 values/value.yml 并按全部系统的声明校验
+    #[arg(short = 's', long = "sys")]
+    pub sys: Option<String>,
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct ValueUnsetArgs {
+    /// 变量名
+    #[arg(help = "变量名")]
+    pub key: String,
+    /// 系统名称
+    ///
+    /// 删除 values/<sys>/value.yml 中的取值, 省略时删除项目全局 values/value.yml 中的取值
+    #[arg(short = 's', long = "sys")]
+    pub sys: Option<String>,
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct ValueListArgs {
+    /// 系统名称
+    ///
+    /// 列出该系统的变量, 省略时列出全部系统的变量与项目全局取值
+    #[arg(short = 's', long = "sys")]
+    pub sys: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SecretCmd {
+    /// 写入加密项
+    ///
+    /// 使用项目密钥(.secret.key)或口令(GALAXY_SECRET_PASS)加密后写入 value 文件
+    Set(SecretSetArgs),
+    /// 读取并解密加密项
+    Get(SecretGetArgs),
+    /// 更换密钥
+    ///
+    /// 生成新的项目密钥(或使用 GALAXY_SECRET_NEW_PASS 口令), 重新加密 values/ 下的全部加密项
+    Rotate(SecretRotateArgs),
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct SecretSetArgs {
+    /// 变量名
+    #[arg(help = "变量名")]
+    pub key: String,
+    /// 明文值
+    ///
+    /// 省略时从终端以密码方式输入
+    #[arg(help = "明文值, 省略时交互输入")]
+    pub value: Option<String>,
+    /// 系统名称
+    ///
+    /// 写入 values/<sys>/value.yml, 省略时写入项目全局 values/value.yml
+    #[arg(short = 's', long = "sys")]
+    pub sys: Option<String>,
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct SecretGetArgs {
+    /// 变量名
+    #[arg(help = "变量名")]
+    pub key: String,
+    /// 系统名称
+    ///
+    /// 读取 values/<sys>/value.yml, 省略时读取项目全局 values/value.yml
+    #[arg(short = 's', long = "sys")]
+    pub sys: Option<String>,
+}
+
+#[derive(Debug, Args, Getters)]
+pub struct SecretRotateArgs {
+    /// 调试输出级别
+    ///
+    /// 设置调试信息的详细程度：
+    /// - 0: 无调试输出
+    /// - 1: 基础调试信息
+    /// - 2: 详细调试信息
+    /// - 3: 完整调试信息
+    #[arg(short = 'd', long = "debug", default_value = "0")]
+    pub debug: usize,
+    /// 日志配置
+    ///
+    /// 配置日志输出格式和级别，格式：模块=级别,模块=级别
+    /// 例如：--log cmd=debug,parse=info
+    #[arg(long = "log")]
+    pub log: Option<String>,
+}
+impl DfxArgsGetter for SecretRotateArgs {
+    fn debug_level(&self) -> usize {
+        self.debug
+    }
+
+    fn log_setting(&self) -> Option<String> {
+        self.log.clone()
+    }
 }
 
 #[derive(Debug, Args, Getters)]
 pub struct SettingArgs {
+    /// 设定全部变量
+    ///
+    /// 默认跳过 value 文件中已有取值的变量
+    #[arg(long = "all", default_value = "false", action = ArgAction::SetTrue, help = "包括已有取值的变量")]
+    pub all: bool,
     /// 调试输出级别
     ///
     /// 设置调试信息的详细程度：
@@ -98,6 +254,18 @@ pub struct UpdateArgs {
     /// - 3: 强制更新所有内容
     #[arg(short = 'f', long = "force", default_value = "0")]
     pub force: usize,
+
+    /// 并发更新数
+    ///
+    /// 同时拉取的模块/依赖数量, 0 表示默认值(GALAXY_UPDATE_JOBS 或 4)
+    #[arg(short = 'j', long = "jobs", default_value = "0")]
+    pub jobs: usize,
+
+    /// 锁定模式
+    ///
+    /// 拉取结果须与 ops-lock.yml 一致, 不一致时失败且不改写锁文件
+    #[arg(long = "locked", default_value = "false", action = ArgAction::SetTrue)]
+    pub locked: bool,
 }
 
 impl DfxArgsGetter for UpdateArgs {
@@ -173,18 +341,20 @@ pub struct LocalArgs {
     #[arg(long = "log")]
     pub log: Option<String>,
 
-    /// 值文件路径
-    ///
-    /// 指定用于本地化的值文件路径，通常为YAML格式
-    /// 例如：--value cicd_value.yml
-    #[arg(long = "value", help = "本地化值文件路径")]
-    pub value: Option<String>,
+    /// 值文件、覆盖值与环境覆盖层
+    #[command(flatten)]
+    pub values: ValueArgs,
 
-    /// 使用默认模块配置
+    /// 预览本地化结果
+    ///
+    /// 仅在内存中渲染, 输出与 local/ 目录现有文件的差异(diff), 不修改磁盘
+    #[arg(long = "dry-run", visible_alias = "diff", default_value = "false", action = ArgAction::SetTrue, help = "预览本地化差异, 不写入文件")]
+    pub dry_run: bool,
+    /// 严格模式
     ///
-    /// 启用默认模块模式，不使用用户自定义的value.yml文件
-    #[arg(long = "default", default_value = "false" , action = ArgAction::SetTrue, help = "使用默认模块配置")]
-    pub use_default_value: bool,
+    /// 模板引用未定义的变量时报错, 列出文件、行号与变量名
+    #[arg(long = "strict", default_value = "false", action = ArgAction::SetTrue, help = "未定义变量时报错")]
+    pub strict: bool,
 }
 impl DfxArgsGetter for LocalArgs {
     fn debug_level(&self) -> usize {
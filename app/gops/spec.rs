@@ -1,12 +1,18 @@
-use galaxy_ops::error::MainResult;
+use std::path::{Path, PathBuf};
+
+use galaxy_ops::const_vars::{SECRET_KEY_FILE, SECRET_NEW_PASS_ENV, SECRET_PASS_ENV};
+use galaxy_ops::error::{LocalizeReason, MainReason, MainResult, OpsReason, ToErr};
 use galaxy_ops::infra::configure_dfx_logging;
 use galaxy_ops::ops_prj::proj::OpsProject;
-use orion_error::{ErrorConv, ErrorOwe};
+use galaxy_ops::project::parse_set_value;
+use galaxy_ops::secret::{SecretKey, get_secret, rotate_files, set_secret};
+use galaxy_ops::types::Localizable;
+use orion_error::{ErrorConv, ErrorOwe, ErrorWith};
 use orion_infra::path::make_new_path;
 use orion_variate::update::UpdateOptions;
-use orion_variate::vars::ValueDict;
+use orion_variate::vars::{ValueDict, ValueType};
 
-use crate::args::GInsCmd;
+use crate::args::{GInsCmd, SecretCmd, ValueCmd};
 
 pub async fn do_ins_cmd(cmd: GInsCmd) -> MainResult<()> {
     let current_dir = std::env::current_dir().expect("无法获取当前目录");
@@ -27,24 +33,169 @@ pub async fn do_ins_cmd(cmd: GInsCmd) -> MainResult<()> {
             configure_dfx_logging(&dfx);
             let options = UpdateOptions::from((dfx.force, ValueDict::default()));
             let spec = OpsProject::load(&current_dir).err_conv()?;
-            spec.update(&options).await.err_conv()?;
+            spec.update_lock(&options, dfx.locked, dfx.jobs)
+                .await
+                .err_conv()?;
         }
-        GInsCmd::Localize(_args) => {
-            todo!();
-            /*
+        GInsCmd::Localize(args) => {
             configure_dfx_logging(&args);
             let spec = OpsProject::load(&current_dir).err_conv()?;
-            let dict = load_project_global_value(spec.root_local(), args.value())?;
-            spec.localize(LocalizeOptions::new(dict, args.use_default_value))
-                .await
-                .err_conv()?;
-            */
+            let options = args
+                .values
+                .localize_options(spec.root_local())?
+                .with_dry_run(args.dry_run)
+                .with_strict(args.strict);
+            spec.localize(None, options).await.err_conv()?;
         }
         GInsCmd::Setting(args) => {
             configure_dfx_logging(&args);
             let spec = OpsProject::load(&current_dir).err_conv()?;
-            spec.ia_setting()?;
+            spec.ia_setting(args.all)?;
+        }
+        GInsCmd::Secret(SecretCmd::Set(args)) => {
+            let spec = OpsProject::load(&current_dir).err_conv()?;
+            let key = match SecretKey::load(spec.root_local())? {
+                Some(key) => key,
+                None => {
+                    let key_file = spec.root_local().join(SECRET_KEY_FILE);
+                    println!("generate secret key: {}", key_file.display());
+                    SecretKey::generate(&key_file)?
+                }
+            };
+            let plain = match args.value() {
+                Some(value) => value.clone(),
+                None => inquire::Password::new(args.key())
+                    .without_confirmation()
+                    .prompt()
+                    .owe_data()?,
+            };
+            let value_file = secret_value_file(&spec, args.sys());
+            if let Some(parent) = value_file.parent() {
+                std::fs::create_dir_all(parent).owe_sys().with(parent)?;
+            }
+            set_secret(&value_file, args.key(), plain.as_str(), &key)?;
+            println!("set {} in {}", args.key(), value_file.display());
+        }
+        GInsCmd::Secret(SecretCmd::Get(args)) => {
+            let spec = OpsProject::load(&current_dir).err_conv()?;
+            let key = require_secret(spec.root_local())?;
+            let value_file = secret_value_file(&spec, args.sys());
+            match get_secret(&value_file, args.key(), &key)? {
+                Some(plain) => println!("{plain}"),
+                None => {
+                    return MainReason::from(LocalizeReason::Secret(format!(
+                        "{} not found in {}",
+                        args.key(),
+                        value_file.display()
+                    )))
+                    .err_result();
+                }
+            }
+        }
+        GInsCmd::Secret(SecretCmd::Rotate(args)) => {
+            configure_dfx_logging(&args);
+            let spec = OpsProject::load(&current_dir).err_conv()?;
+            let old = require_secret(spec.root_local())?;
+            let key_file = spec.root_local().join(SECRET_KEY_FILE);
+            let new_file = key_file.with_extension("new");
+            let new = match std::env::var(SECRET_NEW_PASS_ENV) {
+                Ok(pass) if !pass.is_empty() => SecretKey::from_pass(pass),
+                _ => SecretKey::generate(&new_file)?,
+            };
+            // 暂存失败时原文件未改动, 可以丢弃新密钥
+            let rotation = match rotate_files(&spec.value_files()?, &old, &new) {
+                Ok(rotation) => rotation,
+                Err(e) => {
+                    let _ = std::fs::remove_file(&new_file);
+                    return Err(e);
+                }
+            };
+            // 替换中途失败时部分文件已是新密钥加密, 保留新密钥
+            let count = match rotation.commit() {
+                Ok(count) => count,
+                Err(e) => {
+                    if new_file.exists() {
+                        println!("new secret key kept at {}", new_file.display());
+                    }
+                    return Err(e);
+                }
+            };
+            if new_file.exists() {
+                std::fs::rename(&new_file, &key_file)
+                    .owe_sys()
+                    .with(&key_file)?;
+                println!("new secret key: {}", key_file.display());
+            } else {
+                println!("use new passphrase from {SECRET_NEW_PASS_ENV} as {SECRET_PASS_ENV}");
+            }
+            println!("rotated {count} secret(s)");
+        }
+        GInsCmd::Value(ValueCmd::Get(args)) => {
+            let spec = OpsProject::load(&current_dir).err_conv()?;
+            let sys = args.sys().as_deref();
+            match spec.value_get(sys, args.key())? {
+                Some(value) => match serde_json::to_value(&value).owe_data()? {
+                    serde_json::Value::String(text) => println!("{text}"),
+                    other => println!("{other}"),
+                },
+                None => {
+                    return MainReason::from(OpsReason::Value(format!(
+                        "{} not set in {}",
+                        args.key(),
+                        spec.target_value_file(sys)?.display()
+                    )))
+                    .err_result();
+                }
+            }
+        }
+        GInsCmd::Value(ValueCmd::Set(args)) => {
+            let spec = OpsProject::load(&current_dir).err_conv()?;
+            let sys = args.sys().as_deref();
+            let value = if args.string {
+                ValueType::from(args.value().as_str())
+            } else {
+                serde_json::from_value(parse_set_value(args.value())).owe_data()?
+            };
+            spec.value_set(sys, args.key(), value)?;
+            println!(
+                "set {} in {}",
+                args.key(),
+                spec.target_value_file(sys)?.display()
+            );
+        }
+        GInsCmd::Value(ValueCmd::Unset(args)) => {
+            let spec = OpsProject::load(&current_dir).err_conv()?;
+            let sys = args.sys().as_deref();
+            let value_file = spec.target_value_file(sys)?;
+            if spec.value_unset(sys, args.key())? {
+                println!("unset {} in {}", args.key(), value_file.display());
+            } else {
+                println!("{} not set in {}", args.key(), value_file.display());
+            }
+        }
+        GInsCmd::Value(ValueCmd::List(args)) => {
+            let spec = OpsProject::load(&current_dir).err_conv()?;
+            for status in spec.value_list(args.sys().as_deref())? {
+                println!("{status}");
+            }
         }
     }
     Ok(())
 }
+
+// --sys 指定时为 values/<sys>/value.yml, 否则为项目全局 values/value.yml
+fn secret_value_file(spec: &OpsProject, sys: &Option<String>) -> PathBuf {
+    match sys {
+        Some(sys) => spec.value_path().join(sys).value_file(),
+        None => spec.value_path().value_file(),
+    }
+}
+
+fn require_secret(root: &Path) -> MainResult<SecretKey> {
+    SecretKey::load(root)?.ok_or_else(|| {
+        MainReason::from(LocalizeReason::Secret(format!(
+            "no secret key, set {SECRET_PASS_ENV} or provide {SECRET_KEY_FILE}"
+        )))
+        .to_err()
+    })
+}
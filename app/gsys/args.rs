@@ -1,6 +1,7 @@
 use clap::{ArgAction, Parser};
 use derive_getters::Getters;
 use galaxy_ops::infra::DfxArgsGetter;
+use galaxy_ops::project::ValueArgs;
 
 #[derive(Debug, Parser)] // requires `derive` feature
 #[command(name = "gsys")]
@@ -28,6 +29,19 @@ pub enum GSysCmd {
         long_about = "Generate localized configuration files for the system based on environment-specific values. Useful for adapting system configurations to different deployment environments."
     )]
     Localize(LocalArgs),
+    /// Explain where each module value comes from
+    #[command(
+        name = "explain-value",
+        about = "Explain value provenance",
+        long_about = "Print, for each module and variable, the final value, the layer that won (global, mod-cust, mod-default), the shadowed layers and the ${VAR} references expanded during evaluation."
+    )]
+    ExplainValue(ExplainArgs),
+    /// Interactively set variable values
+    #[command(
+        about = "Interactively set variable values",
+        long_about = "Prompt for each variable in sys/vars.yml by type: a list for enums, yes/no for booleans, validated input for numbers and a hidden prompt for sensitive or encrypted values. Variables already set in values/value.yml are skipped unless --all is given. A summary of changes is shown before saving."
+    )]
+    Setting(SettingArgs),
 }
 
 #[derive(Debug, Args, Getters)]
@@ -66,6 +80,19 @@ pub struct UpdateArgs {
         help = "Force update: 0=normal, 1=skip confirmation, 2=overwrite files, 3=force git pull"
     )]
     pub force: usize,
+
+    /// Max number of modules/depends fetched concurrently
+    #[arg(
+        short = 'j',
+        long = "jobs",
+        default_value = "0",
+        help = "Concurrent fetch limit: 0=default (GALAXY_UPDATE_JOBS or 4)"
+    )]
+    pub jobs: usize,
+
+    /// Require fetched revisions to match sys-lock.yml
+    #[arg(long = "locked", default_value = "false", action = ArgAction::SetTrue, help = "Fail if resolved revisions differ from sys-lock.yml instead of rewriting it")]
+    pub locked: bool,
 }
 impl DfxArgsGetter for UpdateArgs {
     fn debug_level(&self) -> usize {
@@ -94,17 +121,64 @@ pub struct LocalArgs {
     )]
     pub log: Option<String>,
 
-    /// Path to values file for localization
+    /// Value files, overrides and environment overlay
+    #[command(flatten)]
+    pub values: ValueArgs,
+
+    /// Render into memory and print a diff against local/ without writing
+    #[arg(long = "dry-run", visible_alias = "diff", default_value = "false", action = ArgAction::SetTrue, help = "Print a unified diff against local/ instead of writing files")]
+    pub dry_run: bool,
+    /// Fail when templates reference undefined variables
+    #[arg(long = "strict", default_value = "false", action = ArgAction::SetTrue, help = "Fail on undefined template variables, listing file, line and name")]
+    pub strict: bool,
+}
+#[derive(Debug, Args, Getters)]
+pub struct ExplainArgs {
+    /// Enable debug output with specified level (0-4)
+    #[arg(
+        short = 'd',
+        long = "debug",
+        default_value = "0",
+        help = "Debug level: 0=off, 1=basic, 2=verbose, 3=trace, 4=full"
+    )]
+    pub debug: usize,
+    /// Configure logging output format and levels
+    #[arg(
+        long = "log",
+        help = "Configure logging: eg --log cmd=debug,parse=info"
+    )]
+    pub log: Option<String>,
+
+    /// Value files, overrides and environment overlay
+    #[command(flatten)]
+    pub values: ValueArgs,
+
+    /// Only explain the given module
     #[arg(
-        long = "value",
-        help = "Path to YAML/JSON file containing environment-specific values"
+        short = 'm',
+        long = "module",
+        help = "Module name to explain (default: all modules)"
     )]
-    pub value: Option<String>,
+    pub module: Option<String>,
+
+    /// Only explain the given variable
+    #[arg(
+        short = 'k',
+        long = "key",
+        help = "Variable name to explain (default: all variables)"
+    )]
+    pub key: Option<String>,
+}
+impl DfxArgsGetter for ExplainArgs {
+    fn debug_level(&self) -> usize {
+        self.debug
+    }
 
-    /// Use default values instead of user-provided value.yml
-    #[arg(long = "default", default_value = "false" , action = ArgAction::SetTrue, help = "Use built-in default values instead of user-provided value.yml")]
-    pub use_default_value: bool,
+    fn log_setting(&self) -> Option<String> {
+        self.log.clone()
+    }
 }
+
 impl DfxArgsGetter for LocalArgs {
     fn debug_level(&self) -> usize {
         self.debug
@@ -114,3 +188,33 @@ impl DfxArgsGetter for LocalArgs {
         self.log.clone()
     }
 }
+
+#[derive(Debug, Args, Getters)]
+pub struct SettingArgs {
+    /// Enable debug output with specified level (0-4)
+    #[arg(
+        short = 'd',
+        long = "debug",
+        default_value = "0",
+        help = "Debug level: 0=off, 1=basic, 2=verbose, 3=trace, 4=full"
+    )]
+    pub debug: usize,
+    /// Configure logging output format and levels
+    #[arg(
+        long = "log",
+        help = "Configure logging: eg --log cmd=debug,parse=info"
+    )]
+    pub log: Option<String>,
+    /// Prompt for every variable, including ones that already have a value
+    #[arg(long = "all", default_value = "false", action = ArgAction::SetTrue, help = "Also prompt for variables that already have a value")]
+    pub all: bool,
+}
+impl DfxArgsGetter for SettingArgs {
+    fn debug_level(&self) -> usize {
+        self.debug
+    }
+
+    fn log_setting(&self) -> Option<String> {
+        self.log.clone()
+    }
+}
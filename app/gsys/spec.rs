@@ -5,9 +5,7 @@ use inquire::Select;
 use orion_error::{ErrorConv, ErrorOwe};
 use orion_infra::path::make_new_path;
 
-use galaxy_ops::project::load_project_global_value;
 use galaxy_ops::system::proj::SysProject;
-use galaxy_ops::types::LocalizeOptions;
 use orion_variate::update::UpdateOptions;
 use orion_variate::vars::ValueDict;
 
@@ -52,15 +50,37 @@ pub async fn do_sys_cmd(cmd: GSysCmd) -> MainResult<()> {
             configure_dfx_logging(&dfx);
             let options = UpdateOptions::from((dfx.force, ValueDict::default()));
             let spec = SysProject::load(&current_dir).err_conv()?;
-            spec.update(&options).await.err_conv()?;
+            spec.update_lock(&options, dfx.locked, dfx.jobs)
+                .await
+                .err_conv()?;
         }
         GSysCmd::Localize(args) => {
             configure_dfx_logging(&args);
             let spec = SysProject::load(&current_dir).err_conv()?;
-            let dict = load_project_global_value(spec.root_local(), args.value())?;
-            spec.localize(LocalizeOptions::new(dict, args.use_default_value))
-                .await
-                .err_conv()?;
+            let options = args
+                .values
+                .localize_options(spec.root_local())?
+                .with_dry_run(args.dry_run)
+                .with_strict(args.strict);
+            spec.localize(options).await.err_conv()?;
+        }
+        GSysCmd::ExplainValue(args) => {
+            configure_dfx_logging(&args);
+            let spec = SysProject::load(&current_dir).err_conv()?;
+            let options = args.values.localize_options(spec.root_local())?;
+            let explains = spec.explain_value(options, args.module().as_deref())?;
+            for (module, items) in explains {
+                println!("[{module}]");
+                items
+                    .iter()
+                    .filter(|x| args.key().as_ref().is_none_or(|k| k == x.key()))
+                    .for_each(|x| print!("{x}"));
+            }
+        }
+        GSysCmd::Setting(args) => {
+            configure_dfx_logging(&args);
+            let spec = SysProject::load(&current_dir).err_conv()?;
+            spec.ia_setting(args.all)?;
         }
     }
     Ok(())
@@ -1,6 +1,18 @@
+use std::path::{Path, PathBuf};
+
 use derive_getters::Getters;
-use orion_variate::addr::AddrType;
+use derive_more::Deref;
+use log::debug;
+use md5::Md5;
+use orion_error::{ErrorOwe, ErrorWith};
+use orion_variate::{addr::AddrType, ext::Artifact, update::UpdateOptions};
 use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    const_vars::ARTIFACT_CACHE_ENV,
+    error::{MainError, MainReason, MainResult, ModReason, ToErr},
+};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum OsType {
@@ -19,3 +31,288 @@ pub struct BinPackage {
     cep: String,
     addr: AddrType,
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestKind {
+    Sha256,
+    Md5,
+}
+
+// 制品摘要声明, 以 artifact 的本地文件名(local)匹配
+#[derive(Getters, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ArtifactDigest {
+    local: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    md5: Option<String>,
+}
+
+impl ArtifactDigest {
+    pub fn new<S: Into<String>>(local: S) -> Self {
+        Self {
+            local: local.into(),
+            sha256: None,
+            md5: None,
+        }
+    }
+    pub fn with_sha256<S: Into<String>>(mut self, digest: S) -> Self {
+        self.sha256 = Some(digest.into().to_lowercase());
+        self
+    }
+    pub fn with_md5<S: Into<String>>(mut self, digest: S) -> Self {
+        self.md5 = Some(digest.into().to_lowercase());
+        self
+    }
+    // 摘要须为小写十六进制: sha256 64 位, md5 32 位; 摘要会拼入缓存路径
+    pub fn validate(&self) -> MainResult<()> {
+        for (kind, digest, len) in [("sha256", &self.sha256, 64), ("md5", &self.md5, 32)] {
+            let Some(digest) = digest else {
+                continue;
+            };
+            let is_hex = digest
+                .chars()
+                .all(|x| x.is_ascii_digit() || ('a'..='f').contains(&x));
+            if digest.len() != len || !is_hex {
+                return Err(MainError::from_conf(format!(
+                    "artifact {} bad {kind} digest: {digest}, want {len} lowercase hex chars",
+                    self.local
+                )));
+            }
+        }
+        Ok(())
+    }
+    // 缓存键, 优先使用 sha256
+    pub fn cache_key(&self) -> MainResult<Option<PathBuf>> {
+        self.validate()?;
+        if let Some(sha256) = &self.sha256 {
+            return Ok(Some(PathBuf::from("sha256").join(sha256)));
+        }
+        Ok(self.md5.as_ref().map(|x| PathBuf::from("md5").join(x)))
+    }
+
+    pub fn verify(&self, path: &Path) -> MainResult<()> {
+        let expects = [
+            (DigestKind::Sha256, &self.sha256),
+            (DigestKind::Md5, &self.md5),
+        ];
+        for (kind, expect) in expects {
+            if let Some(expect) = expect {
+                let actual = file_digest(path, kind)?;
+                if !actual.eq_ignore_ascii_case(expect) {
+                    return MainReason::from(ModReason::Artifact(format!(
+                        "{} {kind:?} mismatch, expect {expect}, actual {actual}",
+                        path.display()
+                    )))
+                    .err_result();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Deref)]
+#[serde(transparent)]
+pub struct ArtifactDigests {
+    items: Vec<ArtifactDigest>,
+}
+
+impl From<Vec<ArtifactDigest>> for ArtifactDigests {
+    fn from(items: Vec<ArtifactDigest>) -> Self {
+        Self { items }
+    }
+}
+
+impl ArtifactDigests {
+    pub fn find(&self, local: &str) -> Option<&ArtifactDigest> {
+        self.items.iter().find(|x| x.local == local)
+    }
+    pub fn validate(&self) -> MainResult<()> {
+        self.items.iter().try_for_each(|x| x.validate())
+    }
+}
+
+pub fn file_digest(path: &Path, kind: DigestKind) -> MainResult<String> {
+    let mut file = std::fs::File::open(path).owe_sys().with(path)?;
+    let bytes = match kind {
+        DigestKind::Sha256 => {
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut file, &mut hasher).owe_sys().with(path)?;
+            hasher.finalize().to_vec()
+        }
+        DigestKind::Md5 => {
+            let mut hasher = Md5::new();
+            std::io::copy(&mut file, &mut hasher).owe_sys().with(path)?;
+            hasher.finalize().to_vec()
+        }
+    };
+    Ok(bytes.iter().map(|x| format!("{x:02x}")).collect())
+}
+
+// 以摘要为键的本地制品缓存, 跨系统/跨 update 复用下载结果
+#[derive(Getters, Clone, Debug)]
+pub struct ArtifactCache {
+    root: PathBuf,
+}
+
+impl ArtifactCache {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+    pub fn from_env() -> Self {
+        let root = std::env::var(ARTIFACT_CACHE_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| {
+                home::home_dir()
+                    .unwrap_or_else(std::env::temp_dir)
+                    .join(".galaxy/cache/artifacts")
+            });
+        Self { root }
+    }
+    pub fn lookup(&self, digest: &ArtifactDigest) -> MainResult<Option<PathBuf>> {
+        let Some(key) = digest.cache_key()? else {
+            return Ok(None);
+        };
+        let path = self.root.join(key);
+        if path.is_file() && digest.verify(&path).is_ok() {
+            return Ok(Some(path));
+        }
+        Ok(None)
+    }
+    pub fn store(&self, digest: &ArtifactDigest, file: &Path) -> MainResult<()> {
+        if let Some(key) = digest.cache_key()? {
+            let path = self.root.join(key);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).owe_sys().with(parent)?;
+            }
+            std::fs::copy(file, &path).owe_sys().with(&path)?;
+        }
+        Ok(())
+    }
+}
+
+// artifact 的本地文件名与下载地址
+pub fn artifact_source(art: &Artifact) -> MainResult<(String, AddrType)> {
+    let value = serde_json::to_value(art).owe_data()?;
+    let local = value
+        .get("local")
+        .and_then(|x| x.as_str())
+        .map(String::from);
+    let addr = value
+        .get("addr")
+        .cloned()
+        .and_then(|x| serde_json::from_value::<AddrType>(x).ok());
+    match (local, addr) {
+        (Some(local), Some(addr)) => Ok((local, addr)),
+        _ => MainReason::from(ModReason::Artifact(format!("bad artifact: {value}"))).err_result(),
+    }
+}
+
+// 获取带摘要声明的制品: 本地已有 > 缓存命中 > 下载并校验后写入缓存
+pub async fn fetch_artifact(
+    art: &Artifact,
+    digest: &ArtifactDigest,
+    dst_dir: &Path,
+    cache: &ArtifactCache,
+    options: &UpdateOptions,
+) -> MainResult<PathBuf> {
+    digest.validate()?;
+    let (local, addr) = artifact_source(art)?;
+    let dst = dst_dir.join(&local);
+    if dst.is_file() && digest.verify(&dst).is_ok() {
+        return Ok(dst);
+    }
+    std::fs::create_dir_all(dst_dir).owe_sys().with(dst_dir)?;
+    if let Some(cached) = cache.lookup(digest)? {
+        debug!(target: "mod/artifact", "use cache {} for {}", cached.display(), local);
+        std::fs::copy(&cached, &dst).owe_sys().with(&dst)?;
+        return Ok(dst);
+    }
+    let unit = addr
+        .update_local_rename(dst_dir, local.as_str(), options)
+        .await
+        .owe(MainReason::from(ModReason::Update))
+        .with(&dst)?;
+    if let Err(e) = digest.verify(unit.position()) {
+        std::fs::remove_file(unit.position()).owe_sys()?;
+        return Err(e);
+    }
+    cache.store(digest, unit.position())?;
+    Ok(unit.position().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use orion_variate::{addr::LocalAddr, ext::Artifact};
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_digest_verify() {
+        let tmp_dir = tempdir().unwrap();
+        let file = tmp_dir.path().join("a.tar.gz");
+        std::fs::write(&file, "hello").unwrap();
+        let digest = ArtifactDigest::new("a.tar.gz")
+            .with_sha256("2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824")
+            .with_md5("5d41402abc4b2a76b9719d911017c592");
+        assert!(digest.verify(&file).is_ok());
+
+        let bad = ArtifactDigest::new("a.tar.gz").with_md5("00000000000000000000000000000000");
+        assert!(bad.verify(&file).is_err());
+    }
+
+    #[test]
+    fn test_digest_validate() {
+        let ok = ArtifactDigest::new("a.tar.gz").with_md5("5d41402abc4b2a76b9719d911017c592");
+        assert_eq!(
+            ok.cache_key().unwrap(),
+            Some(PathBuf::from("md5/5d41402abc4b2a76b9719d911017c592"))
+        );
+        let digests: ArtifactDigests = serde_yaml::from_str(
+            "- local: a.tar.gz\n  sha256: ../../x\n- local: b.tar.gz\n  md5: 5D41402ABC4B2A76B9719D911017C592",
+        )
+        .unwrap();
+        assert!(digests.validate().is_err());
+        for digest in digests.iter() {
+            assert!(digest.validate().is_err());
+            assert!(digest.cache_key().is_err());
+        }
+        let cache = ArtifactCache::new(PathBuf::from("/tmp/cache"));
+        assert!(cache.lookup(&digests[0]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_use_cache() {
+        let tmp_dir = tempdir().unwrap();
+        let src = tmp_dir.path().join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("pkg.txt"), "hello").unwrap();
+        let art = Artifact::new(
+            "pkg",
+            "0.1.0",
+            LocalAddr::from(src.join("pkg.txt").display().to_string()),
+            "pkg.txt",
+        );
+        let digest = ArtifactDigest::new("pkg.txt")
+            .with_sha256("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+        let cache = ArtifactCache::new(tmp_dir.path().join("cache"));
+        let options = UpdateOptions::for_test();
+
+        let first = tmp_dir.path().join("first");
+        fetch_artifact(&art, &digest, &first, &cache, &options)
+            .await
+            .unwrap();
+        assert!(cache.lookup(&digest).unwrap().is_some());
+
+        // 源文件删除后仍可从缓存获取
+        std::fs::remove_file(src.join("pkg.txt")).unwrap();
+        let second = tmp_dir.path().join("second");
+        let path = fetch_artifact(&art, &digest, &second, &cache, &options)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "hello");
+    }
+}
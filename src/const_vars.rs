@@ -9,20 +9,32 @@ pub const WORK_GXL: &str = "work.gxl";
 pub const ADM_GXL: &str = "adm.gxl";
 pub const PRJ_TOML: &str = "project.toml";
 pub const MOD_LIST_YML: &str = "mod_list.yml";
+pub const MOD_ORDER_YML: &str = "mod_order.yml";
 pub const RESOURCE_YML: &str = "resource.yml";
 pub const NET_RES_YML: &str = "net_res.yml";
 pub const SYS_MODLE_DEF_YML: &str = "sys_model.yml";
 pub const VARS_YML: &str = "vars.yml";
+pub const VAR_EXPORTS_YML: &str = "exports.yml";
 pub const SPEC_YML: &str = "spec.yml";
 pub const VALUE_FILE: &str = "value.yml";
 pub const DEFAULT_VALUE_FILE: &str = "_default.yml";
 pub const USER_VALUE_FILE: &str = "value.yml";
+pub const USER_VALUE_STEM: &str = "value";
 pub const SAMPLE_VALUE_FILE: &str = "_value.yml";
 pub const VALUE_DIR: &str = "values";
+// 环境覆盖层: values/env/<name>.yml
+pub const VALUE_ENV_DIR: &str = "env";
 pub const GLOBAL_VALUE_FILE: &str = "value/value.yml";
 pub const USED_JSON: &str = "_used.json";
 pub const USED_READABLE_FILE: &str = "_used.yml";
 pub const ARTIFACT_YML: &str = "artifact.yml";
+pub const ARTIFACT_DIGEST_YML: &str = "artifact_digest.yml";
+pub const ARTIFACT_DIR: &str = "artifacts";
+pub const ARTIFACT_CACHE_ENV: &str = "GALAXY_ARTIFACT_CACHE";
+pub const SECRET_KEY_FILE: &str = ".secret.key";
+pub const SECRET_KEY_ENV: &str = "GALAXY_SECRET_KEY";
+pub const SECRET_PASS_ENV: &str = "GALAXY_SECRET_PASS";
+pub const SECRET_NEW_PASS_ENV: &str = "GALAXY_SECRET_NEW_PASS";
 pub const DEPENDS_YML: &str = "depends.yml";
 pub const CONF_SPEC_YML: &str = "conf.yml";
 pub const SETTING_YML: &str = "setting.yml";
@@ -30,7 +42,10 @@ pub const LOGS_SPEC_YML: &str = "logs.yml";
 pub const RES_SPEC_YML: &str = "res.yml";
 pub const SPEC_DIR: &str = "spec";
 pub const MOD_DIR: &str = "mod";
+pub const MOD_VERSION_FILE: &str = "version.txt";
+pub const MOD_MANIFEST_YML: &str = "manifest.yml";
 pub const LOCAL_DIR: &str = "local";
+pub const RENDER_MANIFEST_YML: &str = ".render_manifest.yml";
 pub const CONFS_DIR: &str = "confs";
 pub const WORKFLOWS_DIR: &str = "workflows";
 pub const SETUP_SH: &str = "setup.sh";
@@ -44,6 +59,8 @@ pub const BITNAMI_COMMON_GIT_URL: &str =
     "https://e.coding.net/dy-sec/galaxy-open/bitnami-common.git";
 // Constants for OpsProject
 pub const OPS_PRJ_CONF_FILE: &str = "ops-prj.yml";
+pub const OPS_LOCK_FILE: &str = "ops-lock.yml";
+pub const SYS_LOCK_FILE: &str = "sys-lock.yml";
 // Constants for SysProject
 pub const SYS_PRJ_CONF_FILE_V1: &str = "sys_prj.yml";
 pub const SYS_PRJ_CONF_FILE_V2: &str = "sys-prj.yml";
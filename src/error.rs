@@ -37,6 +37,16 @@ pub enum ModReason {
     Update,
     #[error("localize fail")]
     Localize,
+    #[error("artifact fail: {0}")]
+    Artifact(String),
+    #[error("package fail: {0}")]
+    Package(String),
+    #[error("target fail: {0}")]
+    Target(String),
+    #[error("lint fail: {0}")]
+    Lint(String),
+    #[error("lock mismatch:\n{0}")]
+    Locked(String),
 }
 #[derive(Clone, Debug, Serialize, PartialEq, Error)]
 pub enum SysReason {
@@ -50,6 +60,12 @@ pub enum SysReason {
     Update,
     #[error("localize fail")]
     Localize,
+    #[error("depend error: {0}")]
+    Depend(String),
+    #[error("update fail:\n{0}")]
+    UpdateFail(String),
+    #[error("lock mismatch:\n{0}")]
+    Locked(String),
 }
 
 #[derive(Clone, Debug, Serialize, PartialEq, Error)]
@@ -64,12 +80,26 @@ pub enum OpsReason {
     Update,
     #[error("localize fail")]
     Localize,
+    #[error("lock mismatch:\n{0}")]
+    Locked(String),
+    #[error("value: {0}")]
+    Value(String),
 }
 
 #[derive(Clone, Debug, Serialize, PartialEq, Error)]
 pub enum LocalizeReason {
     #[error("miss:{0}")]
     Templatize(String),
+    #[error("validate fail:\n{0}")]
+    Validate(String),
+    #[error("miss export: {0}")]
+    MissExport(String),
+    #[error("undefined vars:\n{0}")]
+    Undefined(String),
+    #[error("secret: {0}")]
+    Secret(String),
+    #[error("bad override: {0}")]
+    Override(String),
 }
 impl ErrorCode for ElementReason {
     fn error_code(&self) -> i32 {
@@ -83,6 +113,11 @@ impl ErrorCode for LocalizeReason {
     fn error_code(&self) -> i32 {
         match self {
             LocalizeReason::Templatize(_) => 541,
+            LocalizeReason::Validate(_) => 542,
+            LocalizeReason::MissExport(_) => 543,
+            LocalizeReason::Undefined(_) => 544,
+            LocalizeReason::Secret(_) => 545,
+            LocalizeReason::Override(_) => 546,
         }
     }
 }
@@ -94,6 +129,11 @@ impl ErrorCode for ModReason {
             ModReason::Save => 553,
             ModReason::Update => 554,
             ModReason::Localize => 555,
+            ModReason::Artifact(_) => 556,
+            ModReason::Package(_) => 557,
+            ModReason::Target(_) => 558,
+            ModReason::Lint(_) => 559,
+            ModReason::Locked(_) => 560,
         }
     }
 }
@@ -105,6 +145,9 @@ impl ErrorCode for SysReason {
             SysReason::Save => 563,
             SysReason::Update => 564,
             SysReason::Localize => 565,
+            SysReason::Depend(_) => 566,
+            SysReason::UpdateFail(_) => 567,
+            SysReason::Locked(_) => 568,
         }
     }
 }
@@ -117,6 +160,8 @@ impl ErrorCode for OpsReason {
             OpsReason::Save => 573,
             OpsReason::Update => 574,
             OpsReason::Localize => 575,
+            OpsReason::Locked(_) => 576,
+            OpsReason::Value(_) => 577,
         }
     }
 }
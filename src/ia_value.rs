@@ -0,0 +1,417 @@
+use std::path::Path;
+
+use derive_getters::Getters;
+use inquire::{Confirm, CustomUserError, Password, Select, Text, validator::Validation};
+use orion_error::{ErrorOwe, ErrorWith};
+use orion_variate::vars::{ValueDict, ValueType, VarCollection};
+
+use crate::{
+    error::{LocalizeReason, MainReason, MainResult, ToErr},
+    project::{load_value_file, save_value_file},
+    secret::{SECRET_MASK, SecretKey, is_encrypted},
+    software::{Constraint, ConstraintRule, VarKind, VarRules},
+};
+
+// 交互设定时变量的输入方式
+#[derive(Clone, Debug, PartialEq)]
+pub enum PromptKind {
+    Text,
+    Int,
+    Float,
+    Confirm,
+    Select(Vec<serde_json::Value>),
+    Secret,
+}
+
+#[derive(Clone, Debug, Getters)]
+pub struct VarPrompt {
+    name: String,
+    desp: Option<String>,
+    default: serde_json::Value,
+    kind: PromptKind,
+    rule: Option<ConstraintRule>,
+}
+
+impl VarPrompt {
+    // 类型优先取 vars.yml 中的 type, 否则按默认值推断; sensitive 或已加密的变量以密码方式输入
+    pub fn new<S: Into<String>>(
+        name: S,
+        desp: Option<String>,
+        default: serde_json::Value,
+        rule: Option<&ConstraintRule>,
+        secret: bool,
+    ) -> Self {
+        let encrypted = default.as_str().is_some_and(is_encrypted);
+        let kind = if secret || encrypted {
+            PromptKind::Secret
+        } else if let Some(Constraint::OneOf(options)) = rule.and_then(|x| x.constraint().as_ref())
+        {
+            PromptKind::Select(options.clone())
+        } else {
+            match rule.and_then(|x| x.kind().clone()) {
+                Some(VarKind::Bool) => PromptKind::Confirm,
+                Some(VarKind::Int) => PromptKind::Int,
+                Some(VarKind::Float) => PromptKind::Float,
+                Some(VarKind::String) => PromptKind::Text,
+                None if default.is_boolean() => PromptKind::Confirm,
+                None if default.is_i64() || default.is_u64() => PromptKind::Int,
+                None if default.is_f64() => PromptKind::Float,
+                None => PromptKind::Text,
+            }
+        };
+        Self {
+            name: name.into(),
+            desp,
+            default,
+            kind,
+            rule: rule.cloned(),
+        }
+    }
+
+    fn message(&self) -> String {
+        match &self.desp {
+            Some(desp) => format!("{}\n{desp}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    // 空输入不回落到默认值, 避免 sensitive 变量的默认值以明文写入
+    fn secret_input(
+        &self,
+        input: &str,
+        key: Option<&SecretKey>,
+    ) -> MainResult<Option<serde_json::Value>> {
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let Some(key) = key else {
+            return secret_key_err(&[self]);
+        };
+        Ok(Some(serde_json::Value::String(key.encrypt(input)?)))
+    }
+
+    // 返回 None 表示保持现有值
+    fn ask(
+        &self,
+        current: Option<&serde_json::Value>,
+        key: Option<&SecretKey>,
+    ) -> MainResult<Option<serde_json::Value>> {
+        let base = current.unwrap_or(&self.default);
+        let message = self.message();
+        match &self.kind {
+            PromptKind::Secret => {
+                let input = Password::new(&message)
+                    .without_confirmation()
+                    .with_help_message("leave empty to keep current value or leave it unset")
+                    .prompt()
+                    .owe_data()?;
+                self.secret_input(input.as_str(), key)
+            }
+            PromptKind::Select(options) => {
+                let labels: Vec<String> = options.iter().map(value_text).collect();
+                let cursor = options.iter().position(|x| x == base).unwrap_or(0);
+                let choice = Select::new(&message, labels.clone())
+                    .with_starting_cursor(cursor)
+                    .prompt()
+                    .owe_data()?;
+                let index = labels.iter().position(|x| x == &choice).unwrap_or(0);
+                Ok(Some(options[index].clone()))
+            }
+            PromptKind::Confirm => {
+                let value = Confirm::new(&message)
+                    .with_default(base.as_bool().unwrap_or(false))
+                    .prompt()
+                    .owe_data()?;
+                Ok(Some(serde_json::Value::Bool(value)))
+            }
+            PromptKind::Text | PromptKind::Int | PromptKind::Float => {
+                let kind = self.kind.clone();
+                let rule = self.rule.clone();
+                let input = Text::new(&message)
+                    .with_default(value_text(base).as_str())
+                    .with_validator(move |input: &str| -> Result<Validation, CustomUserError> {
+                        let checked = parse_input(&kind, input).and_then(|v| {
+                            match rule.as_ref().and_then(|x| x.check(Some(&v))) {
+                                Some(msg) => Err(msg),
+                                None => Ok(v),
+                            }
+                        });
+                        Ok(match checked {
+                            Ok(_) => Validation::Valid,
+                            Err(msg) => Validation::Invalid(msg.into()),
+                        })
+                    })
+                    .prompt()
+                    .owe_data()?;
+                parse_input(&self.kind, input.as_str())
+                    .map(Some)
+                    .map_err(|msg| MainReason::from(LocalizeReason::Validate(msg)).to_err())
+            }
+        }
+    }
+}
+
+fn value_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn parse_input(kind: &PromptKind, input: &str) -> Result<serde_json::Value, String> {
+    let input = input.trim();
+    match kind {
+        PromptKind::Int => input
+            .parse::<i64>()
+            .map(serde_json::Value::from)
+            .map_err(|_| format!("{input} is not an integer")),
+        PromptKind::Float => input
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(serde_json::Value::Number)
+            .ok_or(format!("{input} is not a number")),
+        _ => Ok(serde_json::Value::String(input.to_string())),
+    }
+}
+
+pub fn var_prompts(
+    vars: &VarCollection,
+    rules: &VarRules,
+    sensitive: &[String],
+) -> MainResult<Vec<VarPrompt>> {
+    let mut prompts = Vec::new();
+    for var in vars.vars() {
+        let default = serde_json::to_value(var.value()).owe_data()?;
+        prompts.push(VarPrompt::new(
+            var.name(),
+            var.desp().as_ref().map(|x| x.to_string()),
+            default,
+            rules.find(var.name()),
+            sensitive.iter().any(|x| x == var.name()),
+        ));
+    }
+    Ok(prompts)
+}
+
+// sensitive 变量只以密文保存, 没有密钥时拒绝设定
+pub fn check_secret_key(prompts: &[&VarPrompt], key: Option<&SecretKey>) -> MainResult<()> {
+    let secrets: Vec<&VarPrompt> = prompts
+        .iter()
+        .copied()
+        .filter(|x| x.kind() == &PromptKind::Secret)
+        .collect();
+    if key.is_none() && !secrets.is_empty() {
+        return secret_key_err(&secrets);
+    }
+    Ok(())
+}
+
+fn secret_key_err<T>(prompts: &[&VarPrompt]) -> MainResult<T> {
+    let names: Vec<&str> = prompts.iter().map(|x| x.name().as_str()).collect();
+    MainReason::from(LocalizeReason::Secret(format!(
+        "{} is sensitive, create a secret key before setting it",
+        names.join(", ")
+    )))
+    .err_result()
+}
+
+// 已有取值的变量除非 all, 否则不再询问
+pub fn pending_prompts<'a>(
+    prompts: &'a [VarPrompt],
+    current: &ValueDict,
+    all: bool,
+) -> Vec<&'a VarPrompt> {
+    prompts
+        .iter()
+        .filter(|x| all || current.get(x.name()).is_none())
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueChange {
+    name: String,
+    old: Option<serde_json::Value>,
+    new: serde_json::Value,
+    secret: bool,
+}
+
+pub fn change_summary(changes: &[ValueChange]) -> String {
+    let show = |change: &ValueChange, value: &serde_json::Value| {
+        if change.secret {
+            SECRET_MASK.to_string()
+        } else {
+            value_text(value)
+        }
+    };
+    let mut summary = String::new();
+    for change in changes {
+        if let Some(old) = &change.old {
+            summary.push_str(format!("- {}: {}\n", change.name, show(change, old)).as_str());
+        }
+        summary.push_str(format!("+ {}: {}\n", change.name, show(change, &change.new)).as_str());
+    }
+    summary
+}
+
+// 逐个询问变量写入 value_file, 保存前输出变更摘要并确认; 返回是否已保存
+pub fn ia_value_setting(
+    prompts: &[VarPrompt],
+    value_file: &Path,
+    all: bool,
+    key: Option<&SecretKey>,
+) -> MainResult<bool> {
+    let mut dict = if value_file.exists() {
+        load_value_file(value_file, None)?
+    } else {
+        ValueDict::new()
+    };
+    let pending = pending_prompts(prompts, &dict, all);
+    if pending.is_empty() {
+        println!("all variables already set, use --all to change them");
+        return Ok(false);
+    }
+    check_secret_key(&pending, key)?;
+    let mut changes = Vec::new();
+    for prompt in pending {
+        let current = dict
+            .get(prompt.name())
+            .map(serde_json::to_value)
+            .transpose()
+            .owe_data()?;
+        let Some(value) = prompt.ask(current.as_ref(), key)? else {
+            continue;
+        };
+        if current.as_ref() == Some(&value) {
+            continue;
+        }
+        let typed: ValueType = serde_json::from_value(value.clone()).owe_data()?;
+        dict.insert(prompt.name().clone(), typed);
+        changes.push(ValueChange {
+            name: prompt.name().clone(),
+            old: current,
+            new: value,
+            secret: prompt.kind() == &PromptKind::Secret,
+        });
+    }
+    if changes.is_empty() {
+        println!("no change");
+        return Ok(false);
+    }
+    print!("{}", change_summary(&changes));
+    if !Confirm::new("Do you want to save these changes?")
+        .with_default(true)
+        .prompt()
+        .owe_data()?
+    {
+        return Ok(false);
+    }
+    if let Some(parent) = value_file.parent() {
+        std::fs::create_dir_all(parent).owe_sys().with(parent)?;
+    }
+    save_value_file(value_file, &dict)?;
+    println!("Changes saved to {}", value_file.display());
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use orion_variate::vars::VarDefinition;
+
+    #[test]
+    fn test_var_prompt_kind() {
+        let rules = VarRules::from(vec![
+            ConstraintRule::new(
+                "MODE",
+                Constraint::OneOf(vec![serde_json::json!("dev"), serde_json::json!("prod")]),
+            ),
+            ConstraintRule::define("RATIO").with_kind(VarKind::Float),
+            ConstraintRule::define("ENABLE").with_kind(VarKind::Bool),
+        ]);
+        let vars = VarCollection::define(vec![
+            VarDefinition::from(("MODE", "dev")),
+            VarDefinition::from(("RATIO", "0.5")),
+            VarDefinition::from(("ENABLE", "true")),
+            VarDefinition::from(("PORT", 3306)),
+            VarDefinition::from(("DB_PASS", "changeme")),
+            VarDefinition::from(("HOST", "localhost")),
+        ]);
+        let sensitive = vec!["DB_PASS".to_string()];
+        let prompts = var_prompts(&vars, &rules, &sensitive).unwrap();
+        let kinds: Vec<&PromptKind> = prompts.iter().map(|x| x.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &PromptKind::Select(vec![serde_json::json!("dev"), serde_json::json!("prod")]),
+                &PromptKind::Float,
+                &PromptKind::Confirm,
+                &PromptKind::Int,
+                &PromptKind::Secret,
+                &PromptKind::Text,
+            ]
+        );
+
+        let mut current = ValueDict::new();
+        current.insert("HOST", ValueType::from("10.0.0.1"));
+        let names = |all| {
+            pending_prompts(&prompts, &current, all)
+                .iter()
+                .map(|x| x.name().clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(!names(false).contains(&"HOST".to_string()));
+        assert_eq!(names(true).len(), prompts.len());
+
+        let pending = pending_prompts(&prompts, &current, false);
+        let err = check_secret_key(&pending, None).unwrap_err().to_string();
+        assert!(err.contains("DB_PASS"));
+        let key = SecretKey::from_pass("test-pass");
+        assert!(check_secret_key(&pending, Some(&key)).is_ok());
+        let plain: Vec<&VarPrompt> = pending
+            .into_iter()
+            .filter(|x| x.name() != "DB_PASS")
+            .collect();
+        assert!(check_secret_key(&plain, None).is_ok());
+
+        // 空输入保持未设置, 不写入明文默认值
+        let pass = VarPrompt::new("DB_PASS", None, serde_json::json!("changeme"), None, true);
+        assert_eq!(pass.secret_input("", Some(&key)).unwrap(), None);
+        let value = pass.secret_input("s3cret", Some(&key)).unwrap().unwrap();
+        assert_eq!(key.decrypt(value.as_str().unwrap()).unwrap(), "s3cret");
+        assert!(pass.secret_input("s3cret", None).is_err());
+    }
+
+    #[test]
+    fn test_parse_input_and_summary() {
+        assert_eq!(
+            parse_input(&PromptKind::Int, " 42 "),
+            Ok(serde_json::json!(42))
+        );
+        assert!(parse_input(&PromptKind::Int, "4.2").is_err());
+        assert_eq!(
+            parse_input(&PromptKind::Float, "4.2"),
+            Ok(serde_json::json!(4.2))
+        );
+        assert!(parse_input(&PromptKind::Float, "abc").is_err());
+
+        let changes = vec![
+            ValueChange {
+                name: "PORT".into(),
+                old: Some(serde_json::json!(3306)),
+                new: serde_json::json!(3307),
+                secret: false,
+            },
+            ValueChange {
+                name: "DB_PASS".into(),
+                old: None,
+                new: serde_json::json!("s3cret"),
+                secret: true,
+            },
+        ];
+        assert_eq!(
+            change_summary(&changes),
+            "- PORT: 3306\n+ PORT: 3307\n+ DB_PASS: ******\n"
+        );
+    }
+}
@@ -1,6 +1,30 @@
+use indicatif::{ProgressBar, ProgressStyle};
 use once_cell::sync::OnceCell;
 use orion_infra::logging::{LogConf, configure_logging};
 
+const DEFAULT_UPDATE_JOBS: usize = 4;
+
+// 模块/依赖并发更新数, jobs 为 0 时取 GALAXY_UPDATE_JOBS 或默认值
+pub fn update_jobs(jobs: usize) -> usize {
+    match jobs {
+        0 => std::env::var("GALAXY_UPDATE_JOBS")
+            .ok()
+            .and_then(|x| x.parse::<usize>().ok())
+            .filter(|x| *x > 0)
+            .unwrap_or(DEFAULT_UPDATE_JOBS),
+        jobs => jobs,
+    }
+}
+
+pub fn update_progress(len: usize, title: &str) -> ProgressBar {
+    let bar = ProgressBar::new(len as u64);
+    if let Ok(style) = ProgressStyle::with_template("{prefix} [{bar:30}] {pos}/{len} {msg}") {
+        bar.set_style(style.progress_chars("=> "));
+    }
+    bar.set_prefix(title.to_string());
+    bar
+}
+
 pub trait DfxArgsGetter {
     fn debug_level(&self) -> usize;
     fn log_setting(&self) -> Option<String>;
@@ -62,3 +86,14 @@ pub fn once_init_log() {
         TestIniter {}
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_jobs() {
+        assert_eq!(update_jobs(3), 3);
+        assert!(update_jobs(0) > 0);
+    }
+}
@@ -2,6 +2,7 @@ pub mod artifact;
 pub mod conf;
 pub mod const_vars;
 pub mod error;
+pub mod ia_value;
 pub mod module;
 pub mod resource;
 pub mod software;
@@ -13,9 +14,11 @@ pub mod workflow;
 pub mod tools;
 mod app_sys;
 pub mod infra;
+pub mod lock;
 pub mod ops_prj;
 pub mod package;
 pub mod predule;
 pub mod project;
+pub mod secret;
 pub mod types;
 //pub mod update;
@@ -0,0 +1,348 @@
+use std::path::Path;
+
+use derive_getters::Getters;
+use derive_more::Deref;
+use orion_common::serde::{Configable, Yamlable};
+use orion_error::{ErrorOwe, ErrorWith};
+use orion_variate::addr::AddrType;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::MainResult;
+
+// 一次 update 实际拉取到的版本: git 提交号 与 内容摘要
+#[derive(Clone, Debug, Getters, Serialize, Deserialize)]
+pub struct LockEntry {
+    name: String,
+    addr: AddrType,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    revision: Option<String>,
+    digest: String,
+}
+
+impl LockEntry {
+    pub fn new<S: Into<String>>(name: S, addr: AddrType, digest: String) -> Self {
+        Self {
+            name: name.into(),
+            addr,
+            revision: None,
+            digest,
+        }
+    }
+    pub fn with_revision(mut self, revision: Option<String>) -> Self {
+        self.revision = revision;
+        self
+    }
+    // 按本地路径计算摘要, git 仓库同时记录 HEAD 提交号
+    pub fn from_local<S: Into<String>>(
+        name: S,
+        addr: &AddrType,
+        path: &Path,
+        skips: &[&str],
+    ) -> MainResult<Self> {
+        let digest = digest_path(path, skips)?;
+        Ok(Self::new(name, addr.clone(), digest).with_revision(git_revision(path)))
+    }
+
+    fn addr_text(&self) -> String {
+        serde_json::to_string(&self.addr).unwrap_or_default()
+    }
+
+    // 与锁中记录(prev)比较, 返回差异说明
+    fn diff_from(&self, prev: &LockEntry) -> Vec<String> {
+        let mut diffs = Vec::new();
+        if prev.addr_text() != self.addr_text() {
+            diffs.push(format!("{}: addr changed", self.name));
+        }
+        if let (Some(a), Some(b)) = (&prev.revision, &self.revision) {
+            if a != b {
+                diffs.push(format!("{}: revision {a} -> {b}", self.name));
+            }
+        }
+        if prev.digest != self.digest {
+            diffs.push(format!(
+                "{}: digest {} -> {}",
+                self.name, prev.digest, self.digest
+            ));
+        }
+        diffs
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Deref)]
+#[serde(transparent)]
+pub struct LockFile {
+    entries: Vec<LockEntry>,
+}
+
+impl LockFile {
+    pub fn push(&mut self, entry: LockEntry) {
+        self.entries.push(entry);
+    }
+    // 合并子项, 名称加上前缀
+    pub fn append(&mut self, prefix: &str, other: LockFile) {
+        for mut entry in other.entries {
+            entry.name = format!("{prefix}/{}", entry.name);
+            self.entries.push(entry);
+        }
+    }
+    pub fn extend(&mut self, other: LockFile) {
+        self.entries.extend(other.entries);
+    }
+    pub fn find(&self, name: &str) -> Option<&LockEntry> {
+        self.entries.iter().find(|x| x.name == name)
+    }
+    pub fn sort(&mut self) {
+        self.entries.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    // 未重新拉取的条目没有提交号, 内容未变时沿用旧锁中的记录
+    pub fn inherit(&mut self, old: &LockFile) {
+        for entry in self.entries.iter_mut() {
+            if entry.revision.is_none() {
+                if let Some(prev) = old.find(entry.name.as_str()) {
+                    if prev.digest == entry.digest {
+                        entry.revision = prev.revision.clone();
+                    }
+                }
+            }
+        }
+    }
+
+    // 取 prefix 下的条目并去掉前缀, 与 append 相反
+    pub fn sub(&self, prefix: &str) -> LockFile {
+        let prefix = format!("{prefix}/");
+        LockFile {
+            entries: self
+                .entries
+                .iter()
+                .filter_map(|x| {
+                    let name = x.name.strip_prefix(prefix.as_str())?;
+                    let mut entry = x.clone();
+                    entry.name = name.to_string();
+                    Some(entry)
+                })
+                .collect(),
+        }
+    }
+
+    // 单个条目与锁比较, 返回差异说明
+    pub fn check(&self, entry: &LockEntry) -> Vec<String> {
+        match self.find(entry.name.as_str()) {
+            None => vec![format!("{}: not in lock", entry.name)],
+            Some(prev) => entry.diff_from(prev),
+        }
+    }
+
+    // 与旧锁比较, 返回差异说明
+    pub fn diff(&self, old: &LockFile) -> Vec<String> {
+        let mut diffs = Vec::new();
+        for entry in self.entries.iter() {
+            diffs.extend(old.check(entry));
+        }
+        for prev in old.entries.iter() {
+            if self.find(prev.name.as_str()).is_none() {
+                diffs.push(format!("{}: removed", prev.name));
+            }
+        }
+        diffs
+    }
+
+    // --locked: path 为 git 仓库时检出锁中记录的提交号, 不使用上游最新版本
+    pub fn pin(&self, name: &str, path: &Path) -> MainResult<()> {
+        let Some(revision) = self.find(name).and_then(|x| x.revision.as_ref()) else {
+            return Ok(());
+        };
+        let Ok(repo) = git2::Repository::open(path) else {
+            return Ok(());
+        };
+        if git_revision(path).as_ref() == Some(revision) {
+            return Ok(());
+        }
+        let oid = git2::Oid::from_str(revision)
+            .owe_data()
+            .with(format!("locked revision of {name}"))?;
+        let commit = repo
+            .find_commit(oid)
+            .owe_res()
+            .with(format!("locked revision {revision} of {name}"))?;
+        repo.checkout_tree(
+            commit.as_object(),
+            Some(git2::build::CheckoutBuilder::new().force()),
+        )
+        .owe_res()
+        .with(path)?;
+        repo.set_head_detached(oid).owe_res().with(path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> MainResult<Option<Self>> {
+        if path.exists() {
+            let lock = Self::from_conf(path).owe_conf().with(path)?;
+            return Ok(Some(lock));
+        }
+        Ok(None)
+    }
+
+    // 写入锁文件; locked 时要求锁文件存在且与本次结果一致, 返回差异报告
+    pub fn apply(mut self, path: &Path, locked: bool) -> MainResult<Option<String>> {
+        self.sort();
+        let old = Self::load(path)?;
+        if let Some(old) = &old {
+            self.inherit(old);
+        }
+        if locked {
+            let Some(old) = old else {
+                return Ok(Some(format!("lock file not found: {}", path.display())));
+            };
+            let diffs = self.diff(&old);
+            if !diffs.is_empty() {
+                return Ok(Some(diffs.join("\n")));
+            }
+            return Ok(None);
+        }
+        if path.exists() {
+            std::fs::remove_file(path).owe_sys().with(path)?;
+        }
+        self.save_yml(path).owe_res().with(path)?;
+        Ok(None)
+    }
+}
+
+pub fn git_revision(path: &Path) -> Option<String> {
+    let repo = git2::Repository::open(path).ok()?;
+    let commit = repo.head().ok()?.peel_to_commit().ok()?;
+    Some(commit.id().to_string())
+}
+
+// 目录(或文件)内容摘要, 忽略 .git 以及顶层 skips 中的目录
+pub fn digest_path(path: &Path, skips: &[&str]) -> MainResult<String> {
+    let mut hasher = Sha256::new();
+    let walker = walkdir::WalkDir::new(path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|x| {
+            let name = x.file_name().to_string_lossy();
+            if name == ".git" {
+                return false;
+            }
+            !(x.depth() == 1 && skips.contains(&name.as_ref()))
+        });
+    for entry in walker {
+        let entry = entry.owe_sys()?;
+        if entry.file_type().is_file() {
+            let rel = entry.path().strip_prefix(path).unwrap_or(entry.path());
+            hasher.update(rel.to_string_lossy().as_bytes());
+            hasher.update([0u8]);
+            let data = std::fs::read(entry.path()).owe_sys().with(entry.path())?;
+            hasher.update(&data);
+        }
+    }
+    let hex: String = hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect();
+    Ok(format!("sha256:{hex}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use orion_variate::addr::LocalAddr;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_digest_skip() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        std::fs::create_dir_all(root.join("spec")).unwrap();
+        std::fs::write(root.join("spec/a.yml"), "a: 1").unwrap();
+        let origin = digest_path(root, &["local"]).unwrap();
+
+        std::fs::create_dir_all(root.join("local")).unwrap();
+        std::fs::write(root.join("local/out.txt"), "out").unwrap();
+        assert_eq!(digest_path(root, &["local"]).unwrap(), origin);
+
+        std::fs::write(root.join("spec/a.yml"), "a: 2").unwrap();
+        assert_ne!(digest_path(root, &["local"]).unwrap(), origin);
+    }
+
+    #[test]
+    fn test_lock_apply_locked() {
+        let tmp_dir = tempdir().unwrap();
+        let lock_path = tmp_dir.path().join("sys-lock.yml");
+        let addr = AddrType::from(LocalAddr::from("./mysql"));
+        let make = |digest: &str| {
+            let mut lock = LockFile::default();
+            lock.push(
+                LockEntry::new("mods/mysql", addr.clone(), digest.to_string())
+                    .with_revision(Some("abc".into())),
+            );
+            lock
+        };
+
+        let report = make("sha256:1").apply(&lock_path, true).unwrap();
+        assert!(report.unwrap().contains("lock file not found"));
+        assert!(make("sha256:1").apply(&lock_path, false).unwrap().is_none());
+        assert!(make("sha256:1").apply(&lock_path, true).unwrap().is_none());
+
+        let report = make("sha256:2").apply(&lock_path, true).unwrap().unwrap();
+        assert!(report.contains("mods/mysql: digest sha256:1 -> sha256:2"));
+        let saved = LockFile::load(&lock_path).unwrap().unwrap();
+        assert_eq!(saved.find("mods/mysql").unwrap().digest(), "sha256:1");
+
+        let sub = saved.sub("mods");
+        assert_eq!(sub.find("mysql").unwrap().digest(), "sha256:1");
+        let fetched = LockEntry::new("mysql", addr.clone(), "sha256:2".into());
+        assert_eq!(
+            sub.check(&fetched),
+            vec!["mysql: digest sha256:1 -> sha256:2".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_lock_pin_revision() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path();
+        let repo = git2::Repository::init(root).unwrap();
+        let sign = git2::Signature::now("galaxy", "galaxy@test").unwrap();
+        let commit = |text: &str| {
+            std::fs::write(root.join("a.yml"), text).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("a.yml")).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<git2::Commit> = repo
+                .head()
+                .ok()
+                .and_then(|x| x.peel_to_commit().ok())
+                .into_iter()
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            repo.commit(Some("HEAD"), &sign, &sign, text, &tree, &parents)
+                .unwrap()
+                .to_string()
+        };
+        let first = commit("a: 1");
+        commit("a: 2");
+
+        let addr = AddrType::from(LocalAddr::from("./mysql"));
+        let mut lock = LockFile::default();
+        lock.push(
+            LockEntry::new("mysql", addr.clone(), "sha256:1".into())
+                .with_revision(Some(first.clone())),
+        );
+        lock.pin("mysql", root).unwrap();
+        assert_eq!(git_revision(root), Some(first));
+        assert_eq!(std::fs::read_to_string(root.join("a.yml")).unwrap(), "a: 1");
+
+        let mut lock = LockFile::default();
+        lock.push(
+            LockEntry::new("mysql", addr, "sha256:1".into())
+                .with_revision(Some("0123456789012345678901234567890123456789".into())),
+        );
+        assert!(lock.pin("mysql", root).is_err());
+    }
+}
@@ -1,6 +1,11 @@
-use crate::predule::*;
+use crate::{
+    infra::{update_jobs, update_progress},
+    lock::{LockEntry, LockFile},
+    predule::*,
+};
 
 use async_trait::async_trait;
+use futures::StreamExt;
 use orion_variate::{
     addr::{AddrResult, AddrType, GitAddr, LocalAddr, types::EnvVarPath},
     types::{LocalUpdate, UpdateUnit},
@@ -59,15 +64,83 @@ impl DependencySet {
         }
     }
     pub async fn update(&self, options: &UpdateOptions) -> AddrResult<()> {
-        //let options = UpdateOptions::for_depend();
-        //options.
-        for dep in self.deps().iter() {
-            if dep.is_enable() {
-                dep.update(&self.dep_root().path(options.values()), options)
-                    .await?;
+        self.update_units(options, 0).await.map(|_| ())
+    }
+
+    // 并发更新全部启用的依赖, 返回每个依赖的落地位置; jobs 为并发数, 0 取默认值
+    pub async fn update_units(
+        &self,
+        options: &UpdateOptions,
+        jobs: usize,
+    ) -> AddrResult<Vec<(&Dependency, UpdateUnit)>> {
+        let root = self.dep_root().path(options.values());
+        let deps: Vec<&Dependency> = self.deps().iter().filter(|x| x.is_enable()).collect();
+        if deps.is_empty() {
+            return Ok(Vec::new());
+        }
+        let bar = update_progress(deps.len(), "update depends");
+        let results: Vec<(&Dependency, AddrResult<UpdateUnit>)> = futures::stream::iter(deps)
+            .map(|dep| {
+                let bar = bar.clone();
+                let root = &root;
+                async move {
+                    let result = dep.update(root, options).await;
+                    bar.inc(1);
+                    (dep, result)
+                }
+            })
+            .buffer_unordered(update_jobs(jobs))
+            .collect()
+            .await;
+        bar.finish_and_clear();
+        // 所有失败依赖一并写入返回的错误
+        let mut first = None;
+        let mut fails = Vec::new();
+        let mut units = Vec::new();
+        for (dep, result) in results {
+            match result {
+                Ok(unit) => units.push((dep, unit)),
+                Err(e) => {
+                    error!(target: "mod/depend", "update depend {:?} fail: {}", dep.addr(), e);
+                    fails.push(format!("{:?}: {}", dep.addr(), e));
+                    first.get_or_insert(e);
+                }
             }
         }
-        Ok(())
+        if let Some(e) = first {
+            return Err(e).with(format!(
+                "{} depends update failed:\n{}",
+                fails.len(),
+                fails.join("\n")
+            ));
+        }
+        Ok(units)
+    }
+
+    // pinned 为 --locked 时的锁, 拉取后先检出其中记录的提交号
+    pub fn lock_units(
+        units: Vec<(&Dependency, UpdateUnit)>,
+        pinned: Option<&LockFile>,
+    ) -> MainResult<LockFile> {
+        let mut lock = LockFile::default();
+        for (dep, unit) in units {
+            let name = unit
+                .position()
+                .file_name()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let name = format!("depends/{name}");
+            if let Some(pinned) = pinned {
+                pinned.pin(name.as_str(), unit.position())?;
+            }
+            lock.push(LockEntry::from_local(
+                name,
+                dep.addr(),
+                unit.position(),
+                &[],
+            )?);
+        }
+        Ok(lock)
     }
     pub fn push(&mut self, item: Dependency) {
         self.deps.push(item);
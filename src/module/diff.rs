@@ -0,0 +1,183 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use derive_getters::Getters;
+use derive_more::Display;
+use orion_error::{ErrorOwe, ErrorWith};
+use similar::TextDiff;
+
+use crate::{const_vars::RENDER_MANIFEST_YML, error::MainResult, secret::ValueMask};
+
+#[derive(Clone, Debug, PartialEq, Display)]
+pub enum FileChange {
+    #[display("added")]
+    Added,
+    #[display("removed")]
+    Removed,
+    #[display("changed")]
+    Changed,
+    #[display("unchanged")]
+    Unchanged,
+}
+
+#[derive(Clone, Debug, Getters)]
+pub struct FileDiff {
+    path: PathBuf,
+    change: FileChange,
+    patch: String,
+}
+
+// dry-run 模式下收集渲染结果与 local/ 现有文件的差异
+#[derive(Clone, Debug, Default, Getters)]
+pub struct LocalizeDiff {
+    files: Vec<FileDiff>,
+}
+
+impl LocalizeDiff {
+    pub fn compare(&mut self, dst: &Path, rendered: &str) -> MainResult<()> {
+        let origin = if dst.is_file() {
+            let data = std::fs::read(dst).owe_sys().with(dst)?;
+            Some(String::from_utf8_lossy(&data).to_string())
+        } else {
+            None
+        };
+        self.push(dst, origin, rendered);
+        Ok(())
+    }
+
+    // 符号链接按链接目标比较, 不跟随到目标文件内容
+    pub fn compare_link(&mut self, dst: &Path, link: &Path) -> MainResult<()> {
+        let origin = match std::fs::read_link(dst) {
+            Ok(origin) => Some(link_text(&origin)),
+            Err(_) if dst.is_file() => {
+                let data = std::fs::read(dst).owe_sys().with(dst)?;
+                Some(String::from_utf8_lossy(&data).to_string())
+            }
+            Err(_) => None,
+        };
+        self.push(dst, origin, link_text(link).as_str());
+        Ok(())
+    }
+
+    fn push(&mut self, dst: &Path, origin: Option<String>, rendered: &str) {
+        let change = match &origin {
+            None => FileChange::Added,
+            Some(origin) if origin == rendered => FileChange::Unchanged,
+            Some(_) => FileChange::Changed,
+        };
+        let patch = make_patch(dst, origin.unwrap_or_default().as_str(), rendered);
+        self.files.push(FileDiff {
+            path: dst.to_path_buf(),
+            change,
+            patch,
+        });
+    }
+
+    // local/ 中存在但本次不会生成的文件
+    pub fn collect_removed(&mut self, local: &Path) -> MainResult<()> {
+        if !local.exists() {
+            return Ok(());
+        }
+        let rendered: HashSet<PathBuf> = self.files.iter().map(|x| x.path.clone()).collect();
+        for entry in walkdir::WalkDir::new(local) {
+            let entry = entry.owe_sys()?;
+            let path = entry.path();
+            if path.is_file()
+                && !rendered.contains(path)
+                && entry.file_name() != RENDER_MANIFEST_YML
+            {
+                let data = std::fs::read(path).owe_sys().with(path)?;
+                let origin = String::from_utf8_lossy(&data).to_string();
+                self.files.push(FileDiff {
+                    path: path.to_path_buf(),
+                    change: FileChange::Removed,
+                    patch: make_patch(path, origin.as_str(), ""),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_change(&self) -> bool {
+        self.files.iter().any(|x| x.change != FileChange::Unchanged)
+    }
+
+    // 文件内容中的明文/sensitive 值以掩码输出
+    pub fn print(&self, mask: &ValueMask) {
+        for file in self.files.iter() {
+            if *file.change() == FileChange::Unchanged {
+                continue;
+            }
+            println!("{:10} {}", file.change().to_string(), file.path().display());
+            print!("{}", mask.mask_text(file.patch()));
+        }
+        if !self.has_change() {
+            println!("no change");
+        }
+    }
+}
+
+fn link_text(link: &Path) -> String {
+    format!("-> {}\n", link.display())
+}
+
+fn make_patch(path: &Path, origin: &str, rendered: &str) -> String {
+    let name = path.display().to_string();
+    TextDiff::from_lines(origin, rendered)
+        .unified_diff()
+        .context_radius(3)
+        .header(format!("a/{name}").as_str(), format!("b/{name}").as_str())
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_compare_added_changed_unchanged() {
+        let tmp_dir = tempdir().unwrap();
+        let same = tmp_dir.path().join("same.txt");
+        let changed = tmp_dir.path().join("changed.txt");
+        std::fs::write(&same, "a\nb\n").unwrap();
+        std::fs::write(&changed, "a\nb\n").unwrap();
+
+        let mut diff = LocalizeDiff::default();
+        diff.compare(&same, "a\nb\n").unwrap();
+        diff.compare(&changed, "a\nc\n").unwrap();
+        diff.compare(&tmp_dir.path().join("new.txt"), "x\n")
+            .unwrap();
+
+        assert_eq!(diff.files()[0].change(), &FileChange::Unchanged);
+        assert_eq!(diff.files()[1].change(), &FileChange::Changed);
+        assert!(diff.files()[1].patch().contains("-b"));
+        assert!(diff.files()[1].patch().contains("+c"));
+        assert_eq!(diff.files()[2].change(), &FileChange::Added);
+        assert!(diff.has_change());
+    }
+
+    #[test]
+    fn test_collect_removed() {
+        let tmp_dir = tempdir().unwrap();
+        let keep = tmp_dir.path().join("keep.txt");
+        let gone = tmp_dir.path().join("gone.txt");
+        std::fs::write(&keep, "keep\n").unwrap();
+        std::fs::write(&gone, "gone\n").unwrap();
+
+        let mut diff = LocalizeDiff::default();
+        diff.compare(&keep, "keep\n").unwrap();
+        diff.collect_removed(tmp_dir.path()).unwrap();
+
+        let removed: Vec<&FileDiff> = diff
+            .files()
+            .iter()
+            .filter(|x| *x.change() == FileChange::Removed)
+            .collect();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].path(), &gone);
+        assert!(removed[0].patch().contains("-gone"));
+    }
+}
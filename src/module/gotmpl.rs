@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use gtmpl::{Context, Func, FuncError, Template, Value};
+use serde_json::Value as Json;
+
+use super::helpers::call_helper;
+
+// helper 以 fn 指针注册到 gtmpl, 每个名称生成一个转发函数
+macro_rules! gtmpl_funcs {
+    ($($name:literal),* $(,)?) => {
+        fn gtmpl_funcs() -> Vec<(&'static str, Func)> {
+            vec![$(($name, {
+                fn func(args: &[Value]) -> Result<Value, FuncError> {
+                    forward($name, args)
+                }
+                func as Func
+            })),*]
+        }
+    };
+}
+
+gtmpl_funcs!(
+    "default",
+    "required",
+    "upper",
+    "lower",
+    "b64enc",
+    "b64dec",
+    "sha256",
+    "sha256sum",
+    "indent",
+    "nindent",
+    "toYaml",
+    "toJson",
+    "join",
+    "split",
+    "quote",
+    "add",
+    "sub",
+    "mul",
+    "div",
+    "mod",
+);
+
+fn forward(name: &str, args: &[Value]) -> Result<Value, FuncError> {
+    let params: Vec<Json> = args.iter().map(to_json).collect();
+    call_helper(name, &params)
+        .map(|x| from_json(&x))
+        .map_err(|e| FuncError::Generic(format!("{name}: {e}")))
+}
+
+fn to_json(value: &Value) -> Json {
+    match value {
+        Value::NoValue | Value::Nil | Value::Function(_) => Json::Null,
+        Value::Bool(b) => Json::Bool(*b),
+        Value::String(s) => Json::String(s.clone()),
+        Value::Number(n) => n
+            .as_i64()
+            .map(Json::from)
+            .or_else(|| n.as_f64().map(Json::from))
+            .unwrap_or(Json::Null),
+        Value::Array(items) => Json::Array(items.iter().map(to_json).collect()),
+        Value::Object(map) | Value::Map(map) => {
+            Json::Object(map.iter().map(|(k, v)| (k.clone(), to_json(v))).collect())
+        }
+    }
+}
+
+fn from_json(json: &Json) -> Value {
+    match json {
+        Json::Null => Value::Nil,
+        Json::Bool(b) => Value::Bool(*b),
+        Json::Number(n) => n
+            .as_i64()
+            .map(Value::from)
+            .or_else(|| n.as_u64().map(Value::from))
+            .unwrap_or_else(|| Value::from(n.as_f64().unwrap_or_default())),
+        Json::String(s) => Value::String(s.clone()),
+        Json::Array(items) => Value::Array(items.iter().map(from_json).collect()),
+        Json::Object(map) => Value::Map(
+            map.iter()
+                .map(|(k, v)| (k.clone(), from_json(v)))
+                .collect::<HashMap<String, Value>>(),
+        ),
+    }
+}
+
+// 数据根即 _used.json, 同时以 .Values 暴露, 便于直接使用 helm 模板
+fn helm_context(data: &Json) -> Value {
+    let mut value = from_json(data);
+    if let Value::Map(map) = &mut value {
+        if !map.contains_key("Values") {
+            map.insert("Values".into(), from_json(data));
+        }
+    }
+    value
+}
+
+fn parse_template(template: &str, partials: &str) -> Result<Template, String> {
+    let mut tmpl = Template::default();
+    for (name, func) in gtmpl_funcs() {
+        tmpl.add_func(name, func);
+    }
+    let source = format!("{partials}{template}");
+    tmpl.parse(source.as_str()).map_err(|e| e.to_string())?;
+    Ok(tmpl)
+}
+
+pub fn check_gotmpl(template: &str, partials: &str) -> Result<(), String> {
+    parse_template(template, partials).map(|_| ())
+}
+
+// partials 为同目录 _*.tpl 的内容, 提供 define 的模板定义
+pub fn render_gotmpl(template: &str, partials: &str, data: &Json) -> Result<String, String> {
+    let tmpl = parse_template(template, partials)?;
+    tmpl.render(&Context::from(helm_context(data)))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_gotmpl() {
+        let data = serde_json::json!({
+            "name": "nginx",
+            "port": 80,
+            "res": {"cpu": 2}
+        });
+        let partials = r#"{{- define "app.name" -}}{{ .name }}-app{{- end -}}"#;
+        let tpl = r#"name: {{ template "app.name" . }}
+port: {{ .Values.port }}
+upper: {{ .name | upper }}
+res:{{ toYaml .res | nindent 2 }}"#;
+        let out = render_gotmpl(tpl, partials, &data).unwrap();
+        assert_eq!(
+            out,
+            "name: nginx-app\nport: 80\nupper: NGINX\nres:\n  cpu: 2"
+        );
+        assert!(render_gotmpl("{{ .name ", "", &data).is_err());
+    }
+}
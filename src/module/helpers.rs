@@ -0,0 +1,327 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+
+// 本地化模板使用的 handlebars: 不做 html 转义, 并注册 helm 风格的常用 helper
+pub fn localize_handlebars<'a>() -> Handlebars<'a> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    register_helpers(&mut handlebars);
+    handlebars
+}
+
+type HelperFn = fn(&[Json]) -> Result<Json, String>;
+
+// 参数顺序与 helm 保持一致, 如 default "x" VALUE, indent 4 TEXT
+const HELPERS: [(&str, HelperFn); 20] = [
+    ("default", default),
+    ("required", required),
+    ("upper", upper),
+    ("lower", lower),
+    ("b64enc", b64enc),
+    ("b64dec", b64dec),
+    ("sha256", sha256),
+    ("sha256sum", sha256),
+    ("indent", indent),
+    ("nindent", nindent),
+    ("toYaml", to_yaml),
+    ("toJson", to_json),
+    ("join", join),
+    ("split", split),
+    ("quote", quote),
+    ("add", add),
+    ("sub", sub),
+    ("mul", mul),
+    ("div", div),
+    ("mod", modulo),
+];
+
+pub fn register_helpers(handlebars: &mut Handlebars) {
+    for (name, func) in HELPERS {
+        handlebars.register_helper(name, Box::new(ValueHelper(func)));
+    }
+}
+
+// 供 go template 引擎复用同一套 helper
+pub(crate) fn call_helper(name: &str, params: &[Json]) -> Result<Json, String> {
+    HELPERS
+        .iter()
+        .find(|(x, _)| *x == name)
+        .map(|(_, func)| func(params))
+        .unwrap_or(Err(format!("unknown helper {name}")))
+}
+
+struct ValueHelper(HelperFn);
+
+impl HelperDef for ValueHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let params: Vec<Json> = h.params().iter().map(|x| x.value().clone()).collect();
+        (self.0)(&params)
+            .map(ScopedJson::Derived)
+            .map_err(|e| RenderErrorReason::Other(format!("{}: {e}", h.name())).into())
+    }
+}
+
+fn arg<'a>(params: &'a [Json], idx: usize) -> Result<&'a Json, String> {
+    params.get(idx).ok_or(format!("miss param {}", idx + 1))
+}
+
+fn text(value: &Json) -> String {
+    match value {
+        Json::Null => String::new(),
+        Json::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// 与 helm 的 empty 判定一致
+fn is_empty(value: &Json) -> bool {
+    match value {
+        Json::Null => true,
+        Json::Bool(b) => !b,
+        Json::Number(n) => n.as_f64() == Some(0.0),
+        Json::String(s) => s.is_empty(),
+        Json::Array(a) => a.is_empty(),
+        Json::Object(o) => o.is_empty(),
+    }
+}
+
+fn default(params: &[Json]) -> Result<Json, String> {
+    let fallback = arg(params, 0)?;
+    match params.get(1) {
+        Some(value) if !is_empty(value) => Ok(value.clone()),
+        _ => Ok(fallback.clone()),
+    }
+}
+
+fn required(params: &[Json]) -> Result<Json, String> {
+    let msg = text(arg(params, 0)?);
+    match params.get(1) {
+        Some(value) if !is_empty(value) => Ok(value.clone()),
+        _ => Err(msg),
+    }
+}
+
+fn upper(params: &[Json]) -> Result<Json, String> {
+    Ok(Json::String(text(arg(params, 0)?).to_uppercase()))
+}
+
+fn lower(params: &[Json]) -> Result<Json, String> {
+    Ok(Json::String(text(arg(params, 0)?).to_lowercase()))
+}
+
+fn b64enc(params: &[Json]) -> Result<Json, String> {
+    Ok(Json::String(STANDARD.encode(text(arg(params, 0)?))))
+}
+
+fn b64dec(params: &[Json]) -> Result<Json, String> {
+    let data = STANDARD
+        .decode(text(arg(params, 0)?))
+        .map_err(|e| e.to_string())?;
+    String::from_utf8(data)
+        .map(Json::String)
+        .map_err(|e| e.to_string())
+}
+
+fn sha256(params: &[Json]) -> Result<Json, String> {
+    let hex: String = Sha256::digest(text(arg(params, 0)?).as_bytes())
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect();
+    Ok(Json::String(hex))
+}
+
+fn indent_text(params: &[Json]) -> Result<String, String> {
+    let width = arg(params, 0)?
+        .as_u64()
+        .ok_or("indent width must be a number")?;
+    let pad = " ".repeat(width as usize);
+    Ok(text(arg(params, 1)?)
+        .lines()
+        .map(|x| format!("{pad}{x}"))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+fn indent(params: &[Json]) -> Result<Json, String> {
+    indent_text(params).map(Json::String)
+}
+
+fn nindent(params: &[Json]) -> Result<Json, String> {
+    indent_text(params).map(|x| Json::String(format!("\n{x}")))
+}
+
+fn to_yaml(params: &[Json]) -> Result<Json, String> {
+    let data = serde_yaml::to_string(arg(params, 0)?).map_err(|e| e.to_string())?;
+    Ok(Json::String(data.trim_end().to_string()))
+}
+
+fn to_json(params: &[Json]) -> Result<Json, String> {
+    serde_json::to_string(arg(params, 0)?)
+        .map(Json::String)
+        .map_err(|e| e.to_string())
+}
+
+fn join(params: &[Json]) -> Result<Json, String> {
+    let sep = text(arg(params, 0)?);
+    let items = match arg(params, 1)? {
+        Json::Array(items) => items.iter().map(text).collect::<Vec<String>>(),
+        other => vec![text(other)],
+    };
+    Ok(Json::String(items.join(sep.as_str())))
+}
+
+fn split(params: &[Json]) -> Result<Json, String> {
+    let sep = text(arg(params, 0)?);
+    let items = text(arg(params, 1)?)
+        .split(sep.as_str())
+        .map(|x| Json::String(x.to_string()))
+        .collect();
+    Ok(Json::Array(items))
+}
+
+fn quote(params: &[Json]) -> Result<Json, String> {
+    serde_json::to_string(&text(arg(params, 0)?))
+        .map(Json::String)
+        .map_err(|e| e.to_string())
+}
+
+enum Num {
+    Int(i64),
+    Float(f64),
+}
+
+fn nums(params: &[Json]) -> Result<Vec<Num>, String> {
+    params
+        .iter()
+        .map(|x| {
+            let s = text(x);
+            s.trim()
+                .parse::<i64>()
+                .map(Num::Int)
+                .or_else(|_| s.trim().parse::<f64>().map(Num::Float))
+                .map_err(|_| format!("{s} is not a number"))
+        })
+        .collect()
+}
+
+// 全部为整数时按整数运算, 否则按浮点
+fn arith(
+    params: &[Json],
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Json, String> {
+    let values = nums(params)?;
+    if values.len() < 2 {
+        return Err("need at least 2 params".into());
+    }
+    if values.iter().all(|x| matches!(x, Num::Int(_))) {
+        let mut ints = values.iter().map(|x| match x {
+            Num::Int(i) => *i,
+            Num::Float(f) => *f as i64,
+        });
+        let first = ints.next().unwrap_or_default();
+        let result = ints
+            .try_fold(first, int_op)
+            .ok_or("overflow or divide by zero")?;
+        return Ok(Json::from(result));
+    }
+    let mut floats = values.iter().map(|x| match x {
+        Num::Int(i) => *i as f64,
+        Num::Float(f) => *f,
+    });
+    let first = floats.next().unwrap_or_default();
+    Ok(Json::from(floats.fold(first, float_op)))
+}
+
+fn add(params: &[Json]) -> Result<Json, String> {
+    arith(params, i64::checked_add, |a, b| a + b)
+}
+
+fn sub(params: &[Json]) -> Result<Json, String> {
+    arith(params, i64::checked_sub, |a, b| a - b)
+}
+
+fn mul(params: &[Json]) -> Result<Json, String> {
+    arith(params, i64::checked_mul, |a, b| a * b)
+}
+
+fn div(params: &[Json]) -> Result<Json, String> {
+    arith(params, i64::checked_div, |a, b| a / b)
+}
+
+fn modulo(params: &[Json]) -> Result<Json, String> {
+    arith(params, i64::checked_rem, |a, b| a % b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(tpl: &str, data: Json) -> String {
+        localize_handlebars().render_template(tpl, &data).unwrap()
+    }
+
+    #[test]
+    fn test_string_helpers() {
+        let data = serde_json::json!({"name": "MySql", "empty": "", "hosts": ["a", "b"]});
+        assert_eq!(
+            render("{{upper name}}-{{lower name}}", data.clone()),
+            "MYSQL-mysql"
+        );
+        assert_eq!(render(r#"{{default "x" empty}}"#, data.clone()), "x");
+        assert_eq!(render(r#"{{default "x" name}}"#, data.clone()), "MySql");
+        assert_eq!(render(r#"{{join "," hosts}}"#, data.clone()), "a,b");
+        assert_eq!(
+            render(r#"{{join "|" (split "," "a,b,c")}}"#, data.clone()),
+            "a|b|c"
+        );
+        assert_eq!(render("{{quote name}}", data.clone()), "\"MySql\"");
+        assert_eq!(render("{{b64dec (b64enc name)}}", data.clone()), "MySql");
+        assert_eq!(render("{{sha256 name}}", data.clone()).len(), 64);
+        assert!(
+            localize_handlebars()
+                .render_template(r#"{{required "name is required" missing}}"#, &data)
+                .unwrap_err()
+                .to_string()
+                .contains("name is required")
+        );
+    }
+
+    #[test]
+    fn test_yaml_and_math_helpers() {
+        let data = serde_json::json!({"res": {"cpu": 2, "mem": "1Gi"}, "port": "3306"});
+        assert_eq!(
+            render("res:{{nindent 2 (toYaml res)}}", data.clone()),
+            "res:\n  cpu: 2\n  mem: 1Gi"
+        );
+        assert_eq!(
+            render("{{toJson res}}", data.clone()),
+            r#"{"cpu":2,"mem":"1Gi"}"#
+        );
+        assert_eq!(render("{{add port 1 2}}", data.clone()), "3309");
+        assert_eq!(
+            render(
+                "{{sub 10 4}}/{{mul 2 3}}/{{div 7 2}}/{{mod 7 2}}",
+                data.clone()
+            ),
+            "6/6/3/1"
+        );
+        assert_eq!(render("{{div 7 2.0}}", data.clone()), "3.5");
+        assert!(
+            localize_handlebars()
+                .render_template("{{div 1 0}}", &data)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,520 @@
+use std::{collections::HashSet, fmt::Display};
+
+use derive_more::Display;
+use handlebars::{
+    Path, Template, TemplateError,
+    template::{BlockParam, HelperTemplate, Parameter, TemplateElement},
+};
+use orion_variate::ext::ArtifactPackage;
+use orion_variate::tpl::{CommentFmt, CustTmplLabel, LabelCoverter};
+use regex::Regex;
+use serde::de::DeserializeOwned;
+
+use super::prelude::*;
+use super::{
+    depend::DependencySet,
+    gotmpl::check_gotmpl,
+    localize::{gotmpl_partials, is_gotmpl_partial},
+    model::{ModModelSpec, ModTargetPaths},
+    setting::{EngineSelect, Setting, TemplateConfig, TemplatePath, TplEngine},
+};
+use crate::{
+    const_vars::{LOCAL_DIR, MOD_DIR, SPEC_DIR, VALUE_DIR},
+    predule::*,
+};
+
+#[derive(Clone, Debug, Display, PartialEq)]
+pub enum LintLevel {
+    #[display("error")]
+    Error,
+    #[display("warn")]
+    Warn,
+}
+
+#[derive(Clone, Debug, Getters)]
+pub struct LintIssue {
+    level: LintLevel,
+    file: PathBuf,
+    line: Option<usize>,
+    message: String,
+}
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(
+                f,
+                "{}: {}:{}: {}",
+                self.level,
+                self.file.display(),
+                line,
+                self.message
+            ),
+            None => write!(
+                f,
+                "{}: {}: {}",
+                self.level,
+                self.file.display(),
+                self.message
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Getters)]
+pub struct LintReport {
+    issues: Vec<LintIssue>,
+}
+impl LintReport {
+    fn push<S: Into<String>>(
+        &mut self,
+        level: LintLevel,
+        file: &Path,
+        line: Option<usize>,
+        message: S,
+    ) {
+        self.issues.push(LintIssue {
+            level,
+            file: file.to_path_buf(),
+            line,
+            message: message.into(),
+        });
+    }
+    pub fn error_count(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|x| x.level == LintLevel::Error)
+            .count()
+    }
+    pub fn print(&self) {
+        for issue in self.issues.iter() {
+            println!("{issue}");
+        }
+        println!(
+            "lint: {} error(s), {} warning(s)",
+            self.error_count(),
+            self.issues.len() - self.error_count()
+        );
+    }
+}
+
+// 检查模块工程 mod/ 下的全部 target
+pub fn lint_module(prj_root: &Path) -> MainResult<LintReport> {
+    let mut report = LintReport::default();
+    let mod_root = prj_root.join(MOD_DIR);
+    for target_root in get_sub_dirs(&mod_root).owe_res().with(&mod_root)? {
+        lint_target(&target_root, &mut report)?;
+    }
+    Ok(report)
+}
+
+pub fn lint_target(target_root: &Path, report: &mut LintReport) -> MainResult<()> {
+    let paths = ModTargetPaths::from(&target_root.to_path_buf());
+    let errors = report.error_count();
+    check_yaml::<ArtifactPackage>(paths.artifact_path(), report)?;
+    check_yaml::<DependencySet>(paths.depends_path(), report)?;
+    let vars = check_yaml::<VarCollection>(paths.vars_path(), report)?;
+    let setting = if paths.setting_path().exists() {
+        check_yaml::<Setting>(paths.setting_path(), report)?
+    } else {
+        None
+    };
+    // 各文件均可解析时再整体加载, 避免重复报告
+    if report.error_count() == errors {
+        if let Err(e) = ModModelSpec::load_from(target_root) {
+            report.push(LintLevel::Error, target_root, None, e.to_string());
+        }
+    }
+
+    let localize = setting.as_ref().and_then(|x| x.localize().clone());
+    let tpl_path = localize
+        .as_ref()
+        .and_then(|x| x.templatize_path().clone())
+        .map(|x| x.export_paths(target_root))
+        .unwrap_or_default();
+    let cust = match localize.as_ref().and_then(|x| x.templatize_cust().clone()) {
+        Some(cust) => {
+            let conf = TemplateConfig::from(cust);
+            CustTmplLabel::Setting(LabelCoverter::new(
+                conf.origin().clone(),
+                conf.target().clone(),
+            ))
+        }
+        None => CustTmplLabel::None,
+    };
+
+    let declared: Option<Vec<String>> = vars.map(|x| {
+        x.value_dict()
+            .keys()
+            .map(|k| k.to_string())
+            .collect::<Vec<String>>()
+    });
+    let engine = localize
+        .as_ref()
+        .map(|x| {
+            EngineSelect::new(
+                x.engine().clone().unwrap_or_default(),
+                x.engine_rules(),
+                target_root,
+            )
+        })
+        .unwrap_or_default();
+    let mut referenced = HashSet::new();
+    let spec_root = target_root.join(SPEC_DIR);
+    for tpl in template_files(&spec_root, &tpl_path)? {
+        let Ok(content) = std::fs::read_to_string(&tpl) else {
+            continue;
+        };
+        if engine.engine_of(&tpl) == &TplEngine::GoTmpl {
+            if !is_gotmpl_partial(&tpl) {
+                let partials = gotmpl_partials(&tpl)?;
+                if let Err(e) = check_gotmpl(content.as_str(), partials.as_str()) {
+                    report.push(LintLevel::Error, &tpl, None, e);
+                }
+            }
+            continue;
+        }
+        let content = match cust.convert(&CommentFmt::from(tpl.extension()), content) {
+            Ok(content) => content,
+            Err(e) => {
+                report.push(LintLevel::Error, &tpl, None, e.to_string());
+                continue;
+            }
+        };
+        let refs = match template_refs(content.as_str()) {
+            Ok(refs) => refs,
+            Err(e) => {
+                let line = e.pos().map(|(line, _)| line);
+                report.push(LintLevel::Error, &tpl, line, e.reason().to_string());
+                continue;
+            }
+        };
+        for tpl_ref in refs.iter().filter(|x| !x.is_whole()) {
+            let name = tpl_ref.root().to_string();
+            if let Some(declared) = &declared {
+                if !declared.contains(&name) {
+                    report.push(
+                        LintLevel::Error,
+                        &tpl,
+                        Some(*tpl_ref.line()),
+                        format!("{name} not declared in vars.yml"),
+                    );
+                }
+            }
+            referenced.insert(name);
+        }
+    }
+
+    // 声明但未使用的变量: 模板与工作流中均未出现
+    if let Some(declared) = declared {
+        let texts = target_texts(target_root)?;
+        for name in declared {
+            let word = Regex::new(format!(r"\b{}\b", regex::escape(name.as_str())).as_str())
+                .owe_logic()?;
+            if !referenced.contains(&name) && !texts.iter().any(|x| word.is_match(x)) {
+                report.push(
+                    LintLevel::Warn,
+                    paths.vars_path(),
+                    None,
+                    format!("{name} is declared but never used"),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_yaml<T: DeserializeOwned>(path: &Path, report: &mut LintReport) -> MainResult<Option<T>> {
+    if !path.exists() {
+        report.push(LintLevel::Error, path, None, "file not found");
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path).owe_res().with(path)?;
+    match serde_yaml::from_str::<T>(content.as_str()) {
+        Ok(obj) => Ok(Some(obj)),
+        Err(e) => {
+            let line = e.location().map(|x| x.line());
+            report.push(LintLevel::Error, path, line, e.to_string());
+            Ok(None)
+        }
+    }
+}
+
+// spec/ 下参与渲染的文件, 与 localize 相同的 include/exclude 规则
+fn template_files(spec_root: &Path, tpl_path: &TemplatePath) -> MainResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !spec_root.exists() {
+        return Ok(files);
+    }
+    for entry in walkdir::WalkDir::new(spec_root).sort_by_file_name() {
+        let entry = entry.owe_sys().with(spec_root)?;
+        let path = entry.path();
+        if entry.file_type().is_file() && tpl_path.is_include(path) && !tpl_path.is_exclude(path) {
+            files.push(path.to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+// target 下除本地化产物外的文本文件, 用于判断变量是否被使用
+fn target_texts(target_root: &Path) -> MainResult<Vec<String>> {
+    let mut texts = Vec::new();
+    let walker = walkdir::WalkDir::new(target_root)
+        .into_iter()
+        .filter_entry(|x| {
+            let name = x.file_name().to_string_lossy();
+            !(x.depth() == 1 && (name == LOCAL_DIR || name == VALUE_DIR))
+        });
+    for entry in walker {
+        let entry = entry.owe_sys().with(target_root)?;
+        if entry.file_type().is_file() && entry.file_name() != crate::const_vars::VARS_YML {
+            if let Ok(text) = std::fs::read_to_string(entry.path()) {
+                texts.push(text);
+            }
+        }
+    }
+    Ok(texts)
+}
+
+const BLOCK_CTX_HELPERS: [&str; 2] = ["each", "with"];
+const GUARD_HELPERS: [&str; 3] = ["if", "unless", "default"];
+
+// 模板中对顶层上下文的一次引用, path 为空时引用整个上下文
+#[derive(Clone, Debug, Getters, PartialEq)]
+pub struct TplRef {
+    line: usize,
+    path: String,
+    // 出现在 if/unless/default 的参数中, 允许未定义
+    guarded: bool,
+}
+impl TplRef {
+    pub fn root(&self) -> &str {
+        self.path.split(['.', '/', '[']).next().unwrap_or_default()
+    }
+    // this/@root/partial 等依赖整个上下文
+    pub fn is_whole(&self) -> bool {
+        self.path.is_empty()
+    }
+}
+
+// 按 handlebars 语法树收集模板引用的顶层变量;
+// each/with 块内上下文已切换, 只收集经 @root 或 ../ 回到顶层的引用
+pub fn template_refs(content: &str) -> Result<Vec<TplRef>, TemplateError> {
+    let template = Template::compile(content)?;
+    let mut walker = RefWalker::default();
+    walker.template(&template, 0, 1);
+    Ok(walker.refs)
+}
+
+#[derive(Default)]
+struct RefWalker {
+    refs: Vec<TplRef>,
+    // 当前可见的块参数, 如 each X as |item|
+    locals: Vec<String>,
+}
+
+impl RefWalker {
+    fn template(&mut self, template: &Template, depth: usize, line: usize) {
+        for (idx, element) in template.elements.iter().enumerate() {
+            // else if 链生成的节点没有位置信息, 沿用所在块的行号
+            let line = template.mapping.get(idx).map(|x| x.0).unwrap_or(line);
+            self.element(element, depth, line, false);
+        }
+    }
+
+    fn element(&mut self, element: &TemplateElement, depth: usize, line: usize, guarded: bool) {
+        match element {
+            TemplateElement::Expression(helper)
+            | TemplateElement::HtmlExpression(helper)
+            | TemplateElement::HelperBlock(helper) => self.helper(helper, depth, line, guarded),
+            // partial 与 decorator 可访问整个上下文
+            TemplateElement::DecoratorExpression(_)
+            | TemplateElement::DecoratorBlock(_)
+            | TemplateElement::PartialExpression(_)
+            | TemplateElement::PartialBlock(_) => self.push(line, String::new(), guarded),
+            _ => {}
+        }
+    }
+
+    fn helper(&mut self, helper: &HelperTemplate, depth: usize, line: usize, guarded: bool) {
+        if !helper.block && helper.params.is_empty() && helper.hash.is_empty() {
+            self.param(&helper.name, depth, line, guarded);
+            return;
+        }
+        let name = helper.name.as_name().unwrap_or_default();
+        let guarded = guarded || GUARD_HELPERS.contains(&name);
+        for param in helper.params.iter().chain(helper.hash.values()) {
+            self.param(param, depth, line, guarded);
+        }
+        // each/with 的块内切换到参数对应的上下文, else 分支仍在当前上下文
+        let inner = if BLOCK_CTX_HELPERS.contains(&name) {
+            depth + 1
+        } else {
+            depth
+        };
+        let locals = self.locals.len();
+        match &helper.block_param {
+            Some(BlockParam::Single(x)) => self.local(x),
+            Some(BlockParam::Pair((x, y))) => {
+                self.local(x);
+                self.local(y);
+            }
+            _ => {}
+        }
+        if let Some(template) = &helper.template {
+            self.template(template, inner, line);
+        }
+        self.locals.truncate(locals);
+        if let Some(inverse) = &helper.inverse {
+            self.template(inverse, depth, line);
+        }
+    }
+
+    fn local(&mut self, param: &Parameter) {
+        if let Some(name) = param.as_name() {
+            self.locals.push(name.to_string());
+        }
+    }
+
+    fn param(&mut self, param: &Parameter, depth: usize, line: usize, guarded: bool) {
+        match param {
+            Parameter::Name(name) => self.path(name, depth, line, guarded),
+            Parameter::Path(Path::Relative((_, raw))) => self.path(raw, depth, line, guarded),
+            // 单独的 @root 按局部变量解析
+            Parameter::Path(Path::Local((_, name, _))) if name == "root" => {
+                self.push(line, String::new(), guarded)
+            }
+            Parameter::Subexpression(sub) => self.element(sub.as_element(), depth, line, guarded),
+            // 字面量与 @index 等局部变量
+            _ => {}
+        }
+    }
+
+    // 只保留解析到顶层上下文的路径
+    fn path(&mut self, raw: &str, depth: usize, line: usize, guarded: bool) {
+        let (up, rest) = match raw.strip_prefix("@root") {
+            Some(rest) => (depth, rest),
+            None => {
+                let mut rest = raw;
+                let mut up = 0;
+                while let Some(x) = rest.strip_prefix("../") {
+                    rest = x;
+                    up += 1;
+                }
+                (up, rest)
+            }
+        };
+        if up < depth {
+            return;
+        }
+        let rest = rest.trim_start_matches(['.', '/']);
+        let path = match rest.strip_prefix("this") {
+            Some(x) if x.is_empty() || x.starts_with(['.', '/']) => {
+                x.trim_start_matches(['.', '/'])
+            }
+            _ => rest,
+        };
+        let tpl_ref = TplRef {
+            line,
+            path: path.to_string(),
+            guarded,
+        };
+        if raw == rest && self.locals.iter().any(|x| x == tpl_ref.root()) {
+            return;
+        }
+        self.refs.push(tpl_ref);
+    }
+
+    fn push(&mut self, line: usize, path: String, guarded: bool) {
+        self.refs.push(TplRef {
+            line,
+            path,
+            guarded,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use orion_variate::vars::VarDefinition;
+
+    use super::*;
+    use crate::module::{
+        ModelSTD,
+        init::{ModIniter, ModPrjIniter},
+    };
+
+    #[test]
+    fn test_template_refs() {
+        let tpl = r#"port: {{PORT}}
+{{#if ENABLE_TLS}}cert: {{ CERT_PATH }}{{/if}}
+{{#each USERS as |user|}}- {{name}} {{user.id}} {{@index}} {{@root.DOMAIN}}{{/each}}
+size: {{default "a b" SIZE}}
+{{#if A}}a{{else if B}}b{{else}}{{C}}{{/if}}
+host: {{upper (default "x" HOST)}} {{join (split SEP LIST) ","}}
+"#;
+        let refs: Vec<(usize, String, bool)> = template_refs(tpl)
+            .unwrap()
+            .iter()
+            .map(|x| (*x.line(), x.root().to_string(), *x.guarded()))
+            .collect();
+        assert_eq!(
+            refs,
+            vec![
+                (1, "PORT".to_string(), false),
+                (2, "ENABLE_TLS".to_string(), true),
+                (2, "CERT_PATH".to_string(), false),
+                (3, "USERS".to_string(), false),
+                (3, "DOMAIN".to_string(), false),
+                (4, "SIZE".to_string(), true),
+                (5, "A".to_string(), true),
+                (5, "B".to_string(), true),
+                (5, "C".to_string(), false),
+                (6, "HOST".to_string(), true),
+                (6, "SEP".to_string(), false),
+                (6, "LIST".to_string(), false),
+            ]
+        );
+        assert!(template_refs("{{> header}}").unwrap()[0].is_whole());
+        assert!(template_refs("{{#if A}}").is_err());
+    }
+
+    #[test]
+    fn test_lint_target_report() -> MainResult<()> {
+        let tmp_dir = tempfile::tempdir().owe_res()?;
+        ModModelSpec::init(
+            ModelSTD::x86_ubt22_k8s(),
+            ArtifactPackage::from(Vec::new()),
+            ModWorkflows::mod_k8s_tpl_init(),
+            GxlProject::spec_k8s_tpl(),
+            VarCollection::define(vec![
+                VarDefinition::from(("PORT", 80)),
+                VarDefinition::from(("UNUSED", 1)),
+            ]),
+            None,
+        )
+        .save_to(tmp_dir.path(), None)
+        .owe_res()?;
+        let target = tmp_dir.path().join("x86-ubt22-k8s");
+        std::fs::write(target.join("spec/depends.yml"), "dep: [").owe_res()?;
+        std::fs::write(
+            target.join("spec/app.conf"),
+            "port={{PORT}}\nhost={{HOST}}\n{{#if PORT}}\n",
+        )
+        .owe_res()?;
+        std::fs::write(target.join("spec/svc.conf"), "host={{HOST}}\n").owe_res()?;
+
+        let mut report = LintReport::default();
+        lint_target(&target, &mut report)?;
+        let texts: Vec<String> = report.issues().iter().map(|x| x.to_string()).collect();
+        let has = |part: &str| texts.iter().any(|x| x.contains(part));
+        assert!(has("depends.yml"));
+        assert!(
+            texts
+                .iter()
+                .any(|x| x.starts_with("error:") && x.contains("app.conf:"))
+        );
+        assert!(has("svc.conf:1: HOST not declared in vars.yml"));
+        assert!(has("warn:") && has("UNUSED is declared but never used"));
+        Ok(())
+    }
+}
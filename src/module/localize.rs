@@ -1,27 +1,49 @@
-use std::path::{Path, PathBuf};
+use std::{
+    cell::RefCell,
+    path::{Component, Path, PathBuf},
+};
 
 use fs_extra::dir::CopyOptions;
-use log::{debug, info};
+use log::{debug, info, warn};
 use orion_error::{ErrorOwe, ErrorWith, StructError, UvsConfFrom, UvsResFrom, WithContext};
 use serde::Serialize;
 
 use crate::{
-    error::{MainResult, ModReason},
+    error::{LocalizeReason, MainReason, MainResult, ModReason, ToErr},
     module::setting::TemplatePath,
 };
-use orion_variate::tpl::{CommentFmt, CustTmplLabel, LabelCoverter, TplHandleBars};
-
-use super::setting::TemplateConfig;
+use handlebars::{Handlebars, RenderErrorReason};
+use orion_variate::tpl::{CommentFmt, CustTmplLabel, LabelCoverter};
+
+use super::{
+    diff::LocalizeDiff,
+    gotmpl::render_gotmpl,
+    helpers::localize_handlebars,
+    lint::template_refs,
+    manifest::{IncrementalRender, RenderEntry, RenderManifest, digest_bytes, values_digest},
+    setting::{EngineSelect, FileModes, TemplateConfig, TplEngine},
+};
 
 pub struct LocalizeTemplate<'a> {
-    handlebars: TplHandleBars<'a>,
+    handlebars: Handlebars<'a>,
     cust_cover: CustTmplLabel,
+    diff: Option<RefCell<LocalizeDiff>>,
+    // 严格模式: 引用未定义的变量时报错
+    strict: bool,
+    engine: EngineSelect,
+    modes: FileModes,
+    incremental: Option<IncrementalRender>,
 }
 impl Default for LocalizeTemplate<'_> {
     fn default() -> Self {
         Self {
-            handlebars: TplHandleBars::init(),
+            handlebars: localize_handlebars(),
             cust_cover: CustTmplLabel::None,
+            diff: None,
+            strict: false,
+            engine: EngineSelect::default(),
+            modes: FileModes::default(),
+            incremental: None,
         }
     }
 }
@@ -29,10 +51,156 @@ impl LocalizeTemplate<'_> {
     pub fn new(cust: TemplateConfig) -> Self {
         let convert = LabelCoverter::new(cust.origin().clone(), cust.target().clone());
         Self {
-            handlebars: TplHandleBars::init(),
+            handlebars: localize_handlebars(),
             cust_cover: CustTmplLabel::Setting(convert),
+            diff: None,
+            strict: false,
+            engine: EngineSelect::default(),
+            modes: FileModes::default(),
+            incremental: None,
+        }
+    }
+    pub fn with_engine(mut self, engine: EngineSelect) -> Self {
+        self.engine = engine;
+        self
+    }
+    pub fn with_modes(mut self, modes: FileModes) -> Self {
+        self.modes = modes;
+        self
+    }
+    // 依据上次的渲染清单, 只重新渲染输入有变化的产物
+    pub fn with_incremental(mut self, local: &Path, prev: RenderManifest) -> MainResult<Self> {
+        self.incremental = Some(IncrementalRender::begin(local, prev)?);
+        Ok(self)
+    }
+    // 清理已失效的产物并保存本次清单
+    pub fn finish_incremental(&mut self) -> MainResult<Option<RenderManifest>> {
+        self.incremental.take().map(|x| x.finish()).transpose()
+    }
+    // 只渲染到内存, 与目标文件比较, 不写磁盘
+    pub fn with_dry_run(mut self) -> Self {
+        self.diff = Some(RefCell::new(LocalizeDiff::default()));
+        self
+    }
+    pub fn take_diff(self) -> Option<LocalizeDiff> {
+        self.diff.map(|x| x.into_inner())
+    }
+    // 严格模式: 写出任何产物前先检查全部模板, 有未定义的变量时报错
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.handlebars.set_strict_mode(strict);
+        self.strict = strict;
+        self
+    }
+    // 严格模式下先于渲染检查, 失败时不改动已有产物与渲染清单
+    fn check_refs<T: Serialize>(
+        &self,
+        tpl: &Path,
+        data: &T,
+        templatize: &TemplatePath,
+    ) -> MainResult<()> {
+        if !self.strict {
+            return Ok(());
         }
+        let data = serde_json::to_value(data).owe_data()?;
+        let mut missing = Vec::new();
+        for entry in walkdir::WalkDir::new(tpl) {
+            let entry = entry.owe_data()?;
+            if entry.path_is_symlink() || !entry.file_type().is_file() {
+                continue;
+            }
+            let tpl_path = entry.path();
+            let Some(template) = self.strict_source(tpl_path, templatize)? else {
+                continue;
+            };
+            let found = missing.len();
+            collect_missing(&mut missing, tpl_path, template.as_str(), &data)?;
+            if missing.len() > found {
+                continue;
+            }
+            // each/with 块内的引用只有渲染时才能发现, 在内存中试渲染
+            if let Err(e) = self.handlebars.render_template(&template, &data) {
+                if let RenderErrorReason::MissingVariable(name) = e.reason() {
+                    let name = name.clone().unwrap_or_default();
+                    let line = e.line_no.unwrap_or_default();
+                    push_missing(&mut missing, tpl_path, line, name.as_str());
+                }
+            }
+        }
+        if !missing.is_empty() {
+            return MainReason::from(LocalizeReason::Undefined(missing.join("\n"))).err_result();
+        }
+        Ok(())
     }
+    // 由 handlebars 渲染的模板内容, 其余文件(排除/二进制/partial)不参与检查
+    fn strict_source(
+        &self,
+        tpl_path: &Path,
+        templatize: &TemplatePath,
+    ) -> MainResult<Option<String>> {
+        if !templatize.is_include(tpl_path) || templatize.is_exclude(tpl_path) {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(tpl_path).owe_sys().with(tpl_path)?;
+        if is_binary(&bytes) {
+            return Ok(None);
+        }
+        let Ok(template) = String::from_utf8(bytes) else {
+            return Ok(None);
+        };
+        // gotmpl 没有未定义变量检查, 严格模式下不允许使用
+        if self.engine.engine_of(tpl_path) == &TplEngine::GoTmpl {
+            return Err(StructError::from_conf(
+                "strict mode is not supported by gotmpl engine".to_string(),
+            ))
+            .with(tpl_path);
+        }
+        let template = self
+            .cust_cover
+            .convert(&CommentFmt::from(tpl_path.extension()), template)
+            .with(tpl_path)
+            .owe(ModReason::Localize.into())?;
+        Ok(Some(template))
+    }
+}
+
+fn collect_missing(
+    missing: &mut Vec<String>,
+    tpl_path: &Path,
+    template: &str,
+    data: &serde_json::Value,
+) -> MainResult<()> {
+    let refs = template_refs(template).owe_biz().with(tpl_path)?;
+    for tpl_ref in refs {
+        if !tpl_ref.guarded() && !tpl_ref.is_whole() && !json_lookup(data, tpl_ref.path()) {
+            push_missing(missing, tpl_path, *tpl_ref.line(), tpl_ref.path());
+        }
+    }
+    Ok(())
+}
+
+// file:line: name, 同一处只记录一次
+fn push_missing(missing: &mut Vec<String>, tpl_path: &Path, line: usize, name: &str) {
+    let item = format!("{}:{}: {}", tpl_path.display(), line, name);
+    if !missing.contains(&item) {
+        missing.push(item);
+    }
+}
+
+fn json_lookup(data: &serde_json::Value, path: &str) -> bool {
+    let mut cur = data;
+    for seg in path.split(['.', '/']).filter(|x| !x.is_empty()) {
+        let seg = seg.trim_start_matches('[').trim_end_matches(']');
+        let next = match cur {
+            serde_json::Value::Object(map) => map.get(seg),
+            serde_json::Value::Array(arr) => seg.parse::<usize>().ok().and_then(|i| arr.get(i)),
+            _ => None,
+        };
+        match next {
+            Some(value) => cur = value,
+            None => return false,
+        }
+    }
+    true
 }
 impl LocalizeTemplate<'_> {
     pub fn render_path(
@@ -50,11 +218,28 @@ impl LocalizeTemplate<'_> {
         let data: serde_json::Value = serde_json::from_str(content.as_str())
             .owe_data()
             .with(&err_ctx)?;
+        self.render_json(tpl, dst, &data, setting).with(&err_ctx)
+    }
+
+    pub fn render_json(
+        &self,
+        tpl: &PathBuf,
+        dst: &PathBuf,
+        data: &serde_json::Value,
+        setting: &TemplatePath,
+    ) -> MainResult<()> {
+        let mut err_ctx = WithContext::want("render tpl path");
+        err_ctx.with_path("tpl", tpl);
+        if let Err(e) = self.check_refs(tpl, data, setting) {
+            if let Some(incremental) = &self.incremental {
+                incremental.restore()?;
+            }
+            return Err(e).with(&err_ctx);
+        }
         if tpl.is_dir() {
-            self.render_dir_impl(tpl, dst, &data, setting)
-                .with(&err_ctx)
+            self.render_dir_impl(tpl, dst, data, setting).with(&err_ctx)
         } else {
-            self.render_file_impl(tpl, dst, &data, setting)
+            self.render_file_impl(tpl, dst, data, setting)
                 .with(&err_ctx)
         }
     }
@@ -73,7 +258,12 @@ impl LocalizeTemplate<'_> {
             let relative_path = tpl_path.strip_prefix(tpl_dir).owe_data()?;
             let dst_path = Path::new(dst).join(relative_path);
 
-            if tpl_path.is_dir() {
+            if entry.depth() > 0 && entry.path_is_symlink() {
+                self.link_impl(tpl_dir, &tpl_path, &dst_path, setting)?;
+            } else if tpl_path.is_dir() {
+                if self.diff.is_some() {
+                    continue;
+                }
                 // 如果是目录，确保在目标位置创建对应的目录
                 std::fs::create_dir_all(&dst_path).owe_sys()?;
                 debug!("created dir: {}", dst_path.display());
@@ -106,6 +296,21 @@ impl LocalizeTemplate<'_> {
             return Ok(());
         }
         if templatize.is_exclude(tpl_path) {
+            if let Some(diff) = &self.diff {
+                let data = std::fs::read(tpl_path).owe_sys().with(&err_ctx)?;
+                return diff
+                    .borrow_mut()
+                    .compare(dst_path, String::from_utf8_lossy(&data).as_ref());
+            }
+            if self.incremental.is_some() {
+                let data = std::fs::read(tpl_path).owe_sys().with(&err_ctx)?;
+                if self.keep_output(dst_path, &[&data], String::new(), None) {
+                    return Ok(());
+                }
+                if dst_path.symlink_metadata().is_ok() {
+                    std::fs::remove_file(dst_path).owe_sys().with(dst_path)?;
+                }
+            }
             if let Some(dist) = dst_path.parent() {
                 println!("copy {:30} ---> {}", tpl_path.display(), dist.display());
                 fs_extra::copy_items(&[&tpl_path], dist, &CopyOptions::default())
@@ -119,26 +324,49 @@ impl LocalizeTemplate<'_> {
         }
         err_ctx.with("dst", dst_path.to_string_lossy());
 
-        // 3. 准备目标文件
-        let dst_path = Path::new(&dst_path);
-        if let Some(parent) = dst_path.parent() {
-            std::fs::create_dir_all(parent).owe_sys()?;
-        }
-        if dst_path.exists() {
-            std::fs::remove_file(dst_path).owe_sys()?;
-        }
-
-        // 4. 日志记录
+        // 3. 日志记录
         debug!(
             "Processing template: {} → {}",
             tpl_path.display(),
             dst_path.display()
         );
 
-        // 5. 读取模板内容
-        let template = std::fs::read_to_string(tpl_path)
-            .owe_data()
-            .with(&err_ctx)?;
+        // 4. 读取模板内容, 二进制文件原样复制
+        let bytes = std::fs::read(tpl_path).owe_sys().with(&err_ctx)?;
+        let binary = is_binary(&bytes);
+        if binary || std::str::from_utf8(&bytes).is_err() {
+            if !binary {
+                warn!("not utf-8, copy without render: {}", tpl_path.display());
+            }
+            let mode = self.output_mode(tpl_path)?;
+            if self.keep_output(dst_path, &[&bytes], String::new(), mode) {
+                return Ok(());
+            }
+            return self.write_output(tpl_path, dst_path, &bytes, "copy", &err_ctx);
+        }
+        let template = String::from_utf8(bytes).owe_data().with(&err_ctx)?;
+        if self.engine.engine_of(tpl_path) == &TplEngine::GoTmpl {
+            if is_gotmpl_partial(tpl_path) {
+                debug!("partial:{}", tpl_path.display());
+                return Ok(());
+            }
+            let data = serde_json::to_value(data).owe_data().with(&err_ctx)?;
+            let partials = gotmpl_partials(tpl_path)?;
+            let values = self.values_of(&data, None)?;
+            let mode = self.output_mode(tpl_path)?;
+            if self.keep_output(
+                dst_path,
+                &[template.as_bytes(), partials.as_bytes()],
+                values,
+                mode,
+            ) {
+                return Ok(());
+            }
+            let completed = render_gotmpl(template.as_str(), partials.as_str(), &data)
+                .owe_biz()
+                .with(&err_ctx)?;
+            return self.write_output(tpl_path, dst_path, completed.as_bytes(), "render", &err_ctx);
+        }
 
         //let convert = TplCoverter::new("[[", "]]", "{{", "}}", CommentLabel::yml_style());
         let template = self
@@ -147,31 +375,73 @@ impl LocalizeTemplate<'_> {
             .with(&err_ctx)
             .owe(ModReason::Localize.into())?;
         //let mut dst_file = File::create(dst_path).owe_conf()?;
+        let values = self.values_of(data, value_roots(template.as_str()).as_deref())?;
+        let mode = self.output_mode(tpl_path)?;
+        if self.keep_output(dst_path, &[template.as_bytes()], values, mode) {
+            return Ok(());
+        }
 
-        let rendered_data = self
+        let rendered_data = match self
             .handlebars
             //.render_template_to_write(&template, data, &mut dst_file)
-            .render_data(&template, data)
-            .owe_biz()
-            .with(&err_ctx)?;
+            .render_template(&template, data)
+        {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                // 未定义的变量直接失败, 不写出该产物
+                if let RenderErrorReason::MissingVariable(name) = e.reason() {
+                    let mut missing = Vec::new();
+                    let name = name.clone().unwrap_or_default();
+                    push_missing(&mut missing, tpl_path, e.line_no.unwrap_or_default(), &name);
+                    return MainReason::from(LocalizeReason::Undefined(missing.join("\n")))
+                        .err_result()
+                        .with(&err_ctx);
+                }
+                return Err(e).owe_biz().with(&err_ctx);
+            }
+        };
         let completed = self
             .cust_cover
             .restore(rendered_data)
             .with(&err_ctx)
             .owe(ModReason::Localize.into())?;
+        self.write_output(tpl_path, dst_path, completed.as_bytes(), "render", &err_ctx)
+    }
+
+    fn write_output(
+        &self,
+        tpl_path: &Path,
+        dst_path: &Path,
+        completed: &[u8],
+        action: &str,
+        err_ctx: &WithContext,
+    ) -> MainResult<()> {
+        if let Some(diff) = &self.diff {
+            return diff
+                .borrow_mut()
+                .compare(dst_path, String::from_utf8_lossy(completed).as_ref());
+        }
+
+        // 5. 准备目标文件
+        if let Some(parent) = dst_path.parent() {
+            std::fs::create_dir_all(parent).owe_sys()?;
+        }
+        if dst_path.symlink_metadata().is_ok() {
+            std::fs::remove_file(dst_path).owe_sys()?;
+        }
         std::fs::write(dst_path, completed)
             .owe_conf()
             .with(dst_path)?;
         #[cfg(unix)]
-        {
+        if let Some(mode) = self.output_mode(tpl_path).with(err_ctx)? {
             use std::os::unix::fs::PermissionsExt;
-            let perms = std::fs::Permissions::from_mode(0o644); // rw-r--r--
+            let perms = std::fs::Permissions::from_mode(mode);
             std::fs::set_permissions(dst_path, perms)
                 .owe_sys()
-                .with(&err_ctx)?;
+                .with(err_ctx)?;
         }
         println!(
-            "render {:30} ---> {}",
+            "{action} {:30} ---> {}",
             tpl_path.display(),
             dst_path.display()
         );
@@ -179,12 +449,173 @@ impl LocalizeTemplate<'_> {
         debug!("Successfully generated: {}", dst_path.display());
         Ok(())
     }
+
+    // 权限: setting.yml 中声明的优先, 否则沿用模板文件
+    #[cfg(unix)]
+    fn output_mode(&self, tpl_path: &Path) -> MainResult<Option<u32>> {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(mode) = self.modes.mode_of(tpl_path) {
+            return Ok(Some(mode));
+        }
+        let meta = std::fs::metadata(tpl_path).owe_sys().with(tpl_path)?;
+        Ok(Some(meta.permissions().mode() & 0o7777))
+    }
+    #[cfg(not(unix))]
+    fn output_mode(&self, _tpl_path: &Path) -> MainResult<Option<u32>> {
+        Ok(None)
+    }
+
+    // 增量模式下计算产物依赖的 value 摘要, roots 为 None 时依赖全部 value
+    fn values_of<T: Serialize>(&self, data: &T, roots: Option<&[String]>) -> MainResult<String> {
+        if self.incremental.is_none() {
+            return Ok(String::new());
+        }
+        values_digest(data, roots)
+    }
+
+    // 增量模式下, 模板/value/权限均未变的产物直接保留
+    fn keep_output(
+        &self,
+        dst_path: &Path,
+        template: &[&[u8]],
+        values: String,
+        mode: Option<u32>,
+    ) -> bool {
+        match &self.incremental {
+            Some(incremental) => incremental.keep(
+                dst_path,
+                RenderEntry::new(digest_bytes(template), values, mode),
+            ),
+            None => false,
+        }
+    }
+}
+
+impl LocalizeTemplate<'_> {
+    // 符号链接按原链接目标在 local/ 中重建, 不跟随
+    fn link_impl(
+        &self,
+        tpl_root: &Path,
+        tpl_path: &Path,
+        dst_path: &Path,
+        templatize: &TemplatePath,
+    ) -> MainResult<()> {
+        if !templatize.is_include(tpl_path) {
+            return Ok(());
+        }
+        let link = std::fs::read_link(tpl_path).owe_sys().with(tpl_path)?;
+        if !link_inside(tpl_root, tpl_path, &link) {
+            return Err(StructError::from_conf(format!(
+                "symlink target {} is absolute or outside template",
+                link.display()
+            )))
+            .with(tpl_path);
+        }
+        if let Some(diff) = &self.diff {
+            return diff.borrow_mut().compare_link(dst_path, &link);
+        }
+        if self.keep_output(
+            dst_path,
+            &[link.to_string_lossy().as_bytes()],
+            String::new(),
+            None,
+        ) {
+            return Ok(());
+        }
+        if let Some(parent) = dst_path.parent() {
+            std::fs::create_dir_all(parent).owe_sys()?;
+        }
+        if dst_path.symlink_metadata().is_ok() {
+            std::fs::remove_file(dst_path).owe_sys().with(dst_path)?;
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&link, dst_path)
+            .owe_sys()
+            .with(dst_path)?;
+        #[cfg(windows)]
+        std::os::windows::fs::symlink_file(&link, dst_path)
+            .owe_sys()
+            .with(dst_path)?;
+        println!("link {:30} ---> {}", tpl_path.display(), link.display());
+        Ok(())
+    }
+}
+
+// 模板依赖的顶层变量; 引用整个上下文(this/@root/partial)时视为依赖全部 value
+fn value_roots(template: &str) -> Option<Vec<String>> {
+    let refs = template_refs(template).ok()?;
+    if refs.iter().any(|x| x.is_whole()) {
+        return None;
+    }
+    let mut roots: Vec<String> = refs.iter().map(|x| x.root().to_string()).collect();
+    roots.sort();
+    roots.dedup();
+    Some(roots)
+}
+
+// 链接目标须为相对路径, 且不能离开模板目录
+fn link_inside(tpl_root: &Path, tpl_path: &Path, link: &Path) -> bool {
+    let Some(Ok(base)) = tpl_path.parent().map(|x| x.strip_prefix(tpl_root)) else {
+        return false;
+    };
+    let mut stack: Vec<Component> = base.components().collect();
+    for comp in link.components() {
+        match comp {
+            Component::Normal(_) => stack.push(comp),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if stack.pop().is_none() {
+                    return false;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+// 含 NUL 字节的文件视为二进制
+fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(8000).any(|x| *x == 0)
+}
+
+// helm 约定: _ 开头的 .tpl 只放 define, 不单独输出
+pub(crate) fn is_gotmpl_partial(tpl_path: &Path) -> bool {
+    let name = tpl_path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    name.starts_with('_') && name.ends_with(".tpl")
+}
+
+pub(crate) fn gotmpl_partials(tpl_path: &Path) -> MainResult<String> {
+    let mut partials = String::new();
+    let Some(dir) = tpl_path.parent() else {
+        return Ok(partials);
+    };
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .owe_sys()
+        .with(dir)?
+        .filter_map(|x| x.ok().map(|x| x.path()))
+        .filter(|x| x.is_file() && is_gotmpl_partial(x))
+        .collect();
+    files.sort();
+    for file in files {
+        partials += std::fs::read_to_string(&file)
+            .owe_data()
+            .with(&file)?
+            .as_str();
+    }
+    Ok(partials)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::module::setting::TemplatePath;
+    use crate::module::{
+        diff::FileChange,
+        setting::{ModeRule, TemplatePath},
+    };
     use orion_error::TestAssert;
     use tempfile::tempdir;
 
@@ -296,6 +727,259 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_dry_run_not_write() {
+        let tmp_dir = tempdir().unwrap();
+        let tpl_dir = tmp_dir.path().join("templates");
+        let output_dir = tmp_dir.path().join("output");
+        std::fs::create_dir_all(&tpl_dir).unwrap();
+        std::fs::create_dir_all(&output_dir).unwrap();
+
+        std::fs::write(tpl_dir.join("main.hbs"), "Main: {{title}}").unwrap();
+        std::fs::write(tpl_dir.join("new.hbs"), "New: {{title}}").unwrap();
+        std::fs::write(output_dir.join("main.hbs"), "Main: Old").unwrap();
+        std::fs::write(output_dir.join("stale.txt"), "stale").unwrap();
+
+        let data = serde_json::json!({"title": "Test"});
+        let localizer = LocalizeTemplate::default().with_dry_run();
+        localizer
+            .render_json(&tpl_dir, &output_dir, &data, &TemplatePath::default())
+            .assert();
+        let mut diff = localizer.take_diff().unwrap();
+        diff.collect_removed(&output_dir).assert();
+
+        // 磁盘内容保持不变
+        assert_eq!(
+            std::fs::read_to_string(output_dir.join("main.hbs")).unwrap(),
+            "Main: Old"
+        );
+        assert!(!output_dir.join("new.hbs").exists());
+        assert!(output_dir.join("stale.txt").exists());
+
+        let change_of = |name: &str| {
+            diff.files()
+                .iter()
+                .find(|x| x.path() == &output_dir.join(name))
+                .map(|x| x.change().clone())
+        };
+        assert_eq!(change_of("main.hbs"), Some(FileChange::Changed));
+        assert_eq!(change_of("new.hbs"), Some(FileChange::Added));
+        assert_eq!(change_of("stale.txt"), Some(FileChange::Removed));
+    }
+
+    #[test]
+    fn test_strict_collect_undefined() {
+        let tmp_dir = tempdir().unwrap();
+        let tpl_dir = tmp_dir.path().join("templates");
+        std::fs::create_dir_all(&tpl_dir).unwrap();
+        std::fs::write(
+            tpl_dir.join("a.conf"),
+            "port={{MYSQL_PORT}}\nbad={{MYSQL_PROT}}\n{{#if TLS}}tls{{/if}}",
+        )
+        .unwrap();
+        std::fs::write(tpl_dir.join("b.conf"), "db={{db.nmae}}\nok={{db.name}}").unwrap();
+        std::fs::write(
+            tpl_dir.join("c.conf"),
+            "a={{default \"a b\" OPT}}\n{{#if X}}x{{else if Y}}y{{/if}}\nb={{upper (lower SUB_MISS)}}",
+        )
+        .unwrap();
+        std::fs::write(tpl_dir.join("d.conf"), "{{#each items}}{{nmae}}{{/each}}").unwrap();
+        let data =
+            serde_json::json!({"MYSQL_PORT": 3306, "db": {"name": "x"}, "items": [{"name": "n"}]});
+
+        let out_dir = tmp_dir.path().join("out");
+        let msg = LocalizeTemplate::default()
+            .with_strict(true)
+            .render_json(&tpl_dir, &out_dir, &data, &TemplatePath::default())
+            .unwrap_err()
+            .to_string();
+        // 检查先于渲染, 不写出任何产物
+        assert!(!out_dir.exists());
+        assert!(msg.contains("a.conf:2: MYSQL_PROT"));
+        assert!(msg.contains("b.conf:1: db.nmae"));
+        assert!(!msg.contains("TLS"));
+        assert!(!msg.contains("db.name"));
+        assert!(msg.contains("c.conf:3: SUB_MISS"));
+        assert!(msg.contains("d.conf:1:"));
+        assert!(msg.contains("nmae"));
+        assert!(!msg.contains("OPT"));
+        assert!(!msg.contains("b\""));
+        assert!(!msg.contains(": if"));
+        assert!(!msg.contains(": Y"));
+
+        LocalizeTemplate::default()
+            .render_json(
+                &tpl_dir,
+                &tmp_dir.path().join("out2"),
+                &data,
+                &TemplatePath::default(),
+            )
+            .assert();
+        assert!(tmp_dir.path().join("out2").join("a.conf").exists());
+
+        let gotmpl = EngineSelect::new(TplEngine::GoTmpl, &[], &tpl_dir);
+        assert!(
+            LocalizeTemplate::default()
+                .with_strict(true)
+                .with_engine(gotmpl)
+                .render_json(
+                    &tpl_dir,
+                    &tmp_dir.path().join("out3"),
+                    &data,
+                    &TemplatePath::default(),
+                )
+                .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_binary_mode_symlink() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp_dir = tempdir().unwrap();
+        let tpl_dir = tmp_dir.path().join("templates");
+        let out_dir = tmp_dir.path().join("output");
+        std::fs::create_dir_all(&tpl_dir).unwrap();
+        let bin = vec![0x7f, b'E', b'L', b'F', 0, 1, 0xff, b'{', b'{'];
+        std::fs::write(tpl_dir.join("tool.bin"), &bin).unwrap();
+        let latin1 = vec![b'c', b'a', b'f', 0xe9, b'{', b'{'];
+        std::fs::write(tpl_dir.join("latin1.txt"), &latin1).unwrap();
+        std::fs::write(tpl_dir.join("start.sh"), "echo {{name}}").unwrap();
+        std::fs::set_permissions(
+            tpl_dir.join("start.sh"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        std::fs::write(tpl_dir.join("app.conf"), "name={{name}}").unwrap();
+        std::os::unix::fs::symlink("app.conf", tpl_dir.join("current.conf")).unwrap();
+
+        let modes = FileModes::new(&[ModeRule::new("app.conf", "0600")], &tpl_dir);
+        let data = serde_json::json!({"name": "demo"});
+        LocalizeTemplate::default()
+            .with_modes(modes)
+            .render_json(&tpl_dir, &out_dir, &data, &TemplatePath::default())
+            .assert();
+
+        assert_eq!(std::fs::read(out_dir.join("tool.bin")).unwrap(), bin);
+        assert_eq!(std::fs::read(out_dir.join("latin1.txt")).unwrap(), latin1);
+        let mode_of = |name: &str| {
+            std::fs::metadata(out_dir.join(name))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777
+        };
+        assert_eq!(mode_of("start.sh"), 0o755);
+        assert_eq!(mode_of("app.conf"), 0o600);
+        assert_eq!(
+            std::fs::read_link(out_dir.join("current.conf")).unwrap(),
+            PathBuf::from("app.conf")
+        );
+        assert_eq!(
+            std::fs::read_to_string(out_dir.join("current.conf")).unwrap(),
+            "name=demo"
+        );
+
+        // dry-run 记录链接目标的变化, 不改动已有链接
+        std::fs::remove_file(tpl_dir.join("current.conf")).unwrap();
+        std::os::unix::fs::symlink("start.sh", tpl_dir.join("current.conf")).unwrap();
+        let localizer = LocalizeTemplate::default().with_dry_run();
+        localizer
+            .render_json(&tpl_dir, &out_dir, &data, &TemplatePath::default())
+            .assert();
+        let diff = localizer.take_diff().unwrap();
+        let link = diff
+            .files()
+            .iter()
+            .find(|x| x.path() == &out_dir.join("current.conf"))
+            .unwrap();
+        assert_eq!(link.change(), &FileChange::Changed);
+        assert!(link.patch().contains("-> start.sh"));
+        assert_eq!(
+            std::fs::read_link(out_dir.join("current.conf")).unwrap(),
+            PathBuf::from("app.conf")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reject_escaping_symlink() {
+        let tmp_dir = tempdir().unwrap();
+        let tpl_dir = tmp_dir.path().join("templates");
+        std::fs::create_dir_all(tpl_dir.join("sub")).unwrap();
+        std::fs::write(tpl_dir.join("app.conf"), "app").unwrap();
+        std::os::unix::fs::symlink("../app.conf", tpl_dir.join("sub/app.conf")).unwrap();
+        let render = |name: &str| {
+            LocalizeTemplate::default().render_json(
+                &tpl_dir,
+                &tmp_dir.path().join(name),
+                &serde_json::json!({}),
+                &TemplatePath::default(),
+            )
+        };
+        render("out1").assert();
+
+        std::os::unix::fs::symlink("/etc/passwd", tpl_dir.join("passwd")).unwrap();
+        assert!(render("out2").is_err());
+        std::fs::remove_file(tpl_dir.join("passwd")).unwrap();
+
+        std::os::unix::fs::symlink("../../secret", tpl_dir.join("sub/secret")).unwrap();
+        assert!(render("out3").is_err());
+    }
+
+    #[test]
+    fn test_incremental_render() {
+        let tmp_dir = tempdir().unwrap();
+        let tpl_dir = tmp_dir.path().join("templates");
+        let out_dir = tmp_dir.path().join("output");
+        std::fs::create_dir_all(tpl_dir.join("sub")).unwrap();
+        std::fs::write(tpl_dir.join("a.conf"), "a={{a}}").unwrap();
+        std::fs::write(tpl_dir.join("b.conf"), "b={{b}}").unwrap();
+        std::fs::write(tpl_dir.join("sub/c.conf"), "c").unwrap();
+        std::fs::write(tpl_dir.join("d.conf"), "d={{upper (default \"x\" d)}}").unwrap();
+
+        let render = |data: serde_json::Value| {
+            let prev = RenderManifest::load(&out_dir).unwrap().unwrap_or_default();
+            let mut localizer = LocalizeTemplate::default()
+                .with_incremental(&out_dir, prev)
+                .unwrap();
+            localizer
+                .render_json(&tpl_dir, &out_dir, &data, &TemplatePath::default())
+                .assert();
+            localizer.finish_incremental().unwrap().unwrap()
+        };
+        let manifest = render(serde_json::json!({"a": 1, "b": 1, "d": "p"}));
+        assert_eq!(manifest.files().len(), 4);
+
+        // 输入未变的产物不会被重写
+        std::fs::write(out_dir.join("a.conf"), "kept").unwrap();
+        std::fs::remove_file(tpl_dir.join("sub/c.conf")).unwrap();
+        // 只在子表达式中引用的 value 变化也要重新渲染
+        render(serde_json::json!({"a": 1, "b": 2, "d": "q"}));
+        let read = |name: &str| std::fs::read_to_string(out_dir.join(name)).unwrap();
+        assert_eq!(read("a.conf"), "kept");
+        assert_eq!(read("b.conf"), "b=2");
+        assert_eq!(read("d.conf"), "d=Q");
+        assert!(!out_dir.join("sub/c.conf").exists());
+
+        // 严格检查失败时保留已有产物与渲染清单
+        let prev = RenderManifest::load(&out_dir).unwrap().unwrap();
+        let mut localizer = LocalizeTemplate::default()
+            .with_strict(true)
+            .with_incremental(&out_dir, prev.clone())
+            .unwrap();
+        let data = serde_json::json!({"a": 2, "d": "q"});
+        assert!(
+            localizer
+                .render_json(&tpl_dir, &out_dir, &data, &TemplatePath::default())
+                .is_err()
+        );
+        assert_eq!(read("a.conf"), "kept");
+        assert_eq!(read("b.conf"), "b=2");
+        let manifest = RenderManifest::load(&out_dir).unwrap().unwrap();
+        assert_eq!(manifest.files(), prev.files());
+    }
+
     #[test]
     fn test_helm_nginx_rendering() {
         let root_dir = PathBuf::from("./test_data/helm");
@@ -0,0 +1,218 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use derive_getters::Getters;
+use log::debug;
+use orion_common::serde::{Configable, Yamlable};
+use orion_error::{ErrorOwe, ErrorWith};
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{const_vars::RENDER_MANIFEST_YML, error::MainResult};
+
+// 单个产物的输入: 模板摘要 与 其依赖的 value 摘要
+#[derive(Clone, Debug, PartialEq, Getters, Serialize, Deserialize)]
+pub struct RenderEntry {
+    template: String,
+    values: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    mode: Option<u32>,
+}
+
+impl RenderEntry {
+    pub fn new(template: String, values: String, mode: Option<u32>) -> Self {
+        Self {
+            template,
+            values,
+            mode,
+        }
+    }
+}
+
+// local/ 下的渲染清单, key 为相对 local/ 的产物路径
+#[derive(Clone, Debug, Default, Getters, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RenderManifest {
+    files: BTreeMap<String, RenderEntry>,
+}
+
+impl RenderManifest {
+    pub fn load(local: &Path) -> MainResult<Option<Self>> {
+        let path = local.join(RENDER_MANIFEST_YML);
+        if path.exists() {
+            let manifest = Self::from_conf(&path).owe_conf().with(&path)?;
+            return Ok(Some(manifest));
+        }
+        Ok(None)
+    }
+    pub fn save(&self, local: &Path) -> MainResult<()> {
+        let path = local.join(RENDER_MANIFEST_YML);
+        if path.exists() {
+            std::fs::remove_file(&path).owe_sys().with(&path)?;
+        }
+        self.save_yml(&path).owe_res().with(&path)?;
+        Ok(())
+    }
+}
+
+// 增量本地化: 对比上次清单, 跳过输入未变的产物, 并记录本次清单
+pub struct IncrementalRender {
+    local: PathBuf,
+    prev: RenderManifest,
+    cur: RefCell<RenderManifest>,
+}
+
+impl IncrementalRender {
+    // 清单先从磁盘移除, 中途失败时下次将完整重建
+    pub fn begin(local: &Path, prev: RenderManifest) -> MainResult<Self> {
+        let path = local.join(RENDER_MANIFEST_YML);
+        if path.exists() {
+            std::fs::remove_file(&path).owe_sys().with(&path)?;
+        }
+        Ok(Self {
+            local: local.to_path_buf(),
+            prev,
+            cur: RefCell::new(RenderManifest::default()),
+        })
+    }
+
+    fn key(&self, dst: &Path) -> String {
+        dst.strip_prefix(&self.local)
+            .unwrap_or(dst)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    // 记入本次清单; 输入未变且产物仍存在时返回 true
+    pub fn keep(&self, dst: &Path, entry: RenderEntry) -> bool {
+        let key = self.key(dst);
+        let fresh = self.prev.files.get(&key) == Some(&entry) && dst.symlink_metadata().is_ok();
+        if fresh {
+            debug!("keep:{}", dst.display());
+        }
+        self.cur.borrow_mut().files.insert(key, entry);
+        fresh
+    }
+
+    // 写出产物前中止(如严格检查失败)时, 放回上次的清单
+    pub fn restore(&self) -> MainResult<()> {
+        if self.prev.files.is_empty() {
+            return Ok(());
+        }
+        self.prev.save(&self.local)
+    }
+
+    // 删除模板已不存在的产物, 保存本次清单
+    pub fn finish(self) -> MainResult<RenderManifest> {
+        let cur = self.cur.into_inner();
+        for key in self.prev.files.keys() {
+            if cur.files.contains_key(key) {
+                continue;
+            }
+            let path = self.local.join(key);
+            if path.symlink_metadata().is_ok() {
+                std::fs::remove_file(&path).owe_sys().with(&path)?;
+                println!("remove {}", path.display());
+            }
+            remove_empty_parents(&path, &self.local);
+        }
+        cur.save(&self.local)?;
+        Ok(cur)
+    }
+}
+
+fn remove_empty_parents(path: &Path, root: &Path) {
+    let mut cur = path.parent();
+    while let Some(dir) = cur {
+        if dir == root || !dir.starts_with(root) || std::fs::remove_dir(dir).is_err() {
+            break;
+        }
+        cur = dir.parent();
+    }
+}
+
+pub fn digest_bytes(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+        hasher.update([0u8]);
+    }
+    let hex: String = hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect();
+    format!("sha256:{hex}")
+}
+
+// roots 为 None 时依赖全部 value
+pub fn values_digest<T: serde::Serialize>(
+    data: &T,
+    roots: Option<&[String]>,
+) -> MainResult<String> {
+    let data = serde_json::to_value(data).owe_data()?;
+    let used = match (roots, &data) {
+        (Some(roots), serde_json::Value::Object(map)) => {
+            let used: BTreeMap<&String, Option<&serde_json::Value>> =
+                roots.iter().map(|x| (x, map.get(x))).collect();
+            serde_json::to_vec(&used).owe_data()?
+        }
+        _ => serde_json::to_vec(&data).owe_data()?,
+    };
+    Ok(digest_bytes(&[&used]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_values_digest_roots() {
+        let data = serde_json::json!({"a": 1, "b": {"c": 2}});
+        let other = serde_json::json!({"a": 1, "b": {"c": 3}});
+        let roots = vec!["a".to_string()];
+        assert_eq!(
+            values_digest(&data, Some(&roots)).unwrap(),
+            values_digest(&other, Some(&roots)).unwrap()
+        );
+        assert_ne!(
+            values_digest(&data, None).unwrap(),
+            values_digest(&other, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_incremental_keep_and_remove() {
+        let tmp_dir = tempdir().unwrap();
+        let local = tmp_dir.path().to_path_buf();
+        let kept = local.join("a.conf");
+        let stale = local.join("sub/old.conf");
+        std::fs::create_dir_all(stale.parent().unwrap()).unwrap();
+        std::fs::write(&kept, "a").unwrap();
+        std::fs::write(&stale, "old").unwrap();
+        let entry = |x: &str| RenderEntry::new(x.to_string(), "v".to_string(), None);
+
+        let render = IncrementalRender::begin(&local, RenderManifest::default()).unwrap();
+        assert!(!render.keep(&kept, entry("t1")));
+        assert!(!render.keep(&stale, entry("t1")));
+        render.finish().unwrap();
+
+        let prev = RenderManifest::load(&local).unwrap().unwrap();
+        assert_eq!(prev.files().len(), 2);
+        let render = IncrementalRender::begin(&local, prev).unwrap();
+        assert!(RenderManifest::load(&local).unwrap().is_none());
+        assert!(render.keep(&kept, entry("t1")));
+        render.finish().unwrap();
+        assert!(kept.exists());
+        assert!(!stale.exists());
+        assert!(!local.join("sub").exists());
+
+        let prev = RenderManifest::load(&local).unwrap().unwrap();
+        let render = IncrementalRender::begin(&local, prev).unwrap();
+        assert!(!render.keep(&kept, entry("t2")));
+    }
+}
@@ -1,8 +1,14 @@
 pub mod depend;
+pub mod diff;
+pub mod gotmpl;
+pub mod helpers;
 pub mod init;
+pub mod lint;
 pub mod localize;
+pub mod manifest;
 pub mod metrc;
 pub mod model;
+pub mod package;
 mod prelude;
 pub mod proj;
 pub mod refs;
@@ -17,8 +23,13 @@ use std::{fmt::Display, str::FromStr};
 pub enum CpuArch {
     #[display("x86")]
     X86,
+    // 64 位 arm, 与 arm64 等价
     #[display("arm")]
     Arm,
+    #[display("arm64")]
+    Arm64,
+    #[display("armv7")]
+    Armv7,
 }
 
 impl FromStr for CpuArch {
@@ -28,34 +39,86 @@ impl FromStr for CpuArch {
         match s {
             "x86" => Ok(Self::X86),
             "arm" => Ok(Self::Arm),
+            "arm64" => Ok(Self::Arm64),
+            "armv7" => Ok(Self::Armv7),
             _ => Err(s.to_string()),
         }
     }
 }
 
+impl CpuArch {
+    // 可互相替代的架构: arm 与 arm64 为同一指令集
+    pub fn is_compatible(&self, other: &CpuArch) -> bool {
+        self == other
+            || matches!(
+                (self, other),
+                (CpuArch::Arm, CpuArch::Arm64) | (CpuArch::Arm64, CpuArch::Arm)
+            )
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Display, PartialEq, Eq, Hash)]
 pub enum OsCPE {
     #[display("mac14")]
     MAC14,
+    #[display("mac15")]
+    MAC15,
     #[display("win10")]
     WIN10,
+    #[display("ubt20")]
+    UBT20,
     #[display("ubt22")]
     UBT22,
+    #[display("ubt24")]
+    UBT24,
+    #[display("debian12")]
+    DEB12,
     #[display("cos7")]
     COS7,
+    #[display("rocky9")]
+    ROCKY9,
+    #[display("alpine")]
+    ALPINE,
 }
 
+// 标识, 系列, 版本; 新增 OS 只需在此登记
+static OS_CPE_TABLE: &[(OsCPE, &str, &str, u32)] = &[
+    (OsCPE::MAC14, "mac14", "macos", 14),
+    (OsCPE::MAC15, "mac15", "macos", 15),
+    (OsCPE::WIN10, "win10", "windows", 10),
+    (OsCPE::UBT20, "ubt20", "ubuntu", 2004),
+    (OsCPE::UBT22, "ubt22", "ubuntu", 2204),
+    (OsCPE::UBT24, "ubt24", "ubuntu", 2404),
+    (OsCPE::DEB12, "debian12", "debian", 12),
+    (OsCPE::COS7, "cos7", "rhel", 7),
+    (OsCPE::ROCKY9, "rocky9", "rhel", 9),
+    (OsCPE::ALPINE, "alpine", "alpine", 0),
+];
+
 impl FromStr for OsCPE {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "mac14" => Ok(Self::MAC14),
-            "win10" => Ok(Self::WIN10),
-            "ubt22" => Ok(Self::UBT22),
-            "cos7" => Ok(Self::COS7),
-            _ => Err(s.to_string()),
-        }
+        OS_CPE_TABLE
+            .iter()
+            .find(|(_, id, _, _)| *id == s)
+            .map(|(os, _, _, _)| os.clone())
+            .ok_or_else(|| s.to_string())
+    }
+}
+
+impl OsCPE {
+    fn entry(&self) -> &'static (OsCPE, &'static str, &'static str, u32) {
+        OS_CPE_TABLE
+            .iter()
+            .find(|(os, _, _, _)| os == self)
+            .expect("os cpe not in table")
+    }
+    pub fn family(&self) -> &'static str {
+        self.entry().2
+    }
+    pub fn version(&self) -> u32 {
+        self.entry().3
     }
 }
 
@@ -124,16 +187,32 @@ impl ModelSTD {
         let arch = match arch_str.as_str() {
             "x86" | "x86_64" | "amd64" => CpuArch::X86,
             "arm" | "aarch64" | "arm64" => CpuArch::Arm,
+            "armv7" | "armv7l" | "armhf" => CpuArch::Armv7,
             _ => CpuArch::X86, // 默认使用X86
         };
 
-        // 根据操作系统确定OsCPE
-        // TODO: need version;
+        // 根据操作系统及主版本确定OsCPE
+        let major = match info.version() {
+            os_info::Version::Semantic(major, _, _) => *major,
+            os_info::Version::Custom(v) => v
+                .split(|c: char| !c.is_ascii_digit())
+                .find(|x| !x.is_empty())
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(0),
+            _ => 0,
+        };
         let os = match info.os_type() {
+            os_info::Type::Macos if major >= 15 => OsCPE::MAC15,
             os_info::Type::Macos => OsCPE::MAC14,
             os_info::Type::Windows => OsCPE::WIN10,
+            os_info::Type::Ubuntu if major == 20 => OsCPE::UBT20,
+            os_info::Type::Ubuntu if major >= 24 => OsCPE::UBT24,
             os_info::Type::Ubuntu => OsCPE::UBT22,
+            os_info::Type::Debian => OsCPE::DEB12,
+            os_info::Type::RockyLinux | os_info::Type::AlmaLinux => OsCPE::ROCKY9,
+            os_info::Type::CentOS | os_info::Type::Redhat if major >= 9 => OsCPE::ROCKY9,
             os_info::Type::CentOS | os_info::Type::Redhat => OsCPE::COS7,
+            os_info::Type::Alpine => OsCPE::ALPINE,
             _ => OsCPE::UBT22, // 默认使用Ubuntu 22.04
         };
 
@@ -171,6 +250,35 @@ impl ModelSTD {
     pub fn new(arch: CpuArch, os: OsCPE, spc: RunSPC) -> Self {
         Self { arch, os, spc }
     }
+
+    // 以 provided 替代当前 target 的代价, None 表示不可替代;
+    // 规则: 运行空间一致, CPU 兼容, OS 同系列且版本不高于当前
+    pub fn fallback_cost(&self, provided: &ModelSTD) -> Option<u32> {
+        if self == provided {
+            return Some(0);
+        }
+        if self.spc != provided.spc
+            || !self.arch.is_compatible(&provided.arch)
+            || self.os.family() != provided.os.family()
+            || provided.os.version() > self.os.version()
+        {
+            return None;
+        }
+        let arch_cost = if self.arch == provided.arch { 0 } else { 1 };
+        Some(1 + arch_cost * 100_000 + (self.os.version() - provided.os.version()))
+    }
+
+    // 优先精确匹配, 否则选择代价最小的可替代 target
+    pub fn select<'a, I>(&self, candidates: I) -> Option<&'a ModelSTD>
+    where
+        I: IntoIterator<Item = &'a ModelSTD>,
+    {
+        candidates
+            .into_iter()
+            .filter_map(|x| self.fallback_cost(x).map(|cost| (cost, x)))
+            .min_by_key(|(cost, _)| *cost)
+            .map(|(_, x)| x)
+    }
 }
 
 impl Display for ModelSTD {
@@ -193,3 +301,47 @@ impl FromStr for ModelSTD {
         Ok(ModelSTD { arch, os, spc })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(s: &str) -> ModelSTD {
+        ModelSTD::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_model_parse_new_target() {
+        for s in [
+            "arm64-mac15-host",
+            "armv7-alpine-host",
+            "x86-debian12-k8s",
+            "x86-rocky9-host",
+            "x86-ubt24-host",
+        ] {
+            assert_eq!(model(s).to_string(), s);
+        }
+        assert!(ModelSTD::from_str("x86-ubt18-host").is_err());
+    }
+
+    #[test]
+    fn test_model_fallback_select() {
+        let provided = vec![
+            model("x86-ubt20-host"),
+            model("x86-ubt22-host"),
+            model("x86-ubt22-k8s"),
+            model("arm-mac14-host"),
+        ];
+        let want = model("x86-ubt24-host");
+        assert_eq!(want.select(&provided), Some(&provided[1]));
+        assert_eq!(model("x86-ubt22-k8s").select(&provided), Some(&provided[2]));
+        assert_eq!(
+            model("arm64-mac15-host").select(&provided),
+            Some(&provided[3])
+        );
+        // 不向更高版本/其他系列/不兼容 CPU 回退
+        assert_eq!(model("x86-ubt20-k8s").select(&provided), None);
+        assert_eq!(model("x86-debian12-host").select(&provided), None);
+        assert_eq!(model("armv7-mac15-host").select(&provided), None);
+    }
+}
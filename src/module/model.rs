@@ -1,12 +1,20 @@
 use orion_variate::ext::ArtifactPackage;
+use orion_variate::vars::EnvEvalable;
 
 use super::prelude::*;
 use crate::{
+    artifact::{ArtifactCache, ArtifactDigests, artifact_source, fetch_artifact},
     const_vars::{
-        DEFAULT_VALUE_FILE, LOCAL_DIR, SAMPLE_VALUE_FILE, USED_JSON, USED_READABLE_FILE,
-        USER_VALUE_FILE, VALUE_DIR,
+        ARTIFACT_DIGEST_YML, ARTIFACT_DIR, DEFAULT_VALUE_FILE, LOCAL_DIR, SAMPLE_VALUE_FILE,
+        USED_JSON, USED_READABLE_FILE, USER_VALUE_FILE, USER_VALUE_STEM, VALUE_DIR,
+        VAR_EXPORTS_YML,
     },
+    error::ModReason,
+    lock::LockFile,
     predule::*,
+    project::ValueExplain,
+    secret::{mark_sensitive, sensitive_vars},
+    software::VarRules,
     types::{Localizable, ValuePath},
 };
 use std::{fs::read_to_string, str::FromStr};
@@ -15,7 +23,8 @@ use super::{
     ModelSTD,
     depend::DependencySet,
     localize::LocalizeTemplate,
-    setting::{Setting, TemplateConfig},
+    manifest::RenderManifest,
+    setting::{EngineSelect, FileModes, Setting, TemplateConfig},
 };
 
 #[derive(Getters, Clone, Debug, Serialize)]
@@ -28,6 +37,10 @@ pub struct ModModelSpec {
     local: Option<PathBuf>,
     setting: Option<Setting>,
     depends: DependencySet,
+    rules: VarRules,
+    exports: ValueDict,
+    digests: ArtifactDigests,
+    sensitive: Vec<String>,
 }
 
 impl ModModelSpec {
@@ -35,13 +48,29 @@ impl ModModelSpec {
         self.depends = depends;
         self
     }
+    pub fn with_rules(mut self, rules: VarRules) -> Self {
+        self.rules = rules;
+        self
+    }
+    pub fn with_exports(mut self, exports: ValueDict) -> Self {
+        self.exports = exports;
+        self
+    }
+    pub fn with_digests(mut self, digests: ArtifactDigests) -> Self {
+        self.digests = digests;
+        self
+    }
+    pub fn with_sensitive(mut self, sensitive: Vec<String>) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
 
     fn build_used_value(
         &self,
         options: LocalizeOptions,
         value_paths: &TargetValuePaths,
     ) -> Result<OriginDict, StructError<MainReason>> {
-        crate::project::mix_used_value(options, value_paths, &self.vars)
+        crate::project::mix_used_value(options, value_paths, &self.vars, &self.rules)
     }
 
     fn crate_sample_value_file(
@@ -67,9 +96,53 @@ impl ModModelSpec {
 impl LocalUpdate for ModModelSpec {
     async fn update_local(&self, path: &Path, options: &UpdateOptions) -> AddrResult<UpdateUnit> {
         //self.conf_spec.update_local(path, options).await?;
-        self.depends.update(options).await?;
+        self.update_jobs(path, options, 0).await
+    }
+}
+impl ModModelSpec {
+    // 与 update_local 相同, jobs 为依赖的并发更新数
+    pub async fn update_jobs(
+        &self,
+        path: &Path,
+        options: &UpdateOptions,
+        jobs: usize,
+    ) -> AddrResult<UpdateUnit> {
+        self.depends.update_units(options, jobs).await?;
         Ok(UpdateUnit::new(path.to_path_buf(), self.vars.clone()))
     }
+    // 与 update_local 相同, 同时返回依赖的锁定信息; pinned 为 --locked 时该模块的锁
+    pub async fn update_lock(
+        &self,
+        path: &Path,
+        options: &UpdateOptions,
+        pinned: Option<&LockFile>,
+        jobs: usize,
+    ) -> MainResult<(UpdateUnit, LockFile)> {
+        let units = self
+            .depends
+            .update_units(options, jobs)
+            .await
+            .owe(MainReason::from(ModReason::Update))?;
+        let lock = DependencySet::lock_units(units, pinned)?;
+        self.fetch_artifacts(path, options).await?;
+        Ok((UpdateUnit::new(path.to_path_buf(), self.vars.clone()), lock))
+    }
+
+    // 仅获取声明了摘要的制品, 其余仍由 gxl download 流程下载
+    pub async fn fetch_artifacts(&self, path: &Path, options: &UpdateOptions) -> MainResult<()> {
+        if self.digests.is_empty() {
+            return Ok(());
+        }
+        let cache = ArtifactCache::from_env();
+        let dst_dir = path.join(ARTIFACT_DIR);
+        for art in self.artifact.iter() {
+            let (local, _) = artifact_source(art)?;
+            if let Some(digest) = self.digests.find(local.as_str()) {
+                fetch_artifact(art, digest, &dst_dir, &cache, options).await?;
+            }
+        }
+        Ok(())
+    }
 }
 impl ModModelSpec {
     pub fn save_main(&self, root: &Path, name: Option<String>) -> MainResult<()> {
@@ -104,8 +177,10 @@ pub struct ModTargetPaths {
     spec_path: PathBuf,
     conf_path: PathBuf,
     vars_path: PathBuf,
+    exports_path: PathBuf,
     setting_path: PathBuf,
     artifact_path: PathBuf,
+    digest_path: PathBuf,
     workflow_path: PathBuf,
     depends_path: PathBuf,
 }
@@ -116,8 +191,10 @@ impl From<&PathBuf> for ModTargetPaths {
             target_root: target_root.to_path_buf(),
             conf_path: spec_path.join(CONF_SPEC_YML),
             vars_path: target_root.join(VARS_YML),
+            exports_path: target_root.join(VAR_EXPORTS_YML),
             setting_path: target_root.join(SETTING_YML),
             artifact_path: spec_path.join(ARTIFACT_YML),
+            digest_path: spec_path.join(ARTIFACT_DIGEST_YML),
             depends_path: spec_path.join(DEPENDS_YML),
             workflow_path: target_root.to_path_buf(),
             spec_path,
@@ -138,7 +215,9 @@ impl From<&PathBuf> for TargetValuePaths {
         Self {
             used_readable: value_root.join(USED_READABLE_FILE),
             default_value_file: value_root.join(DEFAULT_VALUE_FILE),
-            user_value_file: value_root.join(USER_VALUE_FILE),
+            // value.yml 不存在时也可使用 value.json/value.toml/value.ini
+            user_value_file: crate::project::find_value_file(value_root, USER_VALUE_STEM)
+                .unwrap_or(value_root.join(USER_VALUE_FILE)),
             sample_value_file: value_root.join(SAMPLE_VALUE_FILE),
             used_json_path: value_root.join(crate::const_vars::USED_JSON),
         }
@@ -166,6 +245,14 @@ impl Persistable<ModModelSpec> for ModModelSpec {
 
         self.depends.save_conf(paths.depends_path()).owe_logic()?;
         self.vars.save_conf(paths.vars_path()).owe_logic()?;
+        mark_sensitive(paths.vars_path(), &self.sensitive)?;
+        self.rules.mark_vars(paths.vars_path())?;
+        if !self.exports.is_empty() {
+            self.exports.save_conf(paths.exports_path()).owe_logic()?;
+        }
+        if !self.digests.is_empty() {
+            self.digests.save_conf(paths.digest_path()).owe_logic()?;
+        }
         self.gxl_prj.save_to(&paths.target_root, None)?;
         flag.mark_suc();
         Ok(())
@@ -207,6 +294,26 @@ impl Persistable<ModModelSpec> for ModModelSpec {
         let vars = VarCollection::from_conf(paths.vars_path())
             .with(&ctx)
             .owe_logic()?;
+        let sensitive = sensitive_vars(paths.vars_path()).with(&ctx)?;
+        let rules = VarRules::from_vars(paths.vars_path()).with(&ctx)?;
+        let exports = if paths.exports_path().exists() {
+            ctx.with_path("exports", paths.exports_path());
+            ValueDict::from_conf(paths.exports_path())
+                .with(&ctx)
+                .owe_logic()?
+        } else {
+            ValueDict::new()
+        };
+        let digests = if paths.digest_path().exists() {
+            ctx.with_path("digests", paths.digest_path());
+            let digests = ArtifactDigests::from_conf(paths.digest_path())
+                .with(&ctx)
+                .owe_logic()?;
+            digests.validate().with(&ctx)?;
+            digests
+        } else {
+            ArtifactDigests::default()
+        };
 
         let gxl_prj = GxlProject::load_from(paths.target_root()).with(&ctx)?;
         flag.mark_suc();
@@ -220,6 +327,10 @@ impl Persistable<ModModelSpec> for ModModelSpec {
             setting,
             depends,
             gxl_prj,
+            rules,
+            exports,
+            digests,
+            sensitive,
         })
     }
 }
@@ -242,7 +353,37 @@ impl ModModelSpec {
             vars,
             setting,
             depends: DependencySet::default(),
+            rules: VarRules::default(),
+            exports: ValueDict::new(),
+            digests: ArtifactDigests::default(),
+            sensitive: Vec::new(),
+        }
+    }
+    // 按 exports.yml 以本模块最终取值计算导出值
+    pub fn export_value(
+        &self,
+        dst_path: Option<ValuePath>,
+        options: LocalizeOptions,
+    ) -> MainResult<ValueDict> {
+        if self.exports.is_empty() {
+            return Ok(ValueDict::new());
         }
+        let local = self
+            .local
+            .clone()
+            .ok_or(MainReason::from(ElementReason::Miss("local-path".into())).to_err())?;
+        let localize_path = dst_path.unwrap_or(ValuePath::new(local.join(VALUE_DIR)));
+        let value_paths = TargetValuePaths::from(localize_path.path());
+        let used = self.build_used_value(options, &value_paths)?;
+        Ok(self.exports.clone().env_eval(&used.export_dict()))
+    }
+    pub fn explain_value(
+        &self,
+        parent: ValuePath,
+        options: LocalizeOptions,
+    ) -> MainResult<Vec<ValueExplain>> {
+        let value_paths = TargetValuePaths::from(parent.path());
+        crate::project::explain_used_value(options, &value_paths, &self.vars, &self.sensitive)
     }
     pub fn get_local_values(&self, parent: ValuePath) -> MainResult<Option<String>> {
         let value_paths = TargetValuePaths::from(parent.path());
@@ -277,21 +418,9 @@ impl Localizable for ModModelSpec {
 
         let value_root = localize_path.path(); //.join(VALUE_DIR);
         let value_paths = TargetValuePaths::from(value_root);
-        let used_value_path = ensure_path(local.join(VALUE_DIR)).owe_logic()?;
-        let used_value_file = used_value_path.join(USED_JSON);
         let local_path = local.join(LOCAL_DIR);
-        debug!( target:"spec/mod/target", "localize mod-target begin: {}" ,local_path.display() );
-        make_clean_path(&local_path).owe_logic()?;
+        let dry_run = options.dry_run();
         ctx.with_path("dst", &local_path);
-        self.crate_sample_value_file(&value_paths)?;
-        debug!(target : "/mod/target/loc", "value export");
-        let used = self.build_used_value(options, &value_paths)?;
-        used.export_origin()
-            .save_valconf(value_paths.used_readable())
-            .owe_res()?;
-        used.export_value().save_json(&used_value_file).owe_res()?;
-
-        debug!(target : "/mod/target/loc", "use value: {}", used_value_file.display());
         let tpl_path_opt = self
             .setting
             .as_ref()
@@ -307,14 +436,101 @@ impl Localizable for ModModelSpec {
             .and_then(|x| x.templatize_cust().clone())
             .map(TemplateConfig::from);
 
+        let strict = options.strict()
+            || self
+                .setting
+                .as_ref()
+                .and_then(|x| x.localize().clone())
+                .and_then(|x| *x.strict())
+                .unwrap_or(false);
+        let modes = self
+            .setting
+            .as_ref()
+            .and_then(|x| x.localize().clone())
+            .map(|x| FileModes::new(x.file_modes(), &local))
+            .unwrap_or_default();
+        let engine = self
+            .setting
+            .as_ref()
+            .and_then(|x| x.localize().clone())
+            .map(|x| {
+                EngineSelect::new(
+                    x.engine().clone().unwrap_or_default(),
+                    x.engine_rules(),
+                    &local,
+                )
+            })
+            .unwrap_or_default();
         let localizer = if let Some(cust) = tpl_custom {
             LocalizeTemplate::new(cust)
         } else {
             LocalizeTemplate::default()
-        };
+        }
+        .with_strict(strict)
+        .with_engine(engine)
+        .with_modes(modes);
+        if dry_run {
+            debug!(target : "/mod/target/loc", "dry-run value export");
+            let (used, mask) = crate::project::mix_used_value_masked(
+                options,
+                &value_paths,
+                &self.vars,
+                &self.rules,
+                &self.sensitive,
+            )?;
+            let data = serde_json::to_value(used.export_value())
+                .owe_data()
+                .with(&ctx)?;
+            let localizer = localizer.with_dry_run();
+            localizer
+                .render_json(&tpl, &local_path, &data, &tpl_path)
+                .with(&ctx)?;
+            if let Some(mut diff) = localizer.take_diff() {
+                diff.collect_removed(&local_path).with(&ctx)?;
+                println!("--- {} : {}", self.model(), local_path.display());
+                diff.print(&mask);
+            }
+            flag.mark_suc();
+            return Ok(());
+        }
+
+        let used_value_path = ensure_path(local.join(VALUE_DIR)).owe_logic()?;
+        let used_value_file = used_value_path.join(USED_JSON);
+        debug!( target:"spec/mod/target", "localize mod-target begin: {}" ,local_path.display() );
+        // 有渲染清单时增量更新 local/, 否则完整重建
+        let prev = RenderManifest::load(&local_path).ok().flatten();
+        if prev.is_none() {
+            make_clean_path(&local_path).owe_logic()?;
+        }
+        let mut localizer = localizer
+            .with_incremental(&local_path, prev.unwrap_or_default())
+            .with(&ctx)?;
+        self.crate_sample_value_file(&value_paths)?;
+        debug!(target : "/mod/target/loc", "value export");
+        let (used, mask) = crate::project::mix_used_value_masked(
+            options,
+            &value_paths,
+            &self.vars,
+            &self.rules,
+            &self.sensitive,
+        )?;
+        // 可读副本不含解密后的明文与 sensitive 变量的值
+        let readable = used.export_origin();
+        if mask.is_empty() {
+            readable
+                .save_valconf(value_paths.used_readable())
+                .owe_res()?;
+        } else {
+            mask.save_readable(&readable, value_paths.used_readable())?;
+        }
+        used.export_value().save_json(&used_value_file).owe_res()?;
+
+        debug!(target : "/mod/target/loc", "use value: {}", used_value_file.display());
+        // 严格检查先于写出, 失败时上次的产物与渲染清单保持不变
         localizer
             .render_path(&tpl, &local_path, &used_value_file, &tpl_path)
             .with(&ctx)?;
+        localizer.finish_incremental().with(&ctx)?;
         flag.mark_suc();
         Ok(())
     }
@@ -0,0 +1,292 @@
+use std::fs::File;
+use std::io::Read;
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use indexmap::IndexMap;
+
+use super::ModelSTD;
+use super::prelude::*;
+use crate::{
+    artifact::{DigestKind, file_digest},
+    const_vars::{ARTIFACT_DIR, LOCAL_DIR, MOD_DIR, MOD_MANIFEST_YML},
+    error::ModReason,
+    predule::*,
+};
+
+#[derive(Clone, Debug, Getters, Serialize, Deserialize, PartialEq)]
+pub struct ManifestTarget {
+    model: ModelSTD,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    vars: Vec<String>,
+}
+impl ManifestTarget {
+    pub fn new(model: ModelSTD, vars: Vec<String>) -> Self {
+        Self { model, vars }
+    }
+}
+
+// 模块包清单: 随 mod/ 目录一起打入 name-version.tar.gz
+#[derive(Clone, Debug, Getters, Serialize, Deserialize)]
+pub struct ModManifest {
+    name: String,
+    version: String,
+    targets: Vec<ManifestTarget>,
+    // 包内相对路径 -> sha256
+    #[serde(default)]
+    checksums: IndexMap<String, String>,
+}
+
+impl ModManifest {
+    pub fn new<S: Into<String>>(name: S, version: S, targets: Vec<ManifestTarget>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            targets,
+            checksums: IndexMap::new(),
+        }
+    }
+    pub fn package_name(&self) -> String {
+        format!("{}-{}.tar.gz", self.name, self.version)
+    }
+
+    pub fn verify(&self, root: &Path) -> MainResult<()> {
+        for (rel, sum) in self.checksums.iter() {
+            let path = root.join(rel);
+            if !path.is_file() {
+                return MainReason::from(ModReason::Package(format!("{rel}: missing")))
+                    .err_result();
+            }
+            let actual = file_digest(&path, DigestKind::Sha256)?;
+            if &actual != sum {
+                return MainReason::from(ModReason::Package(format!(
+                    "{rel}: checksum mismatch, expect {sum}, actual {actual}"
+                )))
+                .err_result();
+            }
+        }
+        Ok(())
+    }
+}
+
+// mod/ 下需要打包的文件, 跳过各 target 的 local/ 与 artifacts/
+fn package_files(prj_root: &Path) -> MainResult<Vec<PathBuf>> {
+    let mod_root = prj_root.join(MOD_DIR);
+    let walker = walkdir::WalkDir::new(&mod_root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|x| {
+            let name = x.file_name().to_string_lossy();
+            if name == ".git" {
+                return false;
+            }
+            !(x.depth() == 2 && (name == LOCAL_DIR || name == ARTIFACT_DIR))
+        });
+    let mut files = Vec::new();
+    for entry in walker {
+        let entry = entry.owe_sys().with(&mod_root)?;
+        if entry.file_type().is_file() {
+            let rel = entry.path().strip_prefix(prj_root).owe_logic()?;
+            files.push(rel.to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+// 将 prj_root 下的 mod/ 与清单打包到 out_dir, 返回包路径
+pub fn write_package(
+    prj_root: &Path,
+    mut manifest: ModManifest,
+    out_dir: &Path,
+) -> MainResult<PathBuf> {
+    let files = package_files(prj_root)?;
+    for rel in files.iter() {
+        let sum = file_digest(&prj_root.join(rel), DigestKind::Sha256)?;
+        manifest
+            .checksums
+            .insert(rel.to_string_lossy().replace('\\', "/"), sum);
+    }
+    std::fs::create_dir_all(out_dir).owe_res().with(out_dir)?;
+    let pkg_path = out_dir.join(manifest.package_name());
+    let file = File::create(&pkg_path).owe_res().with(&pkg_path)?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let data = serde_yaml::to_string(&manifest).owe_data()?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, MOD_MANIFEST_YML, data.as_bytes())
+        .owe_res()
+        .with(&pkg_path)?;
+    for rel in files.iter() {
+        builder
+            .append_path_with_name(prj_root.join(rel), rel)
+            .owe_res()
+            .with(rel)?;
+    }
+    builder
+        .into_inner()
+        .and_then(|x| x.finish())
+        .owe_res()
+        .with(&pkg_path)?;
+    Ok(pkg_path)
+}
+
+fn package_err<T>(msg: String) -> MainResult<T> {
+    MainReason::from(ModReason::Package(msg)).err_result()
+}
+
+// 按 gzip 头与首个条目为清单识别模块包, 下载后的文件名不一定保留 .tar.gz 后缀
+pub fn is_package(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+    let mut magic = [0u8; 2];
+    let is_gzip = File::open(path)
+        .and_then(|mut x| x.read_exact(&mut magic))
+        .is_ok()
+        && magic == [0x1f, 0x8b];
+    is_gzip && first_entry(path).is_some_and(|x| x == MOD_MANIFEST_YML)
+}
+
+fn first_entry(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let entry = archive.entries().ok()?.next()?.ok()?;
+    let name = entry.path().ok()?.to_string_lossy().replace('\\', "/");
+    Some(name)
+}
+
+// 解包到 dst 并按清单校验; 清单须为首个条目, 清单之外的条目一律拒绝
+pub fn unpack_package(pkg_path: &Path, dst: &Path) -> MainResult<ModManifest> {
+    make_clean_path(dst).owe_res().with(dst)?;
+    let file = File::open(pkg_path).owe_res().with(pkg_path)?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut manifest: Option<ModManifest> = None;
+    for entry in archive.entries().owe_res().with(pkg_path)? {
+        let mut entry = entry.owe_res().with(pkg_path)?;
+        let rel = entry
+            .path()
+            .owe_res()
+            .with(pkg_path)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        match &manifest {
+            None if rel == MOD_MANIFEST_YML => {
+                let mut data = String::new();
+                entry.read_to_string(&mut data).owe_res().with(pkg_path)?;
+                let manifest_path = dst.join(MOD_MANIFEST_YML);
+                std::fs::write(&manifest_path, data.as_str())
+                    .owe_res()
+                    .with(&manifest_path)?;
+                manifest = Some(
+                    serde_yaml::from_str(data.as_str())
+                        .owe_conf()
+                        .with(pkg_path)?,
+                );
+            }
+            None => {
+                return package_err(format!(
+                    "{}: no {MOD_MANIFEST_YML} at first entry",
+                    pkg_path.display()
+                ));
+            }
+            Some(listed) => {
+                if !entry.header().entry_type().is_file() || !listed.checksums.contains_key(&rel) {
+                    return package_err(format!("{rel}: not listed in {MOD_MANIFEST_YML}"));
+                }
+                entry.unpack_in(dst).owe_res().with(pkg_path)?;
+            }
+        }
+    }
+    let Some(manifest) = manifest else {
+        return package_err(format!("{}: no {MOD_MANIFEST_YML}", pkg_path.display()));
+    };
+    manifest.verify(dst)?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_package_roundtrip() -> MainResult<()> {
+        let tmp_dir = tempdir().owe_res()?;
+        let prj_root = tmp_dir.path().join("prj");
+        let target = prj_root.join("mod/x86-ubt22-k8s");
+        std::fs::create_dir_all(target.join("spec")).owe_res()?;
+        std::fs::create_dir_all(target.join("local")).owe_res()?;
+        std::fs::write(target.join("spec/vars.yml"), "vars: []").owe_res()?;
+        std::fs::write(target.join("local/out.txt"), "out").owe_res()?;
+
+        let manifest = ModManifest::new(
+            "mysql",
+            "0.2.0",
+            vec![ManifestTarget::new(
+                ModelSTD::x86_ubt22_k8s(),
+                vec!["PORT".into()],
+            )],
+        );
+        let pkg = write_package(&prj_root, manifest, tmp_dir.path())?;
+        assert!(pkg.ends_with("mysql-0.2.0.tar.gz"));
+        assert!(is_package(&pkg));
+
+        let dst = tmp_dir.path().join("unpack");
+        let loaded = unpack_package(&pkg, &dst)?;
+        assert_eq!(loaded.version(), "0.2.0");
+        assert!(
+            loaded
+                .checksums()
+                .contains_key("mod/x86-ubt22-k8s/spec/vars.yml")
+        );
+        assert!(!dst.join("mod/x86-ubt22-k8s/local").exists());
+
+        std::fs::write(dst.join("mod/x86-ubt22-k8s/spec/vars.yml"), "vars: [1]").owe_res()?;
+        assert!(loaded.verify(&dst).is_err());
+        Ok(())
+    }
+
+    fn make_tar_gz(path: &Path, entries: &[(&str, &str)]) -> MainResult<()> {
+        let file = File::create(path).owe_res()?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, data.as_bytes())
+                .owe_res()?;
+        }
+        builder.into_inner().and_then(|x| x.finish()).owe_res()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_reject_unlisted() -> MainResult<()> {
+        let tmp_dir = tempdir().owe_res()?;
+        let plain = tmp_dir.path().join("plain.tar.gz");
+        make_tar_gz(&plain, &[("mod/x86-ubt22-k8s/spec/vars.yml", "vars: []")])?;
+        assert!(!is_package(&plain));
+
+        let manifest = ModManifest::new("mysql", "0.2.0", Vec::new());
+        let data = serde_yaml::to_string(&manifest).owe_data()?;
+        let pkg = tmp_dir.path().join("mysql-0.2.0.tar.gz");
+        make_tar_gz(
+            &pkg,
+            &[
+                (MOD_MANIFEST_YML, data.as_str()),
+                ("mod/extra.sh", "rm -rf /"),
+            ],
+        )?;
+        assert!(is_package(&pkg));
+        let err = unpack_package(&pkg, &tmp_dir.path().join("unpack")).unwrap_err();
+        assert!(err.to_string().contains("mod/extra.sh: not listed"));
+        assert!(!tmp_dir.path().join("unpack/mod/extra.sh").exists());
+        Ok(())
+    }
+}
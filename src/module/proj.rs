@@ -1,8 +1,10 @@
 use super::prelude::*;
 use crate::const_vars::{
     BITNAMI_COMMON_GIT_URL, MOD_PRJ_CONF_FILE_V1, MOD_PRJ_CONF_FILE_V2, MOD_PRJ_TEST_ROOT,
+    MOD_VERSION_FILE, VALUE_DIR,
 };
 use crate::error::ModReason;
+use crate::ia_value::{ia_value_setting, var_prompts};
 use crate::module::init::MOD_PRJ_ROOT_FILE;
 use crate::predule::*;
 use crate::types::{Localizable, ValuePath};
@@ -12,8 +14,11 @@ use crate::{
     const_vars::MODULES_SPC_ROOT,
     module::{
         depend::{Dependency, DependencySet},
+        model::TargetValuePaths,
+        package::{ManifestTarget, ModManifest, write_package},
         spec::ModuleSpec,
     },
+    secret::SecretKey,
     workflow::prj::GxlProject,
 };
 
@@ -103,19 +108,21 @@ impl ModProject {
 }
 
 impl ModConf {
-    pub async fn update(&self, options: &UpdateOptions) -> MainResult<()> {
+    pub async fn update(&self, options: &UpdateOptions, jobs: usize) -> MainResult<()> {
         self.test_envs
-            .update(options)
+            .update_units(options, jobs)
             .await
+            .map(|_| ())
             .owe(ModReason::Update.into())
     }
 }
 
 impl ModProject {
-    pub async fn update(&self, options: &UpdateOptions) -> MainResult<()> {
-        self.conf.update(options).await?;
+    // jobs 为依赖的并发更新数, 0 取默认值
+    pub async fn update(&self, options: &UpdateOptions, jobs: usize) -> MainResult<()> {
+        self.conf.update(options, jobs).await?;
         self.mod_spec()
-            .update_local(self.root_local(), options)
+            .update_jobs(self.root_local(), options, jobs)
             .await
             .owe(ModReason::Update.into())?;
         Ok(())
@@ -149,6 +156,49 @@ impl Localizable for ModProject {
     }
 }
 impl ModProject {
+    // 按 version.txt 打包为 out_dir/name-version.tar.gz
+    pub fn package(&self, out_dir: &Path) -> MainResult<PathBuf> {
+        let version_path = self.root_local().join(MOD_VERSION_FILE);
+        if !version_path.exists() {
+            return MainReason::from(ModReason::Miss(format!("{}", version_path.display())))
+                .err_result();
+        }
+        let version = std::fs::read_to_string(&version_path)
+            .owe_res()
+            .with(&version_path)?;
+        let targets = self
+            .mod_spec()
+            .targets()
+            .iter()
+            .map(|(model, spec)| {
+                let vars = spec
+                    .vars()
+                    .value_dict()
+                    .keys()
+                    .map(|x| x.to_string())
+                    .collect();
+                ManifestTarget::new(model.clone(), vars)
+            })
+            .collect();
+        let manifest = ModManifest::new(self.mod_spec().name().as_str(), version.trim(), targets);
+        let pkg_path = write_package(self.root_local(), manifest, out_dir)?;
+        info!(target: "/mod_prj", "package mod-prj to {}", pkg_path.display());
+        Ok(pkg_path)
+    }
+    // 按变量类型交互设定各 target 的 values/value.yml
+    pub fn ia_setting(&self, all: bool) -> MainResult<()> {
+        let key = SecretKey::load(self.root_local())?;
+        for (model, spec) in self.mod_spec().targets() {
+            let Some(local) = spec.local() else {
+                continue;
+            };
+            let value_paths = TargetValuePaths::from(&local.join(VALUE_DIR));
+            let prompts = var_prompts(spec.vars(), spec.rules(), spec.sensitive())?;
+            println!("Setting variables for {model}");
+            ia_value_setting(&prompts, value_paths.user_value_file(), all, key.as_ref())?;
+        }
+        Ok(())
+    }
     pub fn make_new(prj_path: &Path, name: &str) -> MainResult<Self> {
         let mod_spec = ModuleSpec::make_new(name)?;
         let res = DependencySet::default();
@@ -213,7 +263,7 @@ pub mod tests {
         project.save().assert("save dss_prj");
         let project = ModProject::load(&prj_path).assert("dss-project");
         project
-            .update(&UpdateOptions::default())
+            .update(&UpdateOptions::default(), 0)
             .await
             .assert("spec.update_local");
 
@@ -5,8 +5,14 @@ use crate::predule::*;
 use orion_error::UvsLogicFrom;
 
 use super::ModelSTD;
+use super::package::{is_package, unpack_package};
+use crate::lock::{LockEntry, LockFile, digest_path, git_revision};
 use crate::types::{Localizable, LocalizeOptions, ValuePath};
-use crate::{const_vars::MOD_DIR, error::MainResult, module::model::ModModelSpec};
+use crate::{
+    const_vars::{ARTIFACT_DIR, LOCAL_DIR, MOD_DIR, VALUE_DIR},
+    error::MainResult,
+    module::model::ModModelSpec,
+};
 
 #[derive(Getters, Clone, Debug, Serialize, Deserialize)]
 pub struct ModuleSpecRef {
@@ -16,6 +22,12 @@ pub struct ModuleSpecRef {
     model: ModelSTD,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     enable: Option<bool>,
+    // 强依赖: 所依赖模块必须存在且启用
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    requires: Vec<String>,
+    // 弱依赖: 仅在模块存在时约束先后顺序
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    after: Vec<String>,
     #[serde(skip)]
     local: Option<PathBuf>,
 }
@@ -31,6 +43,8 @@ impl ModuleSpecRef {
             addr: addr.into(),
             model: node,
             enable: None,
+            requires: Vec::new(),
+            after: Vec::new(),
             local: None,
         }
     }
@@ -39,6 +53,15 @@ impl ModuleSpecRef {
         self
     }
 
+    pub fn with_requires<S: Into<String>>(mut self, requires: Vec<S>) -> Self {
+        self.requires = requires.into_iter().map(Into::into).collect();
+        self
+    }
+    pub fn with_after<S: Into<String>>(mut self, after: Vec<S>) -> Self {
+        self.after = after.into_iter().map(Into::into).collect();
+        self
+    }
+
     pub fn is_enable(&self) -> bool {
         self.enable.unwrap_or(true)
     }
@@ -48,12 +71,28 @@ impl ModuleSpecRef {
     pub fn set_local(&mut self, local: PathBuf) {
         self.local = Some(local);
     }
+    // 模块中实际使用的 target: 精确匹配优先, 否则按 ModelSTD::select 回退
+    pub fn resolve_target(&self, target_root: &Path) -> Option<ModelSTD> {
+        if !target_root.exists() {
+            return None;
+        }
+        let provided: Vec<ModelSTD> = get_sub_dirs(target_root)
+            .ok()?
+            .iter()
+            .filter_map(|x| x.file_name()?.to_str()?.parse::<ModelSTD>().ok())
+            .collect();
+        let model = self.model().select(&provided)?.clone();
+        if &model != self.model() {
+            info!(target: "/mod/ref", "mod {} use fallback target {} for {}", self.name, model, self.model());
+        }
+        Some(model)
+    }
     pub fn get_target_spec(&self) -> MainResult<Option<ModModelSpec>> {
         if self.is_enable() {
             if let Some(local) = &self.local {
                 let target_root = local.join(self.name());
-                let target_path = target_root.join(self.model().to_string());
-                if target_path.exists() {
+                if let Some(model) = self.resolve_target(&target_root) {
+                    let target_path = target_root.join(model.to_string());
                     let spec = ModModelSpec::load_from(&target_path)
                         .with(&target_root)
                         .owe(MainReason::from(ModReason::Load))?;
@@ -70,7 +109,9 @@ impl ModuleSpecRef {
         &self,
         _sys_root: &Path,
         options: &UpdateOptions,
-    ) -> MainResult<UpdateUnit> {
+        pinned: Option<&LockFile>,
+        jobs: usize,
+    ) -> MainResult<(UpdateUnit, LockFile)> {
         //trace!(target: "spec/mod/",  "{:?}",self );
         if let Some(local) = &self.local {
             let mut flag = auto_exit_log!(
@@ -79,39 +120,86 @@ impl ModuleSpecRef {
             );
             std::fs::create_dir_all(local).owe_res().with(local)?;
             let target_root = local.join(self.name());
-            let target_path = target_root.join(self.model().to_string());
-            if !target_path.exists() || options.clean_cache() {
-                let tmp_name = "__mod";
+            let mut revision = None;
+            if self.resolve_target(&target_root).is_none() || options.clean_cache() {
+                // 各模块共用 local 且并发更新, 临时目录按模块名区分
+                let tmp_name = format!("__mod_{}", self.name());
                 let prj_path = self
                     .addr
-                    .update_local_rename(local, tmp_name, options)
+                    .update_local_rename(local, tmp_name.as_str(), options)
                     .await
                     .owe(MainReason::from(ModReason::Update))?;
-                let mod_path = prj_path.position().join(MOD_DIR);
-                let tmp_path = local.join(tmp_name);
+                let tmp_path = local.join(&tmp_name);
+                let pkg_path = local.join(format!("__mod_pkg_{}", self.name()));
+                let clean_tmp = || -> MainResult<()> {
+                    for tmp in [&tmp_path, &pkg_path] {
+                        if tmp.is_dir() {
+                            std::fs::remove_dir_all(tmp).owe_sys()?;
+                        } else if tmp.exists() {
+                            std::fs::remove_file(tmp).owe_sys()?;
+                        }
+                    }
+                    Ok(())
+                };
+                if let Some(pinned) = pinned {
+                    pinned.pin(self.name(), prj_path.position())?;
+                }
+                revision = git_revision(prj_path.position());
+                let mut prj_root = prj_path.position().clone();
+                // 模块包(name-version.tar.gz): 解包校验后按普通目录处理
+                if is_package(&prj_root) {
+                    let manifest = unpack_package(&prj_root, &pkg_path)?;
+                    info!(target: "/mod/ref", "unpack mod package {} {}", manifest.name(), manifest.version());
+                    revision = Some(manifest.version().clone());
+                    std::fs::remove_file(&prj_root).owe_sys()?;
+                    prj_root = pkg_path.clone();
+                }
+                let mod_path = prj_root.join(MOD_DIR);
+                // --locked: 覆盖本地模块前先与锁比较, 不一致时保留原有内容
+                if let Some(pinned) = pinned {
+                    let diffs = match self.lock_entry(&mod_path, revision.clone())? {
+                        Some(fetched) => pinned.check(&fetched),
+                        None => Vec::new(),
+                    };
+                    if !diffs.is_empty() {
+                        clean_tmp()?;
+                        return MainReason::from(ModReason::Locked(diffs.join("\n"))).err_result();
+                    }
+                }
                 make_clean_path(&target_root).owe_res()?;
 
                 std::fs::rename(&mod_path, &target_root)
                     .owe_logic()
                     .with(("from", &mod_path))
                     .with(("to", &target_root))?;
-                if tmp_path.exists() {
-                    std::fs::remove_dir_all(tmp_path).owe_sys()?;
-                }
+                clean_tmp()?;
             }
 
             debug!(target: "mod/ref",  "update target success!" );
-            //let target_path = target_root.join(self.node().to_string());
+            let Some(model) = self.resolve_target(&target_root) else {
+                return MainReason::from(ModReason::Miss(format!(
+                    "mod {} has no target for {}",
+                    self.name,
+                    self.model()
+                )))
+                .err_result();
+            };
+            let target_path = target_root.join(model.to_string());
             let spec = ModModelSpec::load_from(&target_path)
                 .with(&target_root)
                 .owe(MainReason::from(ModReason::Load))?;
-            let unit = spec
-                .update_local(&target_path, options)
-                .await
-                .owe(MainReason::from(ModReason::Update))?;
-            ModModelSpec::clean_other(&target_root, self.model())?;
+            let mod_pinned = pinned.map(|x| x.sub(self.name()));
+            let (unit, deps_lock) = spec
+                .update_lock(&target_path, options, mod_pinned.as_ref(), jobs)
+                .await?;
+            ModModelSpec::clean_other(&target_root, &model)?;
+            let mut lock = LockFile::default();
+            if let Some(entry) = self.lock_entry(&target_root, revision)? {
+                lock.push(entry);
+            }
+            lock.append(self.name(), deps_lock);
             flag.mark_suc();
-            return Ok(unit);
+            return Ok((unit, lock));
         } else {
             Err(MainError::from_logic(
                 "no local value in ModuleSpecRef ".into(),
@@ -119,10 +207,41 @@ impl ModuleSpecRef {
         }
     }
 
+    // mod_root 下实际使用的 target 的锁定信息
+    fn lock_entry(
+        &self,
+        mod_root: &Path,
+        revision: Option<String>,
+    ) -> MainResult<Option<LockEntry>> {
+        let Some(model) = self.resolve_target(mod_root) else {
+            return Ok(None);
+        };
+        let digest = digest_path(
+            &mod_root.join(model.to_string()),
+            &[LOCAL_DIR, VALUE_DIR, ARTIFACT_DIR],
+        )?;
+        Ok(Some(
+            LockEntry::new(self.name(), self.addr.clone(), digest).with_revision(revision),
+        ))
+    }
+
     pub fn spec_value_path(&self, parent: ValuePath) -> ValuePath {
         let value = PathBuf::from(self.name());
         parent.join(value)
     }
+
+    // 模块导出值, 供依赖它的模块以 ${mods.<name>.<KEY>} 引用
+    pub fn export_value(
+        &self,
+        dst_path: Option<ValuePath>,
+        options: LocalizeOptions,
+    ) -> MainResult<ValueDict> {
+        if let Some(spec) = self.get_target_spec()? {
+            let cur_dst_path = dst_path.map(|x| self.spec_value_path(x));
+            return spec.export_value(cur_dst_path, options);
+        }
+        Ok(ValueDict::new())
+    }
 }
 
 #[async_trait]
@@ -139,7 +258,10 @@ impl Localizable for ModuleSpecRef {
                     error!(target: "spec/mod/", "localize mod {} fail!", self.name )
                 );
                 let mod_path = local.join(self.name.as_str());
-                let target_path = mod_path.join(self.model().to_string());
+                let model = self
+                    .resolve_target(&mod_path)
+                    .unwrap_or(self.model().clone());
+                let target_path = mod_path.join(model.to_string());
                 let spec =
                     ModModelSpec::load_from(&target_path).owe(MainReason::from(ModReason::Load))?;
                 //if let Some(dst) = &dst_path {
@@ -1,7 +1,7 @@
 use derive_getters::Getters;
 use serde_derive::{Deserialize, Serialize};
 
-use super::{TemplateCustom, TemplateTargets};
+use super::{EngineRule, ModeRule, TemplateCustom, TemplateTargets, TplEngine};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Getters)]
 pub struct LocalizeConf {
@@ -9,6 +9,17 @@ pub struct LocalizeConf {
     templatize_path: Option<TemplateTargets>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     templatize_cust: Option<TemplateCustom>,
+    // 严格渲染: 模板引用未定义变量时报错
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    strict: Option<bool>,
+    // 渲染引擎: 整个 target 的默认值, 以及按路径(glob)的覆盖
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    engine: Option<TplEngine>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    engine_rules: Vec<EngineRule>,
+    // 输出文件权限, 未声明时沿用模板文件自身的权限
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    file_modes: Vec<ModeRule>,
 }
 
 impl LocalizeConf {
@@ -16,6 +27,10 @@ impl LocalizeConf {
         Self {
             templatize_path: Some(TemplateTargets::example()),
             templatize_cust: Some(TemplateCustom::example()),
+            strict: None,
+            engine: None,
+            engine_rules: Vec::new(),
+            file_modes: Vec::new(),
         }
     }
 }
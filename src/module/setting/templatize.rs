@@ -1,4 +1,7 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+use log::warn;
 
 use derive_getters::Getters;
 use serde_derive::{Deserialize, Serialize};
@@ -42,6 +45,107 @@ impl TemplateTargets {
     }
 }
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub enum TplEngine {
+    #[default]
+    #[serde(rename = "handlebars")]
+    Handlebars,
+    // go template, 兼容 helm 模板语法
+    #[serde(rename = "gotmpl")]
+    GoTmpl,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+pub struct EngineRule {
+    path: String,
+    engine: TplEngine,
+}
+impl EngineRule {
+    pub fn new<S: Into<String>>(path: S, engine: TplEngine) -> Self {
+        Self {
+            path: path.into(),
+            engine,
+        }
+    }
+}
+
+// 按模板路径选择渲染引擎, 路径规则与 TemplatePath 相同
+#[derive(Clone, Debug, Default, Getters)]
+pub struct EngineSelect {
+    default: TplEngine,
+    rules: Vec<(PathBuf, TplEngine)>,
+}
+impl EngineSelect {
+    pub fn new(default: TplEngine, rules: &[EngineRule], root: &Path) -> Self {
+        let rules = rules
+            .iter()
+            .map(|x| (root.join(x.path()), x.engine().clone()))
+            .collect();
+        Self { default, rules }
+    }
+    pub fn engine_of(&self, tpl: &Path) -> &TplEngine {
+        self.rules
+            .iter()
+            .find(|(path, _)| path_match(path, tpl))
+            .map(|(_, engine)| engine)
+            .unwrap_or(&self.default)
+    }
+}
+
+// 输出文件权限, mode 为八进制字符串, 如 "0755"
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+pub struct ModeRule {
+    path: String,
+    mode: String,
+}
+impl ModeRule {
+    pub fn new<S: Into<String>>(path: S, mode: S) -> Self {
+        Self {
+            path: path.into(),
+            mode: mode.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Getters)]
+pub struct FileModes {
+    rules: Vec<(PathBuf, u32)>,
+}
+impl FileModes {
+    pub fn new(rules: &[ModeRule], root: &Path) -> Self {
+        let rules = rules
+            .iter()
+            .filter_map(|x| {
+                let mode = x.mode().trim_start_matches("0o");
+                match u32::from_str_radix(mode, 8) {
+                    Ok(mode) => Some((root.join(x.path()), mode)),
+                    Err(_) => {
+                        warn!("ignore bad file mode {} for {}", x.mode(), x.path());
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+    pub fn mode_of(&self, tpl: &Path) -> Option<u32> {
+        self.rules
+            .iter()
+            .find(|(path, _)| path_match(path, tpl))
+            .map(|(_, mode)| *mode)
+    }
+}
+
+fn path_match(rule: &Path, tpl: &Path) -> bool {
+    if tpl.starts_with(rule) {
+        return true;
+    }
+    match (rule.to_str(), tpl.to_str()) {
+        (Some(pattern), Some(tpl)) => Pattern::new(pattern).is_ok_and(|x| x.matches(tpl)),
+        _ => false,
+    }
+}
+
 impl TemplateConfig {
     pub fn example() -> Self {
         TemplateConfig {
@@ -68,3 +172,43 @@ impl TemplateCustom {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_select() {
+        let rules = vec![
+            EngineRule::new("spec/helm/templates/*.yaml", TplEngine::GoTmpl),
+            EngineRule::new("spec/helm/raw", TplEngine::Handlebars),
+        ];
+        let select = EngineSelect::new(TplEngine::Handlebars, &rules, Path::new("/mod/x86"));
+        assert_eq!(
+            select.engine_of(Path::new("/mod/x86/spec/helm/templates/svc.yaml")),
+            &TplEngine::GoTmpl
+        );
+        assert_eq!(
+            select.engine_of(Path::new("/mod/x86/spec/helm/raw/a.yaml")),
+            &TplEngine::Handlebars
+        );
+        assert_eq!(
+            select.engine_of(Path::new("/mod/x86/spec/app.conf")),
+            &TplEngine::Handlebars
+        );
+        let modes = FileModes::new(
+            &[
+                ModeRule::new("spec/bin/*.sh", "0755"),
+                ModeRule::new("spec/bad", "rwx"),
+            ],
+            Path::new("/mod/x86"),
+        );
+        assert_eq!(
+            modes.mode_of(Path::new("/mod/x86/spec/bin/start.sh")),
+            Some(0o755)
+        );
+        assert_eq!(modes.mode_of(Path::new("/mod/x86/spec/bad")), None);
+        let conf: EngineRule = serde_yaml::from_str("path: spec/*.tpl\nengine: gotmpl").unwrap();
+        assert_eq!(conf.engine(), &TplEngine::GoTmpl);
+    }
+}
@@ -3,7 +3,9 @@ use crate::conf::{ConfFile, ConfSpec};
 use crate::predule::*;
 
 use crate::{
-    const_vars::{CONFS_DIR, MOD_DIR},
+    const_vars::{ARTIFACT_DIGEST_YML, ARTIFACT_DIR, CONFS_DIR, LOCAL_DIR, MOD_DIR, VALUE_DIR},
+    error::ModReason,
+    secret::mark_sensitive,
     workflow::prj::GxlProject,
 };
 
@@ -23,7 +25,7 @@ use super::{
     CpuArch, ModelSTD, OsCPE, RunSPC,
     depend::DependencySet,
     init::{ModIniter, ModPrjIniter, mod_init_gitignore},
-    model::ModModelSpec,
+    model::{ModModelSpec, ModTargetPaths},
     setting::Setting,
 };
 use crate::types::{Localizable, LocalizeOptions, ValuePath};
@@ -77,17 +79,188 @@ impl ModuleSpec {
     }
 }
 
-#[async_trait]
-impl LocalUpdate for ModuleSpec {
-    async fn update_local(&self, path: &Path, options: &UpdateOptions) -> AddrResult<UpdateUnit> {
+// 新增 target 的来源: 复制已有 target, 或按运行空间使用 host/k8s 模板
+#[derive(Clone, Debug)]
+pub enum TargetSource {
+    Clone(ModelSTD),
+    Template,
+}
+
+impl ModuleSpec {
+    pub fn add_target(&mut self, model: ModelSTD, source: TargetSource) -> MainResult<()> {
+        let local = self.local_root()?;
+        if self.targets.contains_key(&model) {
+            return MainReason::from(ModReason::Target(format!("{model} already exists")))
+                .err_result();
+        }
+        let mod_root = local.join(MOD_DIR);
+        let target_path = mod_root.join(model.to_string());
+        match source {
+            TargetSource::Clone(from) => {
+                let Some(src) = self.targets.get(&from) else {
+                    return MainReason::from(ModReason::Miss(format!("target {from}")))
+                        .err_result();
+                };
+                let src_path = mod_root.join(from.to_string());
+                std::fs::create_dir_all(&target_path)
+                    .owe_res()
+                    .with(&target_path)?;
+                let options = fs_extra::dir::CopyOptions::new().content_only(true);
+                fs_extra::dir::copy(&src_path, &target_path, &options)
+                    .owe_res()
+                    .with(&src_path)?;
+                // 本地化产物与制品摘要和平台相关, 不随之复制
+                for dir in [LOCAL_DIR, ARTIFACT_DIR, VALUE_DIR] {
+                    Self::clean_path(&target_path.join(dir))?;
+                }
+                let paths = ModTargetPaths::from(&target_path);
+                let digest_path = paths.spec_path().join(ARTIFACT_DIGEST_YML);
+                if digest_path.exists() {
+                    std::fs::remove_file(&digest_path)
+                        .owe_res()
+                        .with(&digest_path)?;
+                }
+                retarget_artifact(src.artifact(), &from, &model)?
+                    .save_conf(paths.artifact_path())
+                    .owe_res()?;
+                src.vars().save_conf(paths.vars_path()).owe_res()?;
+                mark_sensitive(paths.vars_path(), src.sensitive()).owe_res()?;
+                src.rules().mark_vars(paths.vars_path()).owe_res()?;
+            }
+            TargetSource::Template => {
+                // vars 与已有 target 保持一致
+                let first = self.targets.values().next();
+                let vars = first
+                    .map(|x| x.vars().clone())
+                    .unwrap_or(VarCollection::define(Vec::new()));
+                let sensitive = first.map(|x| x.sensitive().clone()).unwrap_or_default();
+                let rules = first.map(|x| x.rules().clone()).unwrap_or_default();
+                let (workflow, gxl_prj) = match model.spc {
+                    RunSPC::Host => (
+                        ModWorkflows::mod_host_tpl_init(),
+                        GxlProject::spec_host_tpl(),
+                    ),
+                    RunSPC::K8S => (ModWorkflows::mod_k8s_tpl_init(), GxlProject::spec_k8s_tpl()),
+                };
+                ModModelSpec::init(
+                    model.clone(),
+                    ArtifactPackage::from(Vec::new()),
+                    workflow,
+                    gxl_prj,
+                    vars,
+                    None,
+                )
+                .with_sensitive(sensitive)
+                .with_rules(rules)
+                .save_to(&mod_root, None)
+                .owe(ModReason::Save.into())?;
+            }
+        }
+        let spec = ModModelSpec::load_from(&target_path).owe(ModReason::Load.into())?;
+        self.targets.insert(model, spec);
+        Ok(())
+    }
+
+    pub fn remove_target(&mut self, model: &ModelSTD) -> MainResult<()> {
+        let local = self.local_root()?;
+        if !self.targets.contains_key(model) {
+            return MainReason::from(ModReason::Miss(format!("target {model}"))).err_result();
+        }
+        if self.targets.len() == 1 {
+            return MainReason::from(ModReason::Target(format!("{model} is the last target")))
+                .err_result();
+        }
+        Self::clean_path(&local.join(MOD_DIR).join(model.to_string()))?;
+        self.targets.shift_remove(model);
+        Ok(())
+    }
+
+    fn local_root(&self) -> MainResult<PathBuf> {
+        self.local
+            .clone()
+            .ok_or(MainReason::from(ModReason::Miss("local-path".into())).to_err())
+    }
+}
+
+// 制品中与平台相关的字段: 本地文件名与下载地址, 其余字段(名称/版本/缓存地址等)原样保留
+#[derive(Serialize, Deserialize)]
+struct PlatformArtifact {
+    local: String,
+    addr: AddrType,
+    #[serde(flatten)]
+    rest: serde_json::Map<String, serde_json::Value>,
+}
+
+// 复制 target 时仅将制品 local 与 addr 中的平台标识替换为新 target
+fn retarget_artifact(
+    artifact: &ArtifactPackage,
+    from: &ModelSTD,
+    to: &ModelSTD,
+) -> MainResult<ArtifactPackage> {
+    let pairs = [
+        (from.to_string(), to.to_string()),
+        (
+            format!("{}-{}", from.arch, from.os),
+            format!("{}-{}", to.arch, to.os),
+        ),
+    ];
+    let retarget = |text: &str| {
+        pairs
+            .iter()
+            .fold(text.to_string(), |acc, (a, b)| acc.replace(a.as_str(), b))
+    };
+    let mut items = Vec::new();
+    for art in artifact.iter() {
+        let mut platform: PlatformArtifact =
+            serde_json::from_value(serde_json::to_value(art).owe_data()?).owe_data()?;
+        platform.local = retarget(platform.local.as_str());
+        let mut addr = serde_json::to_value(&platform.addr).owe_data()?;
+        retarget_addr(&mut addr, &retarget);
+        platform.addr = serde_json::from_value(addr).owe_data()?;
+        let art: Artifact =
+            serde_json::from_value(serde_json::to_value(&platform).owe_data()?).owe_data()?;
+        items.push(art);
+    }
+    Ok(ArtifactPackage::from(items))
+}
+
+// 地址中仅改写 url/path, 分支/标签/认证等保持不变
+fn retarget_addr(value: &mut serde_json::Value, retarget: &impl Fn(&str) -> String) {
+    if let serde_json::Value::Object(map) = value {
+        for (key, item) in map.iter_mut() {
+            match item {
+                serde_json::Value::String(text) if key == "url" || key == "path" => {
+                    *text = retarget(text.as_str());
+                }
+                _ => retarget_addr(item, retarget),
+            }
+        }
+    }
+}
+
+impl ModuleSpec {
+    // 与 update_local 相同, jobs 为各 target 依赖的并发更新数
+    pub async fn update_jobs(
+        &self,
+        path: &Path,
+        options: &UpdateOptions,
+        jobs: usize,
+    ) -> AddrResult<UpdateUnit> {
         for (target, node) in &self.targets {
-            node.update_local(&path.join(target.to_string()), options)
+            node.update_jobs(&path.join(target.to_string()), options, jobs)
                 .await?;
         }
         Ok(UpdateUnit::from(path.to_path_buf()))
     }
 }
 
+#[async_trait]
+impl LocalUpdate for ModuleSpec {
+    async fn update_local(&self, path: &Path, options: &UpdateOptions) -> AddrResult<UpdateUnit> {
+        self.update_jobs(path, options, 0).await
+    }
+}
+
 impl Persistable<ModuleSpec> for ModuleSpec {
     fn save_to(&self, path: &Path, name: Option<String>) -> SerdeResult<()> {
         let mod_path = path.join(name.unwrap_or(self.name().clone()));
@@ -287,3 +460,67 @@ pub fn make_mod_spec_4test() -> MainResult<ModuleSpec> {
     .with_depends(DependencySet::for_test());
     Ok(ModuleSpec::init("postgresql", vec![k8s, host]))
 }
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_target_add_remove() -> MainResult<()> {
+        let tmp_dir = tempdir().owe_res()?;
+        ModuleSpec::make_new("mod_tgt")?
+            .save_to(tmp_dir.path(), None)
+            .owe_res()?;
+        let mod_path = tmp_dir.path().join("mod_tgt");
+        let mut spec = ModuleSpec::load_from(&mod_path).owe_res()?;
+
+        let ubt24 = ModelSTD::new(CpuArch::X86, OsCPE::UBT24, RunSPC::K8S);
+        spec.add_target(
+            ubt24.clone(),
+            TargetSource::Clone(ModelSTD::x86_ubt22_k8s()),
+        )?;
+        let mac15 = ModelSTD::new(CpuArch::Arm, OsCPE::MAC15, RunSPC::Host);
+        spec.add_target(mac15.clone(), TargetSource::Template)?;
+        assert!(
+            spec.add_target(mac15.clone(), TargetSource::Template)
+                .is_err()
+        );
+
+        let loaded = ModuleSpec::load_from(&mod_path).owe_res()?;
+        let cloned = loaded.targets().get(&ubt24).unwrap();
+        let origin = loaded.targets().get(&ModelSTD::x86_ubt22_k8s()).unwrap();
+        assert_eq!(
+            cloned.vars().value_dict().len(),
+            origin.vars().value_dict().len()
+        );
+        assert!(loaded.targets().contains_key(&mac15));
+
+        spec.remove_target(&mac15)?;
+        let loaded = ModuleSpec::load_from(&mod_path).owe_res()?;
+        assert!(!loaded.targets().contains_key(&mac15));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retarget_artifact_fields() -> MainResult<()> {
+        let from = ModelSTD::x86_ubt22_k8s();
+        let to = ModelSTD::new(CpuArch::X86, OsCPE::UBT24, RunSPC::K8S);
+        let artifact = ArtifactPackage::from(vec![Artifact::new(
+            format!("agent-{from}"),
+            "0.1.0".to_string(),
+            HttpAddr::from(format!("https://repo/{from}/agent-{from}.tar.gz")),
+            format!("agent-{from}.tar.gz"),
+        )]);
+        let retargeted = retarget_artifact(&artifact, &from, &to)?;
+        let art = retargeted.iter().next().unwrap();
+        let (local, addr) = crate::artifact::artifact_source(art)?;
+        assert_eq!(local, format!("agent-{to}.tar.gz"));
+        let addr = serde_json::to_string(&addr).owe_data()?;
+        assert!(addr.contains(format!("https://repo/{to}/agent-{to}.tar.gz").as_str()));
+        let value = serde_json::to_value(art).owe_data()?;
+        assert_eq!(value["name"], serde_json::json!(format!("agent-{from}")));
+        Ok(())
+    }
+}
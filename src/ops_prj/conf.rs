@@ -1,5 +1,6 @@
 use crate::const_vars::OPS_PRJ_CONF_FILE;
 use crate::error::OpsReason;
+use crate::lock::LockFile;
 use crate::predule::*;
 use crate::system::refs::SysModelSpecRef;
 use crate::{error::MainResult, module::depend::DependencySet};
@@ -43,7 +44,7 @@ impl ProjectConf {
 }
 #[async_trait]
 impl SysUpdateable<ProjectConf> for ProjectConf {
-    async fn update_local(mut self, path: &Path, options: &UpdateOptions) -> MainResult<Self> {
+    async fn update_local(self, path: &Path, options: &UpdateOptions) -> MainResult<Self> {
         let mut flag = auto_exit_log!(
             info!(
                 target : "ops-prj/conf",
@@ -54,11 +55,23 @@ impl SysUpdateable<ProjectConf> for ProjectConf {
                 "ins conf update from {} fail!", path.display()
             )
         );
-        self.work_envs
-            .update(options)
-            .await
-            .owe(OpsReason::Update.into())?;
+        self.update_lock(options, None, 0).await?;
         flag.mark_suc();
         Ok(self)
     }
 }
+impl ProjectConf {
+    pub async fn update_lock(
+        &self,
+        options: &UpdateOptions,
+        pinned: Option<&LockFile>,
+        jobs: usize,
+    ) -> MainResult<LockFile> {
+        let units = self
+            .work_envs
+            .update_units(options, jobs)
+            .await
+            .owe(OpsReason::Update.into())?;
+        DependencySet::lock_units(units, pinned)
+    }
+}
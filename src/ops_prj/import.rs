@@ -13,8 +13,11 @@ use orion_variate::{
 
 use crate::{
     error::{MainError, MainResult},
+    ia_value::{ia_value_setting, var_prompts},
     ops_prj::{proj::OpsProject, system::OpsSystem},
     package::types::{PackageType, build_pkg, convert_addr},
+    secret::{SecretKey, sensitive_vars},
+    software::VarRules,
     system::spec::SysModelSpec,
 };
 
@@ -98,47 +101,20 @@ impl OpsProject {
         // 5. 提供系统包的信息， 包组所有组件。
         Ok(sys_spec)
     }
-    pub fn ia_setting(&self) -> MainResult<()> {
-        use inquire::{Confirm, Text};
-
+    // 按变量类型交互设定各系统的 values/<sys>/value.yml
+    pub fn ia_setting(&self, all: bool) -> MainResult<()> {
+        let key = SecretKey::load(self.root_local())?;
         for i in self.ops_target().iter() {
             let vars_path = self.root_local().join(i.sys().name()).join("sys/vars.yml");
-            let value_path = self
-                .root_local()
-                .join("values")
-                .join(i.sys().name())
-                .join("value.yml");
+            let value_path = self.sys_value_path(i).value_file();
             let vars_vec = VarCollection::from_conf(&vars_path).owe_res()?;
-            let mut vals_dict = ValueDict::from_conf(&value_path).owe_res()?;
+            let sensitive = sensitive_vars(&vars_path).owe_res()?;
+            let mut rules = VarRules::from_vars(&vars_path).owe_res()?;
+            rules.merge(&i.load_spec(self.root_local())?.mod_list().var_rules()?);
+            let prompts = var_prompts(&vars_vec, &rules, &sensitive)?;
 
-            // 通过交互模式设定vars的值
             println!("Setting variables for {}", i.sys().name());
-
-            for var in vars_vec.vars() {
-                let prompt = if let Some(desp) = var.desp() {
-                    format!("{}\n{desp}", var.name())
-                } else {
-                    var.name().to_string()
-                };
-                let mut default_value = var.value();
-                let value_str = Text::new(&prompt)
-                    .with_default(&var.value().to_string())
-                    .prompt()
-                    .owe_data()?;
-                default_value.update_by_str(value_str.as_str()).owe_data()?;
-                vals_dict.insert(var.name().to_string(), default_value);
-            }
-
-            // 如果用户确认保存更改
-            if Confirm::new("Do you want to save these changes?")
-                .prompt()
-                .owe_data()?
-            {
-                // 保存修改后的vars到文件
-                // vars.save_to_file(&vars_path)?; // 假设的方法
-                println!("Changes saved to {}", vars_path.display());
-                vals_dict.save_conf(&value_path).owe_res()?;
-            }
+            ia_value_setting(&prompts, &value_path, all, key.as_ref())?;
         }
         Ok(())
     }
@@ -1,5 +1,8 @@
-use crate::const_vars::{VALUE_DIR, VALUE_FILE, WORKINS_PRJ_ROOT};
-use crate::error::OpsReason;
+use crate::const_vars::{
+    OPS_LOCK_FILE, USER_VALUE_STEM, VALUE_DIR, VALUE_ENV_DIR, WORKINS_PRJ_ROOT,
+};
+use crate::error::{MainReason, OpsReason, ToErr};
+use crate::lock::LockFile;
 use crate::ops_prj::system::{OpsSystem, OpsTarget};
 use crate::predule::*;
 
@@ -9,7 +12,8 @@ const OPS_PRJ_ADM: &str = include_str!("init/_gal/adm.gxl");
 const OPS_PRJ_FILE: &str = "ops-prj.yml";
 const PRJ_OPS_TARGET: &str = "ops-systems.yml";
 
-use crate::types::{SysUpdateable, ValuePath};
+use crate::project::{is_value_ext, load_value_file, save_value_file};
+use crate::types::{Localizable, LocalizeOptions, SysUpdateable, ValuePath};
 use async_trait::async_trait;
 use getset::MutGetters;
 use orion_common::serde::{Configable, Persistable};
@@ -65,9 +69,9 @@ impl OpsProject {
         let root_local = root_local.to_path_buf();
         let project = GxlProject::load_from(&root_local).owe(OpsReason::Load.into())?;
         let value_root = ensure_path(root_local.join(VALUE_DIR)).owe_logic()?;
-        let value_file = value_root.join(VALUE_FILE);
+        let value_file = ValuePath::from_root(value_root).value_file();
         let val_dict = if value_file.exists() {
-            ValueDict::from_conf(&value_file).owe_data()?
+            load_value_file(&value_file, None)?
         } else {
             ValueDict::new()
         };
@@ -98,8 +102,8 @@ impl OpsProject {
         self.project.save_to(self.root_local(), None).owe_logic()?;
 
         let value_root = ensure_path(self.root_local().join(VALUE_DIR)).owe_logic()?;
-        let value_file = value_root.join(VALUE_FILE);
-        self.val_dict.save_conf(&value_file).owe_res()?;
+        let value_file = ValuePath::from_root(value_root).value_file();
+        save_value_file(&value_file, &self.val_dict)?;
         workins_init_gitignore(self.root_local())?;
         flag.mark_suc();
         Ok(())
@@ -108,17 +112,63 @@ impl OpsProject {
 
 #[async_trait]
 impl SysUpdateable<OpsProject> for OpsProject {
-    async fn update_local(mut self, path: &Path, options: &UpdateOptions) -> MainResult<Self> {
-        self.conf = self.conf.update_local(path, options).await?;
-        self.save()?;
-        Ok(self)
+    async fn update_local(self, path: &Path, options: &UpdateOptions) -> MainResult<Self> {
+        self.update_local_lock(path, options, false, 0).await
     }
 }
 
 impl OpsProject {
     pub async fn update(self, options: &UpdateOptions) -> MainResult<Self> {
+        self.update_lock(options, false, 0).await
+    }
+    // locked 为 true 时按 ops-lock.yml 固定版本拉取且不改写它, 拉取结果与其不一致则失败
+    pub async fn update_lock(
+        self,
+        options: &UpdateOptions,
+        locked: bool,
+        jobs: usize,
+    ) -> MainResult<Self> {
         let path = self.root_local().clone();
-        self.update_local(&path, options).await
+        self.update_local_lock(&path, options, locked, jobs).await
+    }
+    async fn update_local_lock(
+        self,
+        path: &Path,
+        options: &UpdateOptions,
+        locked: bool,
+        jobs: usize,
+    ) -> MainResult<Self> {
+        let lock_path = path.join(OPS_LOCK_FILE);
+        let pinned = if locked {
+            let Some(pinned) = LockFile::load(&lock_path)? else {
+                return MainReason::from(OpsReason::Locked(format!(
+                    "lock file not found: {}",
+                    lock_path.display()
+                )))
+                .err_result();
+            };
+            Some(pinned)
+        } else {
+            None
+        };
+        let mut lock = self
+            .conf
+            .update_lock(options, pinned.as_ref(), jobs)
+            .await?;
+        for sys in self.ops_target.iter() {
+            let spec = sys.load_spec(path)?;
+            let sys_pinned = pinned.as_ref().map(|x| x.sub(sys.sys().name()));
+            lock.append(
+                sys.sys().name(),
+                spec.update_local_pinned(options, sys_pinned.as_ref(), jobs)
+                    .await?,
+            );
+        }
+        if let Some(report) = lock.apply(&lock_path, locked)? {
+            return MainReason::from(OpsReason::Locked(report)).err_result();
+        }
+        self.save()?;
+        Ok(self)
     }
 }
 
@@ -127,6 +177,68 @@ impl OpsProject {
         let value_root = self.root_local().join(VALUE_DIR);
         ValuePath::from_root(value_root)
     }
+    pub fn sys_value_path(&self, sys: &OpsSystem) -> ValuePath {
+        self.value_path().join(sys.sys().name())
+    }
+    // 系统值层 values/<sys>/value.<yml|json|toml|ini>, 以 sys:<name> 为来源, 优先于项目全局值
+    fn sys_value_layer(&self, sys: &OpsSystem) -> MainResult<Option<(String, ValueDict)>> {
+        let value_file = self.sys_value_path(sys).value_file();
+        if !value_file.exists() {
+            return Ok(None);
+        }
+        let dict = load_value_file(&value_file, None)?;
+        Ok(Some((sys.sys().name().to_string(), dict)))
+    }
+    // 可存放加密项的 value 文件: values/ 下的全部 value.<ext> 与 values/env/*.<ext>
+    pub fn value_files(&self) -> MainResult<Vec<PathBuf>> {
+        let value_root = self.value_path().path().clone();
+        let env_root = value_root.join(VALUE_ENV_DIR);
+        let mut files = Vec::new();
+        if !value_root.exists() {
+            return Ok(files);
+        }
+        for entry in walkdir::WalkDir::new(&value_root).sort_by_file_name() {
+            let entry = entry.owe_sys().with(&value_root)?;
+            let path = entry.path();
+            let is_value = is_value_ext(path)
+                && (path.parent() == Some(env_root.as_path())
+                    || path.file_stem().is_some_and(|x| x == USER_VALUE_STEM));
+            if entry.file_type().is_file() && is_value {
+                files.push(entry.path().to_path_buf());
+            }
+        }
+        Ok(files)
+    }
+}
+
+#[async_trait]
+impl Localizable for OpsProject {
+    async fn localize(
+        &self,
+        dst_path: Option<ValuePath>,
+        options: LocalizeOptions,
+    ) -> MainResult<()> {
+        let mut flag = auto_exit_log!(
+            info!(
+                target : "ops-prj",
+                "localize project {} success!", self.root_local().display()
+            ),
+            error!(
+                target : "ops-prj",
+                "localize project {} fail!", self.root_local().display()
+            )
+        );
+        let value_root = dst_path.unwrap_or(self.value_path());
+        for sys in self.ops_target.iter() {
+            let spec = sys.load_spec(self.root_local())?;
+            let sys_dst = value_root.join(sys.sys().name()).ensure_exist().owe_res()?;
+            let sys_value = self.sys_value_layer(sys)?;
+            spec.localize(Some(sys_dst), options.clone().with_sys_value(sys_value))
+                .await?;
+        }
+        flag.mark_suc();
+        Ok(())
+    }
 }
 impl OpsProject {
     pub fn make_new(prj_path: &Path, name: &str) -> MainResult<Self> {
@@ -151,7 +263,22 @@ pub mod tests {
     use orion_infra::path::make_clean_path;
     use orion_variate::{tools::test_init, update::UpdateOptions};
 
-    use crate::{const_vars::WORKINS_PRJ_ROOT, error::MainResult, ops_prj::proj::OpsProject};
+    use orion_variate::{
+        addr::{AddrType, LocalAddr},
+        vars::{OriginValue, ValueDict, ValueType, VarCollection, VarDefinition},
+    };
+    use tempfile::tempdir;
+
+    use crate::{
+        const_vars::WORKINS_PRJ_ROOT,
+        error::MainResult,
+        module::{ModelSTD, model::TargetValuePaths},
+        ops_prj::{conf::ProjectConf, proj::OpsProject, system::OpsSystem},
+        project::mix_used_value,
+        software::VarRules,
+        system::spec::SysDefine,
+        types::LocalizeOptions,
+    };
 
     #[tokio::test]
     async fn test_workins_example() -> MainResult<()> {
@@ -167,4 +294,37 @@ pub mod tests {
             .assert("spec.update_local");
         Ok(())
     }
+
+    #[test]
+    fn test_sys_value_precedence() {
+        test_init();
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().to_path_buf();
+        let redis = OpsSystem::new(
+            SysDefine::new("redis", ModelSTD::from_cur_sys()),
+            AddrType::from(LocalAddr::from("./redis")),
+        );
+        let project = OpsProject::new(ProjectConf::for_test(), root.clone());
+        let sys_value_root = root.join("values").join("redis");
+        std::fs::create_dir_all(&sys_value_root).unwrap();
+        std::fs::write(sys_value_root.join("value.yml"), "HOST: 10.0.0.2").unwrap();
+
+        let mut global_dict = ValueDict::new();
+        global_dict.insert("HOST", ValueType::from("10.0.0.1"));
+        global_dict.insert("PORT", ValueType::from("6379"));
+        let vars = VarCollection::define(vec![VarDefinition::from(("HOST", "localhost"))]);
+        let options = LocalizeOptions::new(global_dict, false)
+            .with_sys_value(project.sys_value_layer(&redis).unwrap());
+        let value_paths = TargetValuePaths::from(&root.join("mod"));
+
+        let used = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
+        assert_eq!(
+            used.get("HOST"),
+            Some(&OriginValue::from("10.0.0.2").with_origin("sys:redis"))
+        );
+        assert_eq!(
+            used.get("PORT"),
+            Some(&OriginValue::from("6379").with_origin("global"))
+        );
+    }
 }
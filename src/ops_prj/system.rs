@@ -1,9 +1,12 @@
+use std::path::{Path, PathBuf};
+
 use derive_more::{Deref, DerefMut};
 use getset::Getters;
 use orion_variate::addr::AddrType;
 use serde_derive::{Deserialize, Serialize};
 
-use crate::system::spec::SysDefine;
+use crate::error::MainResult;
+use crate::system::spec::{SysDefine, SysModelSpec};
 
 #[derive(Getters, Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[getset(get = "pub")]
@@ -16,6 +19,16 @@ impl OpsSystem {
     pub fn new(sys: SysDefine, addr: AddrType) -> Self {
         Self { sys, addr }
     }
+    // 导入后的系统目录: ${prj_root}/<sys-name>
+    pub fn sys_root(&self, prj_root: &Path) -> PathBuf {
+        prj_root.join(self.sys.name())
+    }
+    pub fn spec_path(&self, prj_root: &Path) -> PathBuf {
+        self.sys_root(prj_root).join("sys")
+    }
+    pub fn load_spec(&self, prj_root: &Path) -> MainResult<SysModelSpec> {
+        SysModelSpec::load_from(&self.spec_path(prj_root))
+    }
 }
 
 #[derive(Getters, Clone, Debug, Serialize, Deserialize, Default, Deref, DerefMut)]
@@ -0,0 +1,319 @@
+use std::fmt::Display;
+
+use orion_common::serde::Configable;
+use orion_variate::vars::{ValueDict, ValueType, VarCollection};
+
+use crate::{
+    const_vars::VARS_YML,
+    error::{MainReason, OpsReason, ToErr},
+    ops_prj::{proj::OpsProject, system::OpsSystem},
+    predule::*,
+    project::{load_value_file, save_value_file},
+    secret::{SECRET_MASK, SecretKey, is_encrypted, sensitive_vars},
+    software::{ConstraintRule, VarRules},
+};
+
+fn value_err<T>(msg: String) -> MainResult<T> {
+    MainReason::from(OpsReason::Value(msg)).err_result()
+}
+
+// sys/vars.yml 中声明的变量
+#[derive(Clone, Debug)]
+struct DeclaredVar {
+    name: String,
+    default: serde_json::Value,
+    sensitive: bool,
+    rule: Option<ConstraintRule>,
+}
+
+impl DeclaredVar {
+    // 有 type 规则时按规则检查, 否则要求与默认值类型一致
+    fn check(&self, value: &serde_json::Value) -> Option<String> {
+        if let Some(reason) = self.rule.as_ref().and_then(|x| x.check(Some(value))) {
+            return Some(reason);
+        }
+        if self.rule.as_ref().is_some_and(|x| x.kind().is_some()) {
+            return None;
+        }
+        let expect = match &self.default {
+            serde_json::Value::Bool(_) if !value.is_boolean() => "bool",
+            serde_json::Value::Number(n) if n.is_f64() && !value.is_number() => "float",
+            serde_json::Value::Number(n) if !n.is_f64() && !(value.is_i64() || value.is_u64()) => {
+                "int"
+            }
+            serde_json::Value::String(_) if !value.is_string() => "string, use --string",
+            _ => return None,
+        };
+        Some(format!("expect type {expect}, got {value}"))
+    }
+    fn is_secret(&self) -> bool {
+        self.sensitive || self.default.as_str().is_some_and(is_encrypted)
+    }
+}
+
+// 变量的声明与设置状态, 供 gops value list 输出
+#[derive(Clone, Debug, Getters)]
+pub struct ValueStatus {
+    name: String,
+    declared: bool,
+    default: Option<serde_json::Value>,
+    value: Option<serde_json::Value>,
+    sensitive: bool,
+}
+
+impl ValueStatus {
+    pub fn status(&self) -> &'static str {
+        match (self.declared, self.value.is_some()) {
+            (true, true) => "set",
+            (true, false) => "default",
+            (false, _) => "undeclared",
+        }
+    }
+}
+
+impl Display for ValueStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let shown = self.value.as_ref().or(self.default.as_ref());
+        let text = match shown {
+            Some(serde_json::Value::String(s)) if self.sensitive || is_encrypted(s) => {
+                SECRET_MASK.to_string()
+            }
+            Some(_) if self.sensitive => SECRET_MASK.to_string(),
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        write!(f, "{:<32} {:<10} {}", self.name, self.status(), text)
+    }
+}
+
+impl OpsProject {
+    fn find_sys(&self, name: &str) -> MainResult<&OpsSystem> {
+        self.ops_target()
+            .iter()
+            .find(|x| x.sys().name() == name)
+            .ok_or_else(|| MainReason::from(OpsReason::Miss(format!("system {name}"))).to_err())
+    }
+
+    // sys 指定时为 values/<sys>/value.yml, 否则为项目全局 values/value.yml
+    pub fn target_value_file(&self, sys: Option<&str>) -> MainResult<PathBuf> {
+        match sys {
+            Some(name) => Ok(self.sys_value_path(self.find_sys(name)?).value_file()),
+            None => Ok(self.value_path().value_file()),
+        }
+    }
+
+    // 指定系统时取其 sys/vars.yml, 否则为全部系统声明的并集
+    fn declared_vars(&self, sys: Option<&str>) -> MainResult<Vec<DeclaredVar>> {
+        let systems = match sys {
+            Some(name) => vec![self.find_sys(name)?],
+            None => self.ops_target().iter().collect(),
+        };
+        let mut declared: Vec<DeclaredVar> = Vec::new();
+        for system in systems {
+            let vars_path = system.spec_path(self.root_local()).join(VARS_YML);
+            if !vars_path.exists() {
+                continue;
+            }
+            let vars = VarCollection::from_conf(&vars_path)
+                .owe_res()
+                .with(&vars_path)?;
+            let sensitive = sensitive_vars(&vars_path).owe_res()?;
+            let rules = VarRules::from_vars(&vars_path).owe_res()?;
+            for var in vars.vars() {
+                if declared.iter().any(|x| x.name == var.name().as_str()) {
+                    continue;
+                }
+                declared.push(DeclaredVar {
+                    name: var.name().to_string(),
+                    default: serde_json::to_value(var.value()).owe_data()?,
+                    sensitive: sensitive.iter().any(|x| x == var.name()),
+                    rule: rules.find(var.name()).cloned(),
+                });
+            }
+        }
+        Ok(declared)
+    }
+
+    fn load_target_values(&self, sys: Option<&str>) -> MainResult<(PathBuf, ValueDict)> {
+        let value_file = self.target_value_file(sys)?;
+        let dict = if value_file.exists() {
+            load_value_file(&value_file, None)?
+        } else {
+            ValueDict::new()
+        };
+        Ok((value_file, dict))
+    }
+
+    pub fn value_get(&self, sys: Option<&str>, key: &str) -> MainResult<Option<ValueType>> {
+        let (_, dict) = self.load_target_values(sys)?;
+        Ok(dict.get(key).cloned())
+    }
+
+    // 只允许设置 sys/vars.yml 中声明的变量, sensitive 变量加密后写入
+    pub fn value_set(&self, sys: Option<&str>, key: &str, value: ValueType) -> MainResult<()> {
+        let declared = self.declared_vars(sys)?;
+        let Some(var) = declared.iter().find(|x| x.name == key) else {
+            return value_err(format!(
+                "{key} is not declared in sys/vars.yml of {}",
+                sys.unwrap_or("any system")
+            ));
+        };
+        let json = serde_json::to_value(&value).owe_data()?;
+        if let Some(reason) = var.check(&json) {
+            return value_err(format!("{key}: {reason}"));
+        }
+        let value = match &json {
+            serde_json::Value::String(text) if is_encrypted(text) => value,
+            _ if var.is_secret() => {
+                let Some(secret) = SecretKey::load(self.root_local())? else {
+                    return value_err(format!(
+                        "{key} is sensitive but no secret key found, use `gops secret set {key}` instead"
+                    ));
+                };
+                let plain = match &json {
+                    serde_json::Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                ValueType::from(secret.encrypt(plain.as_str())?.as_str())
+            }
+            _ => value,
+        };
+        let (value_file, mut dict) = self.load_target_values(sys)?;
+        dict.insert(key.to_string(), value);
+        if let Some(parent) = value_file.parent() {
+            std::fs::create_dir_all(parent).owe_sys().with(parent)?;
+        }
+        save_value_file(&value_file, &dict)
+    }
+
+    // 返回是否删除了取值
+    pub fn value_unset(&self, sys: Option<&str>, key: &str) -> MainResult<bool> {
+        let (value_file, dict) = self.load_target_values(sys)?;
+        if dict.get(key).is_none() {
+            return Ok(false);
+        }
+        let mut kept = ValueDict::new();
+        for (name, value) in dict.iter() {
+            if name != key {
+                kept.insert(name.clone(), value.clone());
+            }
+        }
+        save_value_file(&value_file, &kept)?;
+        Ok(true)
+    }
+
+    // 先按声明顺序列出变量, 再列出 value 文件中未声明的项
+    pub fn value_list(&self, sys: Option<&str>) -> MainResult<Vec<ValueStatus>> {
+        let declared = self.declared_vars(sys)?;
+        let (_, dict) = self.load_target_values(sys)?;
+        let mut list = Vec::new();
+        for var in declared.iter() {
+            list.push(ValueStatus {
+                name: var.name.clone(),
+                declared: true,
+                default: Some(var.default.clone()),
+                value: dict
+                    .get(&var.name)
+                    .map(serde_json::to_value)
+                    .transpose()
+                    .owe_data()?,
+                sensitive: var.sensitive,
+            });
+        }
+        for (name, value) in dict.iter() {
+            if declared.iter().any(|x| &x.name == name) {
+                continue;
+            }
+            list.push(ValueStatus {
+                name: name.clone(),
+                declared: false,
+                default: None,
+                value: Some(serde_json::to_value(value).owe_data()?),
+                sensitive: false,
+            });
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use orion_variate::addr::{AddrType, LocalAddr};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{
+        const_vars::SECRET_KEY_FILE, module::ModelSTD, ops_prj::conf::ProjectConf,
+        system::spec::SysDefine,
+    };
+
+    #[test]
+    fn test_value_commands() {
+        let tmp_dir = tempdir().unwrap();
+        let root = tmp_dir.path().to_path_buf();
+        let mut project = OpsProject::new(ProjectConf::for_test(), root.clone());
+        project.import_ops_sys(OpsSystem::new(
+            SysDefine::new("redis", ModelSTD::from_cur_sys()),
+            AddrType::from(LocalAddr::from("./redis")),
+        ));
+        let sys_spec = root.join("redis").join("sys");
+        std::fs::create_dir_all(&sys_spec).unwrap();
+        std::fs::write(
+            sys_spec.join(VARS_YML),
+            "vars:\n- name: REDIS_PORT\n  value: 6379\n- name: REDIS_PASS\n  value: changeme\n  sensitive: true\n",
+        )
+        .unwrap();
+
+        let sys = Some("redis");
+        assert!(
+            project
+                .value_set(Some("mysql"), "X", ValueType::from("1"))
+                .is_err()
+        );
+        assert!(
+            project
+                .value_set(sys, "UNKNOWN", ValueType::from("1"))
+                .is_err()
+        );
+        // 类型与默认值不一致
+        assert!(
+            project
+                .value_set(sys, "REDIS_PORT", ValueType::from("abc"))
+                .is_err()
+        );
+        project
+            .value_set(sys, "REDIS_PORT", ValueType::from(6380))
+            .unwrap();
+
+        // sensitive 变量无密钥时拒绝写入, 有密钥时加密写入
+        assert!(
+            project
+                .value_set(sys, "REDIS_PASS", ValueType::from("s3cret"))
+                .is_err()
+        );
+        let key = SecretKey::generate(&root.join(SECRET_KEY_FILE)).unwrap();
+        project
+            .value_set(sys, "REDIS_PASS", ValueType::from("s3cret"))
+            .unwrap();
+        let stored = project.value_get(sys, "REDIS_PASS").unwrap().unwrap();
+        let stored = serde_json::to_value(&stored).unwrap();
+        let stored = stored.as_str().unwrap();
+        assert!(is_encrypted(stored));
+        assert_eq!(key.decrypt(stored).unwrap(), "s3cret");
+        assert!(root.join("values/redis/value.yml").exists());
+        assert_eq!(project.value_get(None, "REDIS_PASS").unwrap(), None);
+
+        let list = project.value_list(sys).unwrap();
+        let status: Vec<(&str, &str)> = list
+            .iter()
+            .map(|x| (x.name().as_str(), x.status()))
+            .collect();
+        assert_eq!(status, vec![("REDIS_PORT", "set"), ("REDIS_PASS", "set")]);
+        assert!(!list[1].to_string().contains("s3cret"));
+
+        assert!(project.value_unset(sys, "REDIS_PASS").unwrap());
+        assert!(!project.value_unset(sys, "REDIS_PASS").unwrap());
+        assert_eq!(project.value_get(sys, "REDIS_PASS").unwrap(), None);
+    }
+}
@@ -1,53 +1,625 @@
-use std::path::{Path, PathBuf};
+use std::{
+    fmt::Display,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
+use derive_getters::Getters;
 use log::info;
-use orion_common::serde::{Configable, ValueConfable, Yamlable};
-use orion_error::ErrorOwe;
+use orion_common::serde::{ValueConfable, Yamlable};
+use orion_error::{ErrorOwe, ErrorWith};
 use orion_infra::path::ensure_path;
-use orion_variate::vars::{EnvDict, EnvEvalable, OriginDict, ValueDict, ValueType, VarCollection};
+use orion_variate::vars::{EnvEvalable, OriginDict, ValueDict, ValueType, VarCollection};
+use regex::Regex;
 
 use crate::{
-    const_vars::{VALUE_DIR, VALUE_FILE},
-    error::MainResult,
+    const_vars::{VALUE_DIR, VALUE_ENV_DIR},
+    error::{ElementReason, LocalizeReason, MainReason, MainResult, ToErr},
     module::model::TargetValuePaths,
-    types::LocalizeOptions,
+    secret::{SECRET_MASK, SecretKey, ValueMask, decrypt_dict},
+    software::VarRules,
+    types::{LocalizeOptions, ValuePath},
 };
 
-pub fn load_project_global_value(root: &Path, options: &Option<String>) -> MainResult<ValueDict> {
-    let value_root = ensure_path(root.join(VALUE_DIR)).owe_logic()?;
-    let value_file = if let Some(v_file) = options {
-        PathBuf::from(v_file)
-    } else {
-        let v_file = value_root.join(VALUE_FILE);
-        if !v_file.exists() {
-            let mut dict = ValueDict::new();
-            dict.insert("SAMPLE_KEY", ValueType::from("SAMPLE_VAL"));
-            dict.save_valconf(&v_file).owe_res()?;
+// value 文件格式, 未指定时按扩展名识别, 未知扩展名按 yaml 读取
+#[derive(Clone, Copy, Debug, Default, PartialEq, derive_more::Display)]
+pub enum ValueFormat {
+    #[default]
+    #[display("yaml")]
+    Yaml,
+    #[display("json")]
+    Json,
+    #[display("toml")]
+    Toml,
+    #[display("ini")]
+    Ini,
+}
+
+impl FromStr for ValueFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            "ini" => Ok(Self::Ini),
+            _ => Err(format!("{s}, want one of yaml/json/toml/ini")),
         }
-        v_file
+    }
+}
+
+impl ValueFormat {
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|x| x.to_str())
+            .and_then(|x| Self::from_str(x.to_lowercase().as_str()).ok())
+            .unwrap_or_default()
+    }
+}
+
+const VALUE_EXTS: [&str; 5] = ["yml", "yaml", "json", "toml", "ini"];
+
+pub fn is_value_ext(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| VALUE_EXTS.contains(&x))
+}
+
+// dir 下名为 <stem>.<yml|yaml|json|toml|ini> 的首个文件
+pub fn find_value_file(dir: &Path, stem: &str) -> Option<PathBuf> {
+    VALUE_EXTS
+        .iter()
+        .map(|ext| dir.join(format!("{stem}.{ext}")))
+        .find(|x| x.exists())
+}
+
+pub fn load_value_file(path: &Path, format: Option<ValueFormat>) -> MainResult<ValueDict> {
+    let format = format.unwrap_or_else(|| ValueFormat::from_path(path));
+    if format == ValueFormat::Yaml {
+        return ValueDict::from_yml(path).owe_logic().with(path);
+    }
+    let content = read_to_string(path).owe_res().with(path)?;
+    match format {
+        ValueFormat::Json => serde_json::from_str(content.as_str()).owe_conf().with(path),
+        ValueFormat::Toml => toml::from_str(content.as_str()).owe_conf().with(path),
+        _ => serde_ini::from_str(content.as_str()).owe_conf().with(path),
+    }
+}
+
+// 按扩展名写回 value 文件, 与 load_value_file 对应
+pub fn save_value_file(path: &Path, dict: &ValueDict) -> MainResult<()> {
+    let content = match ValueFormat::from_path(path) {
+        ValueFormat::Yaml => return dict.save_valconf(path).owe_res().with(path),
+        ValueFormat::Json => serde_json::to_string_pretty(dict).owe_data()?,
+        ValueFormat::Toml => toml::to_string(dict).owe_data()?,
+        ValueFormat::Ini => serde_ini::to_string(dict).owe_data()?,
     };
-    let dict = ValueDict::from_yml(&value_file).owe_logic()?;
-    Ok(dict)
+    std::fs::write(path, content).owe_res().with(path)
+}
+
+// 项目全局值 values/value.<yml|json|toml|ini>, 都不存在时生成 value.yml 样例
+pub fn load_project_global_value(root: &Path) -> MainResult<ValueDict> {
+    let value_root = ensure_path(root.join(VALUE_DIR)).owe_logic()?;
+    let value_file = ValuePath::from_root(value_root).value_file();
+    if !value_file.exists() {
+        let mut dict = ValueDict::new();
+        dict.insert("SAMPLE_KEY", ValueType::from("SAMPLE_VAL"));
+        dict.save_valconf(&value_file).owe_res()?;
+    }
+    load_value_file(&value_file, None)
+}
+
+// --env <name> 对应 values/env/<name>.yml, 也可为 json/toml/ini
+pub fn load_project_env_value(
+    root: &Path,
+    env: &Option<String>,
+) -> MainResult<Option<(String, ValueDict)>> {
+    let Some(name) = env else {
+        return Ok(None);
+    };
+    let env_dir = root.join(VALUE_DIR).join(VALUE_ENV_DIR);
+    let Some(env_file) = find_value_file(&env_dir, name) else {
+        return MainReason::from(ElementReason::Miss(format!(
+            "env value: {}",
+            env_dir.join(format!("{name}.yml")).display()
+        )))
+        .err_result();
+    };
+    let dict = load_value_file(&env_file, None)?;
+    info!(target:"mod/target", "use env value : {}", env_file.display());
+    Ok(Some((name.clone(), dict)))
+}
+
+// 命令行覆盖层, 按优先级升序: --value 文件依次在前, 其后为 --set / --set-string / --set-file
+pub fn load_value_overrides(
+    values: &[String],
+    format: Option<ValueFormat>,
+    sets: &[String],
+    set_strings: &[String],
+    set_files: &[String],
+) -> MainResult<Vec<(String, ValueDict)>> {
+    let mut layers = Vec::new();
+    for file in values {
+        let path = PathBuf::from(file);
+        let dict = load_value_file(&path, format)?;
+        layers.push((format!("value:{file}"), dict));
+    }
+    for (origin, items) in [
+        ("set", sets),
+        ("set-string", set_strings),
+        ("set-file", set_files),
+    ] {
+        if items.is_empty() {
+            continue;
+        }
+        let mut root = serde_json::Map::new();
+        for item in items {
+            let Some((key, text)) = item.split_once('=') else {
+                return override_err(format!("--{origin} {item}, want KEY=VAL"));
+            };
+            let value = match origin {
+                "set" => parse_set_value(text),
+                "set-string" => serde_json::Value::String(text.to_string()),
+                _ => {
+                    let path = PathBuf::from(text);
+                    serde_json::Value::String(read_to_string(&path).owe_res().with(&path)?)
+                }
+            };
+            insert_dotted(&mut root, key, value).map_err(|e| {
+                MainReason::from(LocalizeReason::Override(format!("--{origin} {item}, {e}")))
+                    .to_err()
+            })?;
+        }
+        let dict: ValueDict = serde_json::from_value(serde_json::Value::Object(root)).owe_data()?;
+        layers.push((origin.to_string(), dict));
+    }
+    Ok(layers)
+}
+
+// localize 与 explain-value 共用的 value 参数, 由各命令 flatten 引入
+#[derive(Clone, Debug, Default, clap::Args, Getters)]
+pub struct ValueArgs {
+    /// Paths to values files for localization, later files override earlier ones
+    #[arg(
+        long = "value",
+        help = "Path to YAML/JSON file containing environment-specific values, repeatable"
+    )]
+    pub value: Vec<String>,
+
+    /// Format of --value files, detected by extension when omitted
+    #[arg(
+        long = "value-format",
+        help = "Value file format: yaml, json, toml or ini"
+    )]
+    pub value_format: Option<ValueFormat>,
+
+    /// Override a value on the command line, dotted keys set nested maps
+    #[arg(
+        long = "set",
+        help = "Override value: eg --set db.port=3307, numbers and booleans are typed"
+    )]
+    pub set: Vec<String>,
+
+    /// Override a value on the command line, always as a string
+    #[arg(
+        long = "set-string",
+        help = "Override value as string: eg --set-string VERSION=1.10"
+    )]
+    pub set_string: Vec<String>,
+
+    /// Override a value with the content of a file
+    #[arg(
+        long = "set-file",
+        help = "Override value from file: eg --set-file CERT=ca.pem"
+    )]
+    pub set_file: Vec<String>,
+
+    /// Environment overlay, loaded from values/env/<name>.yml
+    #[arg(
+        long = "env",
+        help = "Environment overlay name, eg --env prod for values/env/prod.yml"
+    )]
+    pub env: Option<String>,
+
+    /// Use default values instead of user-provided value.yml
+    #[arg(long = "default", default_value = "false" , action = clap::ArgAction::SetTrue, help = "Use built-in default values instead of user-provided value.yml")]
+    pub use_default_value: bool,
+}
+
+impl ValueArgs {
+    // global 固定为 values/value.yml, --value 文件各自作为覆盖层
+    pub fn localize_options(&self, root: &Path) -> MainResult<LocalizeOptions> {
+        let dict = load_project_global_value(root)?;
+        Ok(LocalizeOptions::new(dict, self.use_default_value)
+            .with_env_value(load_project_env_value(root, &self.env)?)
+            .with_overrides(load_value_overrides(
+                &self.value,
+                self.value_format,
+                &self.set,
+                &self.set_string,
+                &self.set_file,
+            )?)
+            .with_secret(SecretKey::load(root)?))
+    }
+}
+
+fn override_err<T>(msg: String) -> MainResult<T> {
+    MainReason::from(LocalizeReason::Override(msg)).err_result()
+}
+
+// --set 的值按标量推断类型: 数字/布尔, 其余均为字符串
+pub fn parse_set_value(text: &str) -> serde_json::Value {
+    match serde_yaml::from_str::<serde_json::Value>(text) {
+        Ok(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => value,
+        _ => serde_json::Value::String(text.to_string()),
+    }
+}
+
+// a.b.c=x 写入嵌套对象
+fn insert_dotted(
+    root: &mut serde_json::Map<String, serde_json::Value>,
+    key: &str,
+    value: serde_json::Value,
+) -> Result<(), String> {
+    let parts: Vec<&str> = key.split('.').collect();
+    if parts.iter().any(|x| x.is_empty()) {
+        return Err(format!("bad key: {key}"));
+    }
+    let (last, parents) = parts.split_last().expect("split is never empty");
+    let mut cur = root;
+    for part in parents {
+        let node = cur
+            .entry(part.to_string())
+            .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+        let Some(next) = node.as_object_mut() else {
+            return Err(format!("{part} is not a map"));
+        };
+        cur = next;
+    }
+    cur.insert(last.to_string(), value);
+    Ok(())
+}
+
+// 各层取值, 按优先级降序: 命令行覆盖 > env:<name> > sys:<name> > global > mod-cust > mod-default
+fn value_layers(
+    options: &LocalizeOptions,
+    value_paths: &TargetValuePaths,
+    vars: &VarCollection,
+) -> MainResult<Vec<(String, ValueDict)>> {
+    let mut layers = vec![("mod-default".to_string(), vars.value_dict())];
+    if value_paths.user_value_file().exists() && !options.use_default_value() {
+        let user_dict = load_value_file(value_paths.user_value_file(), None)?;
+        info!(target:"mod/target", "use  model value : {}", value_paths.user_value_file().display());
+        layers.push(("mod-cust".to_string(), user_dict));
+    }
+    layers.push(("global".to_string(), options.raw_value().clone()));
+    if let Some((name, sys_dict)) = options.sys_value() {
+        layers.push((format!("sys:{name}"), sys_dict.clone()));
+    }
+    if let Some((name, env_dict)) = options.env_value() {
+        layers.push((format!("env:{name}"), env_dict.clone()));
+    }
+    if !options.overrides().is_empty() {
+        let mut below = serde_json::Map::new();
+        for (_, dict) in layers.iter() {
+            if let serde_json::Value::Object(map) = serde_json::to_value(dict).owe_data()? {
+                below.extend(map);
+            }
+        }
+        for (origin, dict) in options.overrides() {
+            layers.push((origin.clone(), deep_fill(dict, &mut below)?));
+        }
+    }
+    layers.reverse();
+    Ok(layers)
+}
+
+// 覆盖层中的 map 与下层同名 map 合并, --set db.port=3307 不丢失 db 下的其他项
+fn deep_fill(
+    dict: &ValueDict,
+    below: &mut serde_json::Map<String, serde_json::Value>,
+) -> MainResult<ValueDict> {
+    let mut filled = serde_json::Map::new();
+    for (key, value) in dict.iter() {
+        let value = serde_json::to_value(value).owe_data()?;
+        let value = match below.get(key) {
+            Some(lower) => merge_json(lower, value),
+            None => value,
+        };
+        below.insert(key.clone(), value.clone());
+        filled.insert(key.clone(), value);
+    }
+    serde_json::from_value(serde_json::Value::Object(filled)).owe_data()
+}
+
+fn merge_json(lower: &serde_json::Value, over: serde_json::Value) -> serde_json::Value {
+    match (lower, over) {
+        (serde_json::Value::Object(lower), serde_json::Value::Object(over)) => {
+            let mut merged = lower.clone();
+            for (key, value) in over {
+                let value = match merged.get(&key) {
+                    Some(sub) => merge_json(sub, value),
+                    None => value,
+                };
+                merged.insert(key, value);
+            }
+            serde_json::Value::Object(merged)
+        }
+        (_, over) => over,
+    }
 }
 
 pub fn mix_used_value(
     options: LocalizeOptions,
     value_paths: &TargetValuePaths,
     vars: &VarCollection,
+    rules: &VarRules,
 ) -> MainResult<OriginDict> {
-    let mut used = OriginDict::from(options.raw_value().clone().env_eval(&EnvDict::default()));
-    used.set_source("global");
-    if value_paths.user_value_file().exists() && !options.use_default_value() {
-        let user_dict = ValueDict::from_conf(value_paths.user_value_file()).owe_res()?;
-        let mut user_dict = OriginDict::from(user_dict.env_eval(&used.export_dict()));
-        user_dict.set_source("mod-cust");
-        used.merge(&user_dict);
-        info!(target:"mod/target", "use  model value : {}", value_paths.user_value_file().display());
+    mix_used_value_masked(options, value_paths, vars, rules, &[]).map(|(used, _)| used)
+}
+
+// 同 mix_used_value, 另外返回解密得到的明文, 供可读输出掩码
+pub fn mix_used_value_masked(
+    options: LocalizeOptions,
+    value_paths: &TargetValuePaths,
+    vars: &VarCollection,
+    rules: &VarRules,
+    sensitive: &[String],
+) -> MainResult<(OriginDict, ValueMask)> {
+    let mods_value = options.mods_value();
+    let secret = options.secret();
+    let mut mask = ValueMask::default();
+    let mut used = OriginDict::from(ValueDict::new());
+    // 高优先级的层先并入, merge 不覆盖已有项
+    for (origin, dict) in value_layers(&options, value_paths, vars)? {
+        let dict = resolve_mods_ref(dict, mods_value)?;
+        let dict = decrypt_dict(dict, secret, &mut mask)?;
+        let mut dict = OriginDict::from(dict.env_eval(&used.export_dict()));
+        dict.set_source(origin.as_str());
+        used.merge(&dict);
+    }
+    mask.add_sensitive(sensitive, &used.export_dict());
+    rules.validate(&used, &mask)?;
+    Ok((used, mask))
+}
+
+// 替换 ${mods.<mod>.<KEY>} 为依赖模块导出的值, 整个值只是一个引用时保留导出值的类型
+pub fn resolve_mods_ref(dict: ValueDict, mods_value: &ValueDict) -> MainResult<ValueDict> {
+    let re = Regex::new(r"\$\{(mods\.[A-Za-z0-9_-]+\.[A-Za-z0-9_]+)\}").expect("bad mods regex");
+    let mut resolved = ValueDict::new();
+    for (key, value) in dict.iter() {
+        let json = serde_json::to_value(value).owe_data()?;
+        match resolve_json_ref(key, &json, &re, mods_value)? {
+            Some(json) => {
+                let value: ValueType = serde_json::from_value(json).owe_data()?;
+                resolved.insert(key.clone(), value);
+            }
+            None => {
+                resolved.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+// 递归处理 map/数组中的字符串, 没有引用时返回 None
+fn resolve_json_ref(
+    key: &str,
+    value: &serde_json::Value,
+    re: &Regex,
+    mods_value: &ValueDict,
+) -> MainResult<Option<serde_json::Value>> {
+    match value {
+        serde_json::Value::String(text) => resolve_text_ref(key, text, re, mods_value),
+        serde_json::Value::Array(items) => {
+            let mut changed = false;
+            let mut list = Vec::new();
+            for item in items {
+                match resolve_json_ref(key, item, re, mods_value)? {
+                    Some(item) => {
+                        changed = true;
+                        list.push(item);
+                    }
+                    None => list.push(item.clone()),
+                }
+            }
+            Ok(changed.then_some(serde_json::Value::Array(list)))
+        }
+        serde_json::Value::Object(map) => {
+            let mut changed = false;
+            let mut obj = serde_json::Map::new();
+            for (name, item) in map {
+                match resolve_json_ref(key, item, re, mods_value)? {
+                    Some(item) => {
+                        changed = true;
+                        obj.insert(name.clone(), item);
+                    }
+                    None => {
+                        obj.insert(name.clone(), item.clone());
+                    }
+                }
+            }
+            Ok(changed.then_some(serde_json::Value::Object(obj)))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn resolve_text_ref(
+    key: &str,
+    text: &str,
+    re: &Regex,
+    mods_value: &ValueDict,
+) -> MainResult<Option<serde_json::Value>> {
+    let Some(found) = re.captures(text) else {
+        return Ok(None);
+    };
+    let miss_err = |name: &str| {
+        MainReason::from(LocalizeReason::MissExport(format!(
+            "{key} references ${{{name}}}, module not exported or not required"
+        )))
+        .err_result()
+    };
+    if found[0].len() == text.len() {
+        return match mods_value.get(&found[1]) {
+            Some(v) => Ok(Some(serde_json::to_value(v).owe_data()?)),
+            None => miss_err(&found[1]),
+        };
+    }
+    let mut miss = None;
+    let replaced = re.replace_all(text, |cap: &regex::Captures| {
+        match mods_value.get(&cap[1]) {
+            Some(v) => value_to_text(v),
+            None => {
+                miss.get_or_insert(cap[1].to_string());
+                cap[0].to_string()
+            }
+        }
+    });
+    if let Some(name) = miss {
+        return miss_err(&name);
+    }
+    Ok(Some(serde_json::Value::String(replaced.to_string())))
+}
+
+fn value_to_text(value: &ValueType) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => s,
+        Ok(other) => other.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+// 某一层(global/mod-cust/mod-default)中定义的原始值
+#[derive(Clone, Debug, Getters)]
+pub struct ValueLayer {
+    source: String,
+    value: serde_json::Value,
+}
+
+// 变量值来源说明: 最终值, 生效层, 被覆盖层, 以及 ${VAR} 展开记录
+#[derive(Clone, Debug, Getters)]
+pub struct ValueExplain {
+    key: String,
+    value: serde_json::Value,
+    winner: String,
+    raw: Option<serde_json::Value>,
+    shadowed: Vec<ValueLayer>,
+    expanded: Vec<(String, String)>,
+}
+
+impl Display for ValueExplain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} = {}  [{}]", self.key, self.value, self.winner)?;
+        if let Some(raw) = &self.raw {
+            if raw != &self.value {
+                writeln!(f, "    raw     : {raw}")?;
+            }
+        }
+        for (name, value) in &self.expanded {
+            writeln!(f, "    expand  : ${{{name}}} -> {value}")?;
+        }
+        for layer in &self.shadowed {
+            writeln!(f, "    shadowed: [{}] {}", layer.source, layer.value)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn explain_used_value(
+    options: LocalizeOptions,
+    value_paths: &TargetValuePaths,
+    vars: &VarCollection,
+    sensitive: &[String],
+) -> MainResult<Vec<ValueExplain>> {
+    let layers = value_layers(&options, value_paths, vars)?;
+    let (used, mask) =
+        mix_used_value_masked(options, value_paths, vars, &VarRules::default(), sensitive)?;
+    let used_dict = used.export_dict();
+
+    let mut keys: Vec<String> = Vec::new();
+    for (_, dict) in layers.iter() {
+        for key in dict.keys() {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+    }
+    let mut explains = Vec::new();
+    for key in keys {
+        let Some(final_value) = used.get(&key) else {
+            continue;
+        };
+        let winner = final_value
+            .origin()
+            .clone()
+            .unwrap_or_else(|| "unset".to_string());
+        let hide = mask.is_sensitive(key.as_str());
+        let mut raw = None;
+        let mut shadowed = Vec::new();
+        for (source, dict) in layers.iter() {
+            if let Some(value) = dict.get(&key) {
+                let value = if hide {
+                    serde_json::Value::String(SECRET_MASK.to_string())
+                } else {
+                    serde_json::to_value(value).owe_data()?
+                };
+                if source == &winner {
+                    raw = Some(value);
+                } else {
+                    shadowed.push(ValueLayer {
+                        source: source.to_string(),
+                        value,
+                    });
+                }
+            }
+        }
+        let expanded = raw
+            .as_ref()
+            .filter(|_| !hide)
+            .and_then(|x| x.as_str())
+            .map(|x| expand_refs(x, &used_dict))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| (name, mask.mask_text(value.as_str())))
+            .collect();
+        explains.push(ValueExplain {
+            key,
+            value: if hide {
+                serde_json::Value::String(SECRET_MASK.to_string())
+            } else {
+                mask.mask_json(serde_json::to_value(final_value.value()).owe_data()?)
+            },
+            winner,
+            raw,
+            shadowed,
+            expanded,
+        });
     }
-    let mut default_dict = OriginDict::from(vars.value_dict().env_eval(&used.export_dict()));
-    default_dict.set_source("mod-default");
-    used.merge(&default_dict);
-    Ok(used)
+    Ok(explains)
+}
+
+// 找出原始值中引用的 ${VAR}, 以及展开后的值
+fn expand_refs(raw: &str, used: &ValueDict) -> Vec<(String, String)> {
+    let re = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_.]*)\}").expect("bad ref regex");
+    let mut refs: Vec<(String, String)> = Vec::new();
+    for cap in re.captures_iter(raw) {
+        let name = cap[1].to_string();
+        if refs.iter().any(|(x, _)| x == &name) {
+            continue;
+        }
+        let value = if let Some(v) = used.get(&name) {
+            value_to_text(v)
+        } else if let Ok(v) = std::env::var(&name) {
+            v
+        } else {
+            "<unresolved>".to_string()
+        };
+        refs.push((name, value));
+    }
+    refs
 }
 
 #[cfg(test)]
@@ -55,6 +627,7 @@ mod tests {
     use crate::const_vars::USER_VALUE_FILE;
 
     use super::*;
+    use crate::secret::SecretKey;
     use orion_variate::vars::{OriginValue, VarDefinition};
     use tempfile::tempdir;
 
@@ -70,13 +643,49 @@ mod tests {
         let temp_dir = tempdir().unwrap();
         let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
 
-        let result = mix_used_value(options, &value_paths, &vars).unwrap();
+        let result = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
         assert_eq!(
             result.get("TEST_KEY"),
             Some(&OriginValue::from("default_value").with_origin("mod-default"))
         );
     }
 
+    #[test]
+    fn test_build_used_value_with_secret() {
+        test_init();
+        let key = SecretKey::from_pass("galaxy");
+        let mut global_dict = ValueDict::new();
+        global_dict.insert(
+            "DB_PASS".to_string(),
+            ValueType::from(key.encrypt("s3cret").unwrap().as_str()),
+        );
+        let vars = VarCollection::define(vec![
+            VarDefinition::from(("DB_PASS", "")),
+            VarDefinition::from(("DB_URL", "mysql://root:${DB_PASS}@db")),
+        ]);
+        let temp_dir = tempdir().unwrap();
+        let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
+
+        let options = LocalizeOptions::new(global_dict.clone(), false);
+        assert!(mix_used_value(options, &value_paths, &vars, &VarRules::default()).is_err());
+
+        let options = LocalizeOptions::new(global_dict, false).with_secret(Some(key));
+        let (used, mask) =
+            mix_used_value_masked(options, &value_paths, &vars, &VarRules::default(), &[]).unwrap();
+        assert_eq!(
+            used.get("DB_PASS"),
+            Some(&OriginValue::from("s3cret").with_origin("global"))
+        );
+        assert_eq!(
+            used.get("DB_URL"),
+            Some(&OriginValue::from("mysql://root:s3cret@db").with_origin("mod-default"))
+        );
+        assert_eq!(
+            mask.mask_text("mysql://root:s3cret@db"),
+            "mysql://root:******@db"
+        );
+    }
+
     #[test]
     fn test_build_used_value_with_global_value() {
         test_init();
@@ -94,7 +703,7 @@ mod tests {
         let temp_dir = tempdir().unwrap();
         let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
 
-        let result = mix_used_value(options, &value_paths, &vars).unwrap();
+        let result = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
         assert_eq!(
             result.get("TEST_KEY"),
             Some(&OriginValue::from("global_value").with_origin("global"))
@@ -124,7 +733,7 @@ mod tests {
         let options = LocalizeOptions::new(ValueDict::new(), false);
         let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
 
-        let result = mix_used_value(options, &value_paths, &vars).unwrap();
+        let result = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
         assert_eq!(
             result.get("TEST_KEY"),
             Some(&OriginValue::from("user_value").with_origin("mod-cust"))
@@ -153,7 +762,7 @@ mod tests {
         let options = LocalizeOptions::new(global_dict, false);
         let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
 
-        let result = mix_used_value(options, &value_paths, &vars).unwrap();
+        let result = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
         // 验证优先级: global > cust  > default
         assert_eq!(
             result.get("TEST_KEY"),
@@ -182,7 +791,7 @@ mod tests {
         let temp_dir = tempdir().unwrap();
         let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
 
-        let result = mix_used_value(options, &value_paths, &vars).unwrap();
+        let result = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
         assert!(result.is_empty());
     }
 
@@ -199,7 +808,7 @@ mod tests {
         let temp_dir = tempdir().unwrap();
         let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
 
-        let result = mix_used_value(options, &value_paths, &vars).unwrap();
+        let result = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
 
         assert_eq!(
             result.get("STRING_VAR"),
@@ -230,7 +839,7 @@ mod tests {
         let temp_dir = tempdir().unwrap();
         let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
 
-        let result = mix_used_value(options, &value_paths, &vars).unwrap();
+        let result = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
 
         assert_eq!(
             result.get("ENV_VAR"),
@@ -257,7 +866,7 @@ mod tests {
         let options = LocalizeOptions::new(ValueDict::new(), true);
         let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
 
-        let result = mix_used_value(options, &value_paths, &vars).unwrap();
+        let result = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
         assert_eq!(
             result.get("TEST_KEY"),
             Some(&OriginValue::from("default_value").with_origin("mod-default"))
@@ -278,11 +887,332 @@ mod tests {
         let options = LocalizeOptions::new(global_dict, false);
         let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
 
-        let result = mix_used_value(options, &value_paths, &vars).unwrap();
+        let result = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
         // 全局值应该覆盖用户值和默认值
         assert_eq!(
             result.get("TEST_KEY"),
             Some(&OriginValue::from("global_value").with_origin("global"))
         );
     }
+
+    #[test]
+    fn test_rules_violation_report() {
+        use crate::software::{Constraint, ConstraintRule, VarKind};
+        test_init();
+        let temp_dir = tempdir().unwrap();
+        let user_value_path = temp_dir.path().join(USER_VALUE_FILE);
+        std::fs::write(&user_value_path, "PORT: 70000").unwrap();
+
+        let vars = VarCollection::define(vec![
+            VarDefinition::from(("PORT", 3306)),
+            VarDefinition::from(("MODE", "master")),
+            VarDefinition::from(("PASSWORD", "")),
+        ]);
+        let rules = VarRules::from(vec![
+            ConstraintRule::new("PORT", Constraint::WithInScope(1.0, 65535.0)),
+            ConstraintRule::define("MODE").with_kind(VarKind::Int),
+            ConstraintRule::define("PASSWORD").with_required(true),
+        ]);
+        let options = LocalizeOptions::new(ValueDict::new(), false);
+        let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
+
+        let err = mix_used_value(options, &value_paths, &vars, &rules).unwrap_err();
+        let report = err.to_string();
+        assert!(report.contains("PORT [mod-cust]"));
+        assert!(report.contains("MODE [mod-default]"));
+        assert!(report.contains("PASSWORD [mod-default]"));
+
+        let used = mix_used_value(
+            LocalizeOptions::new(ValueDict::new(), true),
+            &value_paths,
+            &vars,
+            &VarRules::from(vec![ConstraintRule::new(
+                "PORT",
+                Constraint::WithInScope(1.0, 65535.0),
+            )]),
+        );
+        assert!(used.is_ok());
+    }
+
+    #[test]
+    fn test_explain_used_value() {
+        test_init();
+        let temp_dir = tempdir().unwrap();
+        let user_value_path = temp_dir.path().join(USER_VALUE_FILE);
+        std::fs::write(&user_value_path, "PORT: 3307\nURL: mysql://${HOST}:3307").unwrap();
+
+        let mut global_dict = ValueDict::new();
+        global_dict.insert("HOST", ValueType::from("10.0.0.1"));
+        let vars = VarCollection::define(vec![
+            VarDefinition::from(("HOST", "localhost")),
+            VarDefinition::from(("PORT", 3306)),
+            VarDefinition::from(("URL", "")),
+        ]);
+        let options = LocalizeOptions::new(global_dict, false);
+        let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
+
+        let explains = explain_used_value(options, &value_paths, &vars, &[]).unwrap();
+        let find = |key: &str| explains.iter().find(|x| x.key() == key).unwrap();
+
+        let host = find("HOST");
+        assert_eq!(host.winner(), "global");
+        assert_eq!(host.shadowed().len(), 1);
+        assert_eq!(host.shadowed()[0].source(), "mod-default");
+
+        let port = find("PORT");
+        assert_eq!(port.winner(), "mod-cust");
+        assert_eq!(port.shadowed()[0].value(), &serde_json::json!(3306));
+
+        let url = find("URL");
+        assert_eq!(url.winner(), "mod-cust");
+        assert_eq!(
+            url.expanded(),
+            &vec![("HOST".to_string(), "10.0.0.1".to_string())]
+        );
+        assert!(url.to_string().contains("expand  : ${HOST} -> 10.0.0.1"));
+    }
+
+    #[test]
+    fn test_explain_sensitive_value() {
+        test_init();
+        let temp_dir = tempdir().unwrap();
+        let user_value_path = temp_dir.path().join(USER_VALUE_FILE);
+        std::fs::write(&user_value_path, "DB_PASS: p@ss").unwrap();
+        let vars = VarCollection::define(vec![
+            VarDefinition::from(("DB_PASS", "changeme")),
+            VarDefinition::from(("DB_URL", "mysql://root:${DB_PASS}@db")),
+        ]);
+        let options = LocalizeOptions::new(ValueDict::new(), false);
+        let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
+        let sensitive = vec!["DB_PASS".to_string()];
+
+        let explains = explain_used_value(options, &value_paths, &vars, &sensitive).unwrap();
+        let text: String = explains.iter().map(|x| x.to_string()).collect();
+        assert!(!text.contains("p@ss") && !text.contains("changeme"));
+        assert!(text.contains("DB_PASS = \"******\""));
+        assert!(text.contains("mysql://root:******@db"));
+    }
+
+    #[test]
+    fn test_env_overlay_value() {
+        test_init();
+        let temp_dir = tempdir().unwrap();
+        let env_dir = temp_dir.path().join(VALUE_DIR).join(VALUE_ENV_DIR);
+        std::fs::create_dir_all(&env_dir).unwrap();
+        std::fs::write(env_dir.join("prod.yml"), "HOST: 10.0.0.9").unwrap();
+        assert!(load_project_env_value(temp_dir.path(), &Some("dev".to_string())).is_err());
+        let env_value = load_project_env_value(temp_dir.path(), &Some("prod".to_string())).unwrap();
+
+        let mut global_dict = ValueDict::new();
+        global_dict.insert("HOST", ValueType::from("10.0.0.1"));
+        global_dict.insert("URL", ValueType::from("http://${HOST}"));
+        let vars = VarCollection::define(vec![
+            VarDefinition::from(("HOST", "localhost")),
+            VarDefinition::from(("PORT", "80")),
+        ]);
+        let options = LocalizeOptions::new(global_dict, false).with_env_value(env_value);
+        let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
+
+        let used =
+            mix_used_value(options.clone(), &value_paths, &vars, &VarRules::default()).unwrap();
+        assert_eq!(
+            used.get("HOST"),
+            Some(&OriginValue::from("10.0.0.9").with_origin("env:prod"))
+        );
+        assert_eq!(
+            used.get("URL"),
+            Some(&OriginValue::from("http://10.0.0.9").with_origin("global"))
+        );
+        assert_eq!(
+            used.get("PORT"),
+            Some(&OriginValue::from("80").with_origin("mod-default"))
+        );
+
+        let explains = explain_used_value(options, &value_paths, &vars, &[]).unwrap();
+        let host = explains.iter().find(|x| x.key() == "HOST").unwrap();
+        assert_eq!(host.winner(), "env:prod");
+        assert_eq!(host.shadowed().len(), 2);
+    }
+
+    #[test]
+    fn test_value_overrides() {
+        test_init();
+        let temp_dir = tempdir().unwrap();
+        let first = temp_dir.path().join("first.yml");
+        let second = temp_dir.path().join("second.yml");
+        let cert = temp_dir.path().join("ca.pem");
+        std::fs::write(&first, "PORT: 3306\ndb:\n  host: 10.0.0.1\n  port: 3306").unwrap();
+        std::fs::write(&second, "PORT: 3308").unwrap();
+        std::fs::write(&cert, "-----CERT-----").unwrap();
+        let values = vec![first.display().to_string(), second.display().to_string()];
+        let to_vec = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+
+        assert!(load_value_overrides(&[], None, &to_vec(&["PORT"]), &[], &[]).is_err());
+        assert!(
+            load_value_overrides(&[], None, &to_vec(&["PORT=1", "PORT.a=2"]), &[], &[]).is_err()
+        );
+        let overrides = load_value_overrides(
+            &values,
+            None,
+            &to_vec(&["db.port=3307", "DEBUG=true"]),
+            &to_vec(&["VERSION=1.10"]),
+            &to_vec(&[format!("CERT={}", cert.display()).as_str()]),
+        )
+        .unwrap();
+        let origins: Vec<&str> = overrides.iter().map(|(x, _)| x.as_str()).collect();
+        assert_eq!(
+            origins,
+            vec![
+                format!("value:{}", values[0]).as_str(),
+                format!("value:{}", values[1]).as_str(),
+                "set",
+                "set-string",
+                "set-file"
+            ]
+        );
+
+        let global_dict = load_project_global_value(temp_dir.path()).unwrap();
+        let vars = VarCollection::define(vec![VarDefinition::from(("VERSION", "1.0"))]);
+        let options = LocalizeOptions::new(global_dict, false).with_overrides(overrides);
+        let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
+        let used = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
+
+        assert_eq!(
+            used.get("PORT").and_then(|x| x.origin().clone()),
+            Some(format!("value:{}", values[1]))
+        );
+        assert_eq!(
+            used.get("VERSION"),
+            Some(&OriginValue::from("1.10").with_origin("set-string"))
+        );
+        assert_eq!(
+            used.get("CERT"),
+            Some(&OriginValue::from("-----CERT-----").with_origin("set-file"))
+        );
+        let data = serde_json::to_value(used.export_value()).unwrap();
+        assert_eq!(data["DEBUG"], serde_json::json!(true));
+        assert_eq!(
+            data["db"],
+            serde_json::json!({"host": "10.0.0.1", "port": 3307})
+        );
+        assert_eq!(
+            used.get("db").and_then(|x| x.origin().clone()),
+            Some("set".to_string())
+        );
+    }
+
+    #[test]
+    fn test_global_value_format() {
+        let temp_dir = tempdir().unwrap();
+        let value_root = temp_dir.path().join(VALUE_DIR);
+        std::fs::create_dir_all(&value_root).unwrap();
+        std::fs::write(value_root.join("value.json"), r#"{"PORT": 3307}"#).unwrap();
+
+        let global_dict = load_project_global_value(temp_dir.path()).unwrap();
+        assert_eq!(
+            serde_json::to_value(global_dict.get("PORT")).unwrap(),
+            serde_json::json!(3307)
+        );
+        // 已有 json 全局值时不再生成 value.yml 样例
+        assert!(!value_root.join("value.yml").exists());
+    }
+
+    #[test]
+    fn test_load_value_formats() {
+        test_init();
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join("v.json"), r#"{"HOST": "h1", "PORT": 3306}"#).unwrap();
+        std::fs::write(root.join("v.toml"), "HOST = \"h2\"\nPORT = 3307").unwrap();
+        std::fs::write(root.join("v.ini"), "HOST=h3\nPORT=3308").unwrap();
+        std::fs::write(root.join("v.txt"), r#"{"HOST": "h4"}"#).unwrap();
+
+        let host = |dict: ValueDict| dict.get("HOST").cloned();
+        for (file, expect) in [("v.json", "h1"), ("v.toml", "h2"), ("v.ini", "h3")] {
+            let dict = load_value_file(&root.join(file), None).unwrap();
+            assert_eq!(host(dict), Some(ValueType::from(expect)));
+        }
+        let dict = load_value_file(&root.join("v.txt"), Some(ValueFormat::Json)).unwrap();
+        assert_eq!(host(dict), Some(ValueType::from("h4")));
+        assert!(load_value_file(&root.join("v.ini"), Some(ValueFormat::Json)).is_err());
+        assert_eq!("yml".parse::<ValueFormat>(), Ok(ValueFormat::Yaml));
+        assert!("xml".parse::<ValueFormat>().is_err());
+
+        let value_root = root.join("mod");
+        std::fs::create_dir_all(&value_root).unwrap();
+        std::fs::write(value_root.join("value.toml"), "PORT = 3309").unwrap();
+        let value_paths = TargetValuePaths::from(&value_root);
+        assert_eq!(
+            value_paths.user_value_file(),
+            &value_root.join("value.toml")
+        );
+    }
+
+    #[test]
+    fn test_mods_ref_resolve() {
+        test_init();
+        let temp_dir = tempdir().unwrap();
+        let user_value_path = temp_dir.path().join(USER_VALUE_FILE);
+        std::fs::write(&user_value_path, "DB_PORT: ${mods.mysql.MYSQL_PORT}").unwrap();
+
+        let mut mods_value = ValueDict::new();
+        mods_value.insert("mods.mysql.MYSQL_HOST", ValueType::from("10.0.0.2"));
+        mods_value.insert("mods.mysql.MYSQL_PORT", ValueType::from("3307"));
+        let vars = VarCollection::define(vec![
+            VarDefinition::from(("DB_URL", "${mods.mysql.MYSQL_HOST}:${DB_PORT}")),
+            VarDefinition::from(("DB_PORT", "3306")),
+        ]);
+        let options = LocalizeOptions::new(ValueDict::new(), false).with_mods_value(mods_value);
+        let value_paths = TargetValuePaths::from(&temp_dir.path().to_path_buf());
+
+        let used = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap();
+        assert_eq!(
+            used.get("DB_PORT"),
+            Some(&OriginValue::from("3307").with_origin("mod-cust"))
+        );
+        assert_eq!(
+            used.get("DB_URL"),
+            Some(&OriginValue::from("10.0.0.2:3307").with_origin("mod-default"))
+        );
+
+        let options = LocalizeOptions::new(ValueDict::new(), false);
+        let err = mix_used_value(options, &value_paths, &vars, &VarRules::default()).unwrap_err();
+        assert!(err.to_string().contains("mods.mysql.MYSQL_PORT"));
+    }
+
+    #[test]
+    fn test_mods_ref_typed_nested() {
+        let typed =
+            |json: serde_json::Value| -> ValueType { serde_json::from_value(json).unwrap() };
+        let mut mods_value = ValueDict::new();
+        mods_value.insert("mods.mysql.MYSQL_HOST", ValueType::from("10.0.0.2"));
+        mods_value.insert("mods.mysql.MYSQL_PORT", typed(serde_json::json!(3307)));
+        let mut dict = ValueDict::new();
+        dict.insert("DB_PORT", ValueType::from("${mods.mysql.MYSQL_PORT}"));
+        dict.insert(
+            "DB",
+            typed(serde_json::json!({
+                "url": "${mods.mysql.MYSQL_HOST}:${mods.mysql.MYSQL_PORT}",
+                "ports": ["${mods.mysql.MYSQL_PORT}", 80],
+            })),
+        );
+
+        let resolved = resolve_mods_ref(dict, &mods_value).unwrap();
+        assert_eq!(
+            serde_json::to_value(resolved.get("DB_PORT")).unwrap(),
+            serde_json::json!(3307)
+        );
+        assert_eq!(
+            serde_json::to_value(resolved.get("DB")).unwrap(),
+            serde_json::json!({"url": "10.0.0.2:3307", "ports": [3307, 80]})
+        );
+
+        let mut dict = ValueDict::new();
+        dict.insert(
+            "DB",
+            typed(serde_json::json!({"hosts": ["${mods.redis.HOST}"]})),
+        );
+        let err = resolve_mods_ref(dict, &mods_value).unwrap_err();
+        assert!(err.to_string().contains("mods.redis.HOST"));
+    }
 }
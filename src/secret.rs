@@ -0,0 +1,631 @@
+use std::path::{Path, PathBuf};
+
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use orion_common::serde::SerdeResult;
+use orion_error::{ErrorOwe, ErrorWith};
+use orion_variate::vars::{ValueDict, ValueType};
+use serde_derive::Deserialize;
+use sha2::Sha256;
+
+use crate::{
+    const_vars::{SECRET_KEY_ENV, SECRET_KEY_FILE, SECRET_PASS_ENV},
+    error::{LocalizeReason, MainReason, MainResult, ToErr},
+    project::{load_value_file, save_value_file},
+};
+
+// 加密值格式: ENC[v1:base64(salt|nonce|密文)]
+const ENC_PREFIX: &str = "ENC[v1:";
+const ENC_SUFFIX: &str = "]";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KDF_ROUNDS: u32 = 100_000;
+pub const SECRET_MASK: &str = "******";
+
+fn secret_err<T>(msg: String) -> MainResult<T> {
+    MainReason::from(LocalizeReason::Secret(msg)).err_result()
+}
+
+// 密钥材料: 项目密钥文件内容 或 口令, 每个值用独立的 salt 派生密钥
+#[derive(Clone)]
+pub struct SecretKey {
+    material: Vec<u8>,
+}
+
+impl std::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretKey({SECRET_MASK})")
+    }
+}
+
+impl SecretKey {
+    pub fn from_pass<S: AsRef<str>>(pass: S) -> Self {
+        Self {
+            material: pass.as_ref().as_bytes().to_vec(),
+        }
+    }
+    pub fn from_file(path: &Path) -> MainResult<Self> {
+        let content = std::fs::read_to_string(path).owe_res().with(path)?;
+        let content = content.trim();
+        if content.is_empty() {
+            return secret_err(format!("empty key file: {}", path.display()));
+        }
+        Ok(Self::from_pass(content))
+    }
+    // 生成随机密钥写入 path, 仅属主可读写
+    pub fn generate(path: &Path) -> MainResult<Self> {
+        let mut raw = [0u8; 32];
+        OsRng.fill_bytes(&mut raw);
+        let content = STANDARD.encode(raw);
+        if path.exists() {
+            std::fs::remove_file(path).owe_sys().with(path)?;
+        }
+        std::fs::write(path, format!("{content}\n"))
+            .owe_sys()
+            .with(path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+                .owe_sys()
+                .with(path)?;
+        }
+        Ok(Self::from_pass(content))
+    }
+    // 口令环境变量优先, 其次环境变量指定的密钥文件, 最后是项目根目录下的密钥文件
+    pub fn load(root: &Path) -> MainResult<Option<Self>> {
+        if let Ok(pass) = std::env::var(SECRET_PASS_ENV) {
+            if !pass.is_empty() {
+                return Ok(Some(Self::from_pass(pass)));
+            }
+        }
+        if let Ok(path) = std::env::var(SECRET_KEY_ENV) {
+            return Self::from_file(Path::new(&path)).map(Some);
+        }
+        let path = root.join(SECRET_KEY_FILE);
+        if path.exists() {
+            return Self::from_file(&path).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn cipher(&self, salt: &[u8]) -> Aes256Gcm {
+        let mut key = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(&self.material, salt, KDF_ROUNDS, &mut key);
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+    }
+
+    pub fn encrypt(&self, plain: &str) -> MainResult<String> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let Ok(data) = self.cipher(&salt).encrypt(&nonce, plain.as_bytes()) else {
+            return secret_err("encrypt failed".to_string());
+        };
+        let mut packed = Vec::with_capacity(SALT_LEN + NONCE_LEN + data.len());
+        packed.extend_from_slice(&salt);
+        packed.extend_from_slice(&nonce);
+        packed.extend_from_slice(&data);
+        Ok(format!(
+            "{ENC_PREFIX}{}{ENC_SUFFIX}",
+            STANDARD.encode(packed)
+        ))
+    }
+
+    pub fn decrypt(&self, text: &str) -> MainResult<String> {
+        let Some(body) = text
+            .strip_prefix(ENC_PREFIX)
+            .and_then(|x| x.strip_suffix(ENC_SUFFIX))
+        else {
+            return secret_err("not an encrypted value".to_string());
+        };
+        let packed = match STANDARD.decode(body) {
+            Ok(packed) if packed.len() > SALT_LEN + NONCE_LEN => packed,
+            _ => return secret_err("bad encrypted value".to_string()),
+        };
+        let (salt, rest) = packed.split_at(SALT_LEN);
+        let (nonce, data) = rest.split_at(NONCE_LEN);
+        let Ok(plain) = self.cipher(salt).decrypt(Nonce::from_slice(nonce), data) else {
+            return secret_err("decrypt failed, wrong key or passphrase".to_string());
+        };
+        String::from_utf8(plain).owe_data()
+    }
+}
+
+pub fn is_encrypted(text: &str) -> bool {
+    text.starts_with(ENC_PREFIX) && text.ends_with(ENC_SUFFIX)
+}
+
+fn encrypted_text(value: &ValueType) -> Option<String> {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) if is_encrypted(s.as_str()) => Some(s),
+        _ => None,
+    }
+}
+
+// 逐个替换 value 中(含嵌套 map/数组)的加密串, 没有加密串时返回 None
+fn map_encrypted(
+    value: &ValueType,
+    f: &mut dyn FnMut(&str) -> MainResult<String>,
+) -> MainResult<Option<ValueType>> {
+    if let Some(text) = encrypted_text(value) {
+        return Ok(Some(ValueType::from(f(text.as_str())?.as_str())));
+    }
+    let json = serde_json::to_value(value).owe_data()?;
+    match map_encrypted_json(&json, f)? {
+        Some(json) => Ok(Some(serde_json::from_value(json).owe_data()?)),
+        None => Ok(None),
+    }
+}
+
+fn map_encrypted_json(
+    value: &serde_json::Value,
+    f: &mut dyn FnMut(&str) -> MainResult<String>,
+) -> MainResult<Option<serde_json::Value>> {
+    match value {
+        serde_json::Value::String(text) if is_encrypted(text.as_str()) => {
+            Ok(Some(serde_json::Value::String(f(text.as_str())?)))
+        }
+        serde_json::Value::Array(items) => {
+            let mut changed = false;
+            let mut list = Vec::new();
+            for item in items {
+                match map_encrypted_json(item, f)? {
+                    Some(item) => {
+                        changed = true;
+                        list.push(item);
+                    }
+                    None => list.push(item.clone()),
+                }
+            }
+            Ok(changed.then_some(serde_json::Value::Array(list)))
+        }
+        serde_json::Value::Object(map) => {
+            let mut changed = false;
+            let mut obj = serde_json::Map::new();
+            for (name, item) in map {
+                match map_encrypted_json(item, f)? {
+                    Some(item) => {
+                        changed = true;
+                        obj.insert(name.clone(), item);
+                    }
+                    None => {
+                        obj.insert(name.clone(), item.clone());
+                    }
+                }
+            }
+            Ok(changed.then_some(serde_json::Value::Object(obj)))
+        }
+        _ => Ok(None),
+    }
+}
+
+// 解密 dict 中(含嵌套)的加密值, 明文记入 mask
+pub fn decrypt_dict(
+    dict: ValueDict,
+    key: Option<&SecretKey>,
+    mask: &mut ValueMask,
+) -> MainResult<ValueDict> {
+    let mut plain_dict = ValueDict::new();
+    for (name, value) in dict.iter() {
+        let plain = map_encrypted(value, &mut |text: &str| {
+            let Some(key) = key else {
+                return secret_err(format!(
+                    "{name} is encrypted, set {SECRET_PASS_ENV} or provide {SECRET_KEY_FILE}"
+                ));
+            };
+            let plain = key.decrypt(text).with(("key", name.as_str()))?;
+            mask.add(plain.as_str());
+            Ok(plain)
+        })?;
+        plain_dict.insert(name.clone(), plain.unwrap_or_else(|| value.clone()));
+    }
+    Ok(plain_dict)
+}
+
+// 面向人的输出(_used.yml, explain 等)中隐藏明文 与 sensitive 变量
+#[derive(Clone, Default)]
+pub struct ValueMask {
+    secrets: Vec<String>,
+    keys: Vec<String>,
+}
+
+impl std::fmt::Debug for ValueMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ValueMask({}, keys: {:?})",
+            self.secrets.len(),
+            self.keys
+        )
+    }
+}
+
+impl ValueMask {
+    pub fn add<S: Into<String>>(&mut self, secret: S) {
+        let secret = secret.into();
+        if secret.is_empty() || self.secrets.contains(&secret) {
+            return;
+        }
+        self.secrets.push(secret);
+        // 先替换较长的, 避免部分重叠时残留
+        self.secrets.sort_by_key(|x| std::cmp::Reverse(x.len()));
+    }
+    // 按变量名隐藏; 字符串取值同时按内容隐藏, 覆盖其被 ${VAR} 展开后的出现
+    pub fn add_sensitive(&mut self, keys: &[String], dict: &ValueDict) {
+        for key in keys {
+            if !self.keys.contains(key) {
+                self.keys.push(key.clone());
+            }
+            if let Some(Ok(serde_json::Value::String(s))) = dict.get(key).map(serde_json::to_value)
+            {
+                self.add(s);
+            }
+        }
+    }
+    pub fn is_sensitive(&self, key: &str) -> bool {
+        self.keys.iter().any(|x| x == key)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty() && self.keys.is_empty()
+    }
+    pub fn mask_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for secret in self.secrets.iter() {
+            if text.contains(secret.as_str()) {
+                text = text.replace(secret.as_str(), SECRET_MASK);
+            }
+        }
+        text
+    }
+    pub fn mask_json(&self, value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::String(s) => serde_json::Value::String(self.mask_text(s.as_str())),
+            serde_json::Value::Array(arr) => {
+                serde_json::Value::Array(arr.into_iter().map(|x| self.mask_json(x)).collect())
+            }
+            serde_json::Value::Object(map) => serde_json::Value::Object(
+                map.into_iter()
+                    .map(|(k, v)| (k, self.mask_json(v)))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+    fn mask_yaml(&self, value: serde_yaml::Value) -> serde_yaml::Value {
+        match value {
+            serde_yaml::Value::String(s) => serde_yaml::Value::String(self.mask_text(s.as_str())),
+            serde_yaml::Value::Sequence(seq) => {
+                serde_yaml::Value::Sequence(seq.into_iter().map(|x| self.mask_yaml(x)).collect())
+            }
+            serde_yaml::Value::Mapping(map) => serde_yaml::Value::Mapping(
+                map.into_iter()
+                    .map(|(k, v)| (k, self.mask_yaml(v)))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+    // 顶层 sensitive 项整体替换; 带来源的项({origin, value})只替换 value
+    fn mask_keys(&self, value: serde_yaml::Value) -> serde_yaml::Value {
+        let serde_yaml::Value::Mapping(map) = value else {
+            return value;
+        };
+        let masked = serde_yaml::Value::String(SECRET_MASK.to_string());
+        serde_yaml::Value::Mapping(
+            map.into_iter()
+                .map(|(k, v)| match k.as_str() {
+                    Some(name) if self.is_sensitive(name) => match v {
+                        serde_yaml::Value::Mapping(mut item) if item.contains_key("value") => {
+                            item.insert("value".into(), masked.clone());
+                            (k, serde_yaml::Value::Mapping(item))
+                        }
+                        _ => (k, masked.clone()),
+                    },
+                    _ => (k, v),
+                })
+                .collect(),
+        )
+    }
+    // 以 yaml 写出可读文件, 其中的明文替换为掩码
+    pub fn save_readable<T: serde::Serialize>(&self, data: &T, path: &Path) -> MainResult<()> {
+        let value = self.mask_keys(serde_yaml::to_value(data).owe_data()?);
+        let content = serde_yaml::to_string(&self.mask_yaml(value)).owe_data()?;
+        std::fs::write(path, content).owe_res().with(path)?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct VarMark {
+    name: String,
+    #[serde(default)]
+    sensitive: bool,
+}
+
+#[derive(Deserialize)]
+struct VarMarks {
+    #[serde(default)]
+    vars: Vec<VarMark>,
+}
+
+// vars.yml 中标记了 sensitive: true 的变量
+pub fn sensitive_vars(path: &Path) -> SerdeResult<Vec<String>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(path).owe_res().with(path)?;
+    let marks: VarMarks = serde_yaml::from_str(content.as_str())
+        .owe_conf()
+        .with(path)?;
+    Ok(marks
+        .vars
+        .into_iter()
+        .filter(|x| x.sensitive)
+        .map(|x| x.name)
+        .collect())
+}
+
+// VarCollection 写出时不带 sensitive, 回写到 vars.yml 对应项
+pub fn mark_sensitive(path: &Path, names: &[String]) -> SerdeResult<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let content = std::fs::read_to_string(path).owe_res().with(path)?;
+    let mut doc: serde_yaml::Value = serde_yaml::from_str(content.as_str())
+        .owe_conf()
+        .with(path)?;
+    if let Some(vars) = doc.get_mut("vars").and_then(|x| x.as_sequence_mut()) {
+        for var in vars.iter_mut() {
+            let marked = var
+                .get("name")
+                .and_then(|x| x.as_str())
+                .is_some_and(|x| names.iter().any(|n| n == x));
+            if let (true, Some(item)) = (marked, var.as_mapping_mut()) {
+                item.insert("sensitive".into(), serde_yaml::Value::Bool(true));
+            }
+        }
+    }
+    let content = serde_yaml::to_string(&doc).owe_data()?;
+    std::fs::write(path, content).owe_res().with(path)?;
+    Ok(())
+}
+
+// 在 value 文件中写入加密项
+pub fn set_secret(path: &Path, name: &str, plain: &str, key: &SecretKey) -> MainResult<()> {
+    let mut dict = if path.exists() {
+        load_value_file(path, None)?
+    } else {
+        ValueDict::new()
+    };
+    dict.insert(
+        name.to_string(),
+        ValueType::from(key.encrypt(plain)?.as_str()),
+    );
+    save_value_file(path, &dict)
+}
+
+pub fn get_secret(path: &Path, name: &str, key: &SecretKey) -> MainResult<Option<String>> {
+    let dict = load_value_file(path, None)?;
+    match dict.get(name) {
+        Some(value) => match encrypted_text(value) {
+            Some(text) => key.decrypt(text.as_str()).with(("key", name)).map(Some),
+            None => secret_err(format!("{name} is not encrypted")),
+        },
+        None => Ok(None),
+    }
+}
+
+// 用新密钥重新加密各 value 文件中(含嵌套)的加密项, 结果先写入同目录的临时文件;
+// 返回前原文件均未改动, commit 后才替换
+pub fn rotate_files(
+    files: &[PathBuf],
+    old: &SecretKey,
+    new: &SecretKey,
+) -> MainResult<SecretRotation> {
+    let mut rotation = SecretRotation::default();
+    for path in files {
+        let dict = load_value_file(path, None)?;
+        let mut cur = ValueDict::new();
+        let mut changed = false;
+        for (name, value) in dict.iter() {
+            let rotated = map_encrypted(value, &mut |text: &str| {
+                let plain = old.decrypt(text).with(("key", name.as_str())).with(path)?;
+                rotation.count += 1;
+                new.encrypt(plain.as_str())
+            })?;
+            changed |= rotated.is_some();
+            cur.insert(name.clone(), rotated.unwrap_or_else(|| value.clone()));
+        }
+        if changed {
+            let tmp = rotate_tmp_path(path);
+            rotation.staged.push((tmp.clone(), path.clone()));
+            save_value_file(&tmp, &cur)?;
+        }
+    }
+    Ok(rotation)
+}
+
+// 保留原扩展名, 写回时格式不变
+fn rotate_tmp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".rotate.{name}"))
+}
+
+// 已重新加密但尚未替换原文件的结果, 未 commit 时丢弃临时文件
+#[derive(Default)]
+pub struct SecretRotation {
+    staged: Vec<(PathBuf, PathBuf)>,
+    count: usize,
+}
+
+impl SecretRotation {
+    pub fn count(&self) -> usize {
+        self.count
+    }
+    // 逐个替换原文件, 中途失败时已替换的文件只能用新密钥解密
+    pub fn commit(mut self) -> MainResult<usize> {
+        while !self.staged.is_empty() {
+            let (tmp, path) = &self.staged[0];
+            std::fs::rename(tmp, path).owe_sys().with(path)?;
+            self.staged.remove(0);
+        }
+        Ok(self.count)
+    }
+}
+
+impl Drop for SecretRotation {
+    fn drop(&mut self) {
+        for (tmp, _) in self.staged.iter() {
+            let _ = std::fs::remove_file(tmp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_secret_roundtrip() {
+        let key = SecretKey::from_pass("galaxy");
+        let text = key.encrypt("db-pass").unwrap();
+        assert!(is_encrypted(text.as_str()));
+        assert!(!text.contains("db-pass"));
+        assert_eq!(key.decrypt(text.as_str()).unwrap(), "db-pass");
+        assert!(
+            SecretKey::from_pass("other")
+                .decrypt(text.as_str())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_decrypt_dict_and_mask() {
+        let key = SecretKey::from_pass("galaxy");
+        let mut dict = ValueDict::new();
+        dict.insert(
+            "DB_PASS",
+            ValueType::from(key.encrypt("s3cret").unwrap().as_str()),
+        );
+        dict.insert("DB_USER", ValueType::from("root"));
+
+        let mut mask = ValueMask::default();
+        assert!(decrypt_dict(dict.clone(), None, &mut mask).is_err());
+        let plain = decrypt_dict(dict, Some(&key), &mut mask).unwrap();
+        assert_eq!(plain.get("DB_PASS"), Some(&ValueType::from("s3cret")));
+        assert_eq!(plain.get("DB_USER"), Some(&ValueType::from("root")));
+        assert_eq!(
+            mask.mask_text("mysql://root:s3cret@db"),
+            "mysql://root:******@db"
+        );
+
+        let tmp_dir = tempdir().unwrap();
+        let readable = tmp_dir.path().join("_used.yml");
+        mask.save_readable(&plain, &readable).unwrap();
+        let content = std::fs::read_to_string(&readable).unwrap();
+        assert!(!content.contains("s3cret") && content.contains("root"));
+    }
+
+    #[test]
+    fn test_nested_secret_rotate() {
+        let tmp_dir = tempdir().unwrap();
+        let value_file = tmp_dir.path().join("value.json");
+        let old = SecretKey::from_pass("old-pass");
+        let new = SecretKey::from_pass("new-pass");
+        let content = serde_json::json!({
+            "db": {"user": "root", "pass": old.encrypt("s3cret").unwrap()},
+            "tokens": [old.encrypt("t1").unwrap(), "plain"],
+        });
+        std::fs::write(&value_file, content.to_string()).unwrap();
+
+        let files = vec![value_file.clone()];
+        let rotation = rotate_files(&files, &old, &new).unwrap();
+        assert_eq!(rotation.commit().unwrap(), 2);
+
+        let dict = load_value_file(&value_file, None).unwrap();
+        let mut mask = ValueMask::default();
+        assert!(decrypt_dict(dict.clone(), Some(&old), &mut mask).is_err());
+        let plain = decrypt_dict(dict, Some(&new), &mut mask).unwrap();
+        assert_eq!(
+            serde_json::to_value(&plain).unwrap(),
+            serde_json::json!({
+                "db": {"user": "root", "pass": "s3cret"},
+                "tokens": ["t1", "plain"],
+            })
+        );
+        assert_eq!(mask.mask_text("t1:s3cret"), "******:******");
+    }
+
+    #[test]
+    fn test_sensitive_vars_mask() {
+        let tmp_dir = tempdir().unwrap();
+        let vars_path = tmp_dir.path().join("vars.yml");
+        std::fs::write(
+            &vars_path,
+            "vars:\n- name: DB_PASS\n  value: p@ss\n- name: DB_USER\n  value: root\n",
+        )
+        .unwrap();
+        assert!(sensitive_vars(&vars_path).unwrap().is_empty());
+        let names = vec!["DB_PASS".to_string()];
+        mark_sensitive(&vars_path, &names).unwrap();
+        assert_eq!(sensitive_vars(&vars_path).unwrap(), names);
+
+        let mut dict = ValueDict::new();
+        dict.insert("DB_PASS", ValueType::from("p@ss"));
+        dict.insert("DB_USER", ValueType::from("root"));
+        let mut mask = ValueMask::default();
+        mask.add_sensitive(&names, &dict);
+        assert!(mask.is_sensitive("DB_PASS") && !mask.is_sensitive("DB_USER"));
+        assert_eq!(mask.mask_text("root:p@ss"), "root:******");
+
+        let readable = tmp_dir.path().join("_used.yml");
+        mask.save_readable(&dict, &readable).unwrap();
+        let content = std::fs::read_to_string(&readable).unwrap();
+        assert!(!content.contains("p@ss") && content.contains("DB_PASS"));
+    }
+
+    #[test]
+    fn test_secret_file_rotate() {
+        let tmp_dir = tempdir().unwrap();
+        let value_file = tmp_dir.path().join("value.yml");
+        let old = SecretKey::generate(&tmp_dir.path().join("old.key")).unwrap();
+        set_secret(&value_file, "TOKEN", "abc", &old).unwrap();
+        assert!(
+            !std::fs::read_to_string(&value_file)
+                .unwrap()
+                .contains("abc")
+        );
+        assert_eq!(
+            get_secret(&value_file, "TOKEN", &old).unwrap(),
+            Some("abc".to_string())
+        );
+
+        let new = SecretKey::from_pass("new-pass");
+        let files = vec![value_file.clone()];
+        // 未 commit 时原文件不变, 临时文件被清理
+        assert_eq!(rotate_files(&files, &old, &new).unwrap().count(), 1);
+        assert_eq!(
+            get_secret(&value_file, "TOKEN", &old).unwrap(),
+            Some("abc".to_string())
+        );
+        assert!(!rotate_tmp_path(&value_file).exists());
+
+        assert_eq!(
+            rotate_files(&files, &old, &new).unwrap().commit().unwrap(),
+            1
+        );
+        assert!(get_secret(&value_file, "TOKEN", &old).is_err());
+        assert_eq!(
+            get_secret(&value_file, "TOKEN", &new).unwrap(),
+            Some("abc".to_string())
+        );
+    }
+}
@@ -1,6 +1,17 @@
-use crate::{conf::ConfSpecRef, spec::WorkSpec};
+use std::{fmt::Display, path::Path};
+
+use crate::{
+    conf::ConfSpecRef,
+    error::{LocalizeReason, MainReason, MainResult, ToErr},
+    secret::ValueMask,
+    spec::WorkSpec,
+};
 use derive_getters::Getters;
-use orion_variate::ext::Artifact;
+use derive_more::{Deref, Display};
+use orion_common::serde::SerdeResult;
+use orion_error::{ErrorOwe, ErrorWith};
+use orion_variate::{ext::Artifact, vars::OriginDict};
+use regex::Regex;
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Getters, Clone, Debug, Deserialize, Serialize)]
@@ -30,8 +41,6 @@ impl LogFile {
     }
 }
 
-//rules: Vec<ConstraintRule>,
-
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub enum FileFormat {
     Json,
@@ -55,28 +64,275 @@ impl LogsSpec {
     }
 }
 
-#[allow(dead_code)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum VarKind {
+    #[display("string")]
+    String,
+    #[display("int")]
+    Int,
+    #[display("float")]
+    Float,
+    #[display("bool")]
+    Bool,
+}
+
+impl VarKind {
+    pub fn is_match(&self, value: &serde_json::Value) -> bool {
+        match self {
+            VarKind::String => value.is_string(),
+            VarKind::Int => value.is_i64() || value.is_u64(),
+            VarKind::Float => value.is_number(),
+            VarKind::Bool => value.is_boolean(),
+        }
+    }
+}
+
+// vars.yml 变量项上的规则, 与 sensitive 标记写在同一项
 #[derive(Clone, Debug, Getters, Deserialize, Serialize)]
 pub struct ConstraintRule {
+    #[serde(rename = "name")]
     key: String,
-    constraint: Constraint,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none", default)]
+    kind: Option<VarKind>,
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    required: bool,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    constraint: Option<Constraint>,
 }
 
 impl ConstraintRule {
     pub fn new<S: Into<String>>(key: S, constraint: Constraint) -> Self {
         Self {
             key: key.into(),
-            constraint,
+            kind: None,
+            required: false,
+            constraint: Some(constraint),
+        }
+    }
+    pub fn define<S: Into<String>>(key: S) -> Self {
+        Self {
+            key: key.into(),
+            kind: None,
+            required: false,
+            constraint: None,
+        }
+    }
+    pub fn with_kind(mut self, kind: VarKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+    pub fn with_required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+    pub fn with_constraint(mut self, constraint: Constraint) -> Self {
+        self.constraint = Some(constraint);
+        self
+    }
+
+    // 返回不满足规则的原因
+    pub fn check(&self, value: Option<&serde_json::Value>) -> Option<String> {
+        let value = match value {
+            Some(v) if !is_empty_value(v) => v,
+            _ => {
+                if self.required {
+                    return Some("required but not set".to_string());
+                }
+                return None;
+            }
+        };
+        if let Some(kind) = &self.kind {
+            if !kind.is_match(value) {
+                return Some(format!("expect type {kind}, got {value}"));
+            }
         }
+        self.constraint.as_ref().and_then(|x| x.check(value))
+    }
+    fn is_empty(&self) -> bool {
+        self.kind.is_none() && !self.required && self.constraint.is_none()
     }
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(untagged)]
 pub enum Constraint {
+    #[serde(rename = "regex")]
     Matching(String),
-    WithInScope(u64, u64),
+    // 闭区间, 整数与浮点数均按 f64 比较
+    #[serde(rename = "range")]
+    WithInScope(f64, f64),
+    #[serde(rename = "enum")]
+    OneOf(Vec<serde_json::Value>),
+}
+
+impl Constraint {
+    pub fn check(&self, value: &serde_json::Value) -> Option<String> {
+        match self {
+            Constraint::Matching(pattern) => match Regex::new(pattern) {
+                Ok(re) => {
+                    if re.is_match(value_text(value).as_str()) {
+                        None
+                    } else {
+                        Some(format!("{value} not match regex '{pattern}'"))
+                    }
+                }
+                Err(e) => Some(format!("bad regex '{pattern}': {e}")),
+            },
+            Constraint::WithInScope(min, max) => {
+                let num = value
+                    .as_f64()
+                    .or_else(|| value_text(value).parse::<f64>().ok());
+                match num {
+                    Some(n) if n >= *min && n <= *max => None,
+                    Some(_) => Some(format!("{value} out of range [{min}, {max}]")),
+                    None => Some(format!("{value} is not a number")),
+                }
+            }
+            Constraint::OneOf(items) => {
+                let text = value_text(value);
+                if items.iter().any(|x| x == value || value_text(x) == text) {
+                    None
+                } else {
+                    let items: Vec<String> = items.iter().map(value_text).collect();
+                    Some(format!("{value} not in [{}]", items.join(", ")))
+                }
+            }
+        }
+    }
+}
+
+fn is_empty_value(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::String(s) => s.is_empty(),
+        _ => false,
+    }
+}
+
+fn value_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+#[derive(Clone, Debug, Getters)]
+pub struct RuleViolation {
+    key: String,
+    origin: String,
+    reason: String,
+}
+
+impl Display for RuleViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}]: {}", self.key, self.origin, self.reason)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Deref)]
+#[serde(transparent)]
+pub struct VarRules {
+    rules: Vec<ConstraintRule>,
+}
+
+impl From<Vec<ConstraintRule>> for VarRules {
+    fn from(rules: Vec<ConstraintRule>) -> Self {
+        Self { rules }
+    }
+}
+
+#[derive(Deserialize)]
+struct VarsRules {
+    #[serde(default)]
+    vars: Vec<ConstraintRule>,
+}
+
+impl VarRules {
+    // vars.yml 中带 type/required/constraint 的变量项
+    pub fn from_vars(path: &Path) -> SerdeResult<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path).owe_res().with(path)?;
+        let parsed: VarsRules = serde_yaml::from_str(content.as_str())
+            .owe_conf()
+            .with(path)?;
+        Ok(Self {
+            rules: parsed.vars.into_iter().filter(|x| !x.is_empty()).collect(),
+        })
+    }
+    // VarCollection 写出时不带规则, 回写到 vars.yml 对应项
+    pub fn mark_vars(&self, path: &Path) -> SerdeResult<()> {
+        if self.rules.is_empty() {
+            return Ok(());
+        }
+        let content = std::fs::read_to_string(path).owe_res().with(path)?;
+        let mut doc: serde_yaml::Value = serde_yaml::from_str(content.as_str())
+            .owe_conf()
+            .with(path)?;
+        if let Some(vars) = doc.get_mut("vars").and_then(|x| x.as_sequence_mut()) {
+            for var in vars.iter_mut() {
+                let rule = var
+                    .get("name")
+                    .and_then(|x| x.as_str())
+                    .and_then(|x| self.find(x));
+                if let (Some(rule), Some(item)) = (rule, var.as_mapping_mut()) {
+                    if let serde_yaml::Value::Mapping(meta) =
+                        serde_yaml::to_value(rule).owe_data()?
+                    {
+                        for (k, v) in meta.into_iter().filter(|(k, _)| k != "name") {
+                            item.insert(k, v);
+                        }
+                    }
+                }
+            }
+        }
+        let content = serde_yaml::to_string(&doc).owe_data()?;
+        std::fs::write(path, content).owe_res().with(path)?;
+        Ok(())
+    }
+    pub fn find(&self, key: &str) -> Option<&ConstraintRule> {
+        self.rules.iter().find(|x| x.key() == key)
+    }
+    // 同名 key 保留已有的规则
+    pub fn merge(&mut self, other: &VarRules) {
+        for rule in other.rules.iter() {
+            if self.find(rule.key()).is_none() {
+                self.rules.push(rule.clone());
+            }
+        }
+    }
+    pub fn check(&self, dict: &OriginDict) -> Vec<RuleViolation> {
+        let mut violations = Vec::new();
+        for rule in self.rules.iter() {
+            let origin_value = dict.get(rule.key());
+            let value = origin_value.and_then(|x| serde_json::to_value(x.value()).ok());
+            if let Some(reason) = rule.check(value.as_ref()) {
+                let origin = origin_value
+                    .and_then(|x| x.origin().clone())
+                    .unwrap_or_else(|| "unset".to_string());
+                violations.push(RuleViolation {
+                    key: rule.key().clone(),
+                    origin,
+                    reason,
+                });
+            }
+        }
+        violations
+    }
+    // 报告中的加密值明文以掩码代替
+    pub fn validate(&self, dict: &OriginDict, mask: &ValueMask) -> MainResult<()> {
+        let violations = self.check(dict);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let report = violations
+            .iter()
+            .map(|x| mask.mask_text(x.to_string().as_str()))
+            .collect::<Vec<String>>()
+            .join("\n");
+        MainReason::from(LocalizeReason::Validate(report)).err_result()
+    }
 }
 
 #[cfg(test)]
@@ -155,4 +411,86 @@ mod tests {
         assert_eq!(loaded.workspec(), redis.workspec());
         Ok(())
     }
+
+    #[test]
+    fn var_rules_from_yaml() {
+        let data = r#"
+vars:
+- name: MYSQL_PORT
+  value: 3306
+  type: int
+  required: true
+  constraint:
+    range: [1, 65535]
+- name: MYSQL_MODE
+  value: master
+  sensitive: false
+  constraint:
+    enum: [master, slave]
+- name: MYSQL_USER
+  value: root
+  constraint:
+    regex: "^[a-z]+$"
+- name: MYSQL_HOST
+  value: localhost
+"#;
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let vars_path = tmp_dir.path().join("vars.yml");
+        std::fs::write(&vars_path, data).unwrap();
+        let rules = VarRules::from_vars(&vars_path).unwrap();
+        assert_eq!(rules.len(), 3);
+        assert!(rules.find("MYSQL_HOST").is_none());
+        let port = rules.find("MYSQL_PORT").unwrap();
+        assert_eq!(port.kind(), &Some(VarKind::Int));
+        assert!(port.required());
+        match port.constraint() {
+            Some(Constraint::WithInScope(min, max)) => {
+                assert_eq!((*min, *max), (1.0, 65535.0));
+            }
+            other => panic!("unexpected constraint: {other:?}"),
+        }
+
+        // 回写到只含 name/value 的 vars.yml 后可再读出
+        let plain = tmp_dir.path().join("plain.yml");
+        std::fs::write(
+            &plain,
+            "vars:\n- name: MYSQL_PORT\n  value: 3306\n- name: MYSQL_MODE\n  value: master\n",
+        )
+        .unwrap();
+        rules.mark_vars(&plain).unwrap();
+        let loaded = VarRules::from_vars(&plain).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.find("MYSQL_PORT").unwrap().required());
+        assert!(loaded.find("MYSQL_MODE").unwrap().constraint().is_some());
+    }
+
+    #[test]
+    fn constraint_rule_check() {
+        use serde_json::json;
+        let port = ConstraintRule::define("PORT")
+            .with_kind(VarKind::Int)
+            .with_required(true)
+            .with_constraint(Constraint::WithInScope(1.0, 65535.0));
+        assert!(port.check(Some(&json!(3306))).is_none());
+        assert!(port.check(Some(&json!(70000))).is_some());
+        assert!(port.check(Some(&json!("3306"))).is_some());
+        assert!(port.check(None).is_some());
+        assert!(port.check(Some(&json!(""))).is_some());
+
+        let ratio = ConstraintRule::new("RATIO", Constraint::WithInScope(0.0, 0.5));
+        assert!(ratio.check(Some(&json!(0.25))).is_none());
+        assert!(ratio.check(Some(&json!(0.75))).is_some());
+
+        let mode = ConstraintRule::new(
+            "MODE",
+            Constraint::OneOf(vec![json!("master"), json!("slave")]),
+        );
+        assert!(mode.check(Some(&json!("slave"))).is_none());
+        assert!(mode.check(Some(&json!("other"))).is_some());
+        assert!(mode.check(None).is_none());
+
+        let user = ConstraintRule::new("USER", Constraint::Matching("^[a-z]+$".into()));
+        assert!(user.check(Some(&json!("galaxy"))).is_none());
+        assert!(user.check(Some(&json!("Galaxy1"))).is_some());
+    }
 }
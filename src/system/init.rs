@@ -59,3 +59,40 @@ pub fn sys_init_gitignore(path: &Path) -> MainResult<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use orion_variate::addr::LocalAddr;
+    use regex::Regex;
+
+    use super::*;
+    use crate::{
+        module::{CpuArch, ModelSTD, OsCPE, RunSPC, refs::ModuleSpecRef},
+        system::ModulesList,
+    };
+
+    // setup 流程引用的变量须在流程内定义, ITEM 的字段须与 mod_order.yml 的键一致
+    #[test]
+    fn test_setup_flow_refs() {
+        let mut list = ModulesList::default();
+        list.add_ref(ModuleSpecRef::from(
+            "mysql",
+            LocalAddr::from("./mysql"),
+            ModelSTD::new(CpuArch::X86, OsCPE::UBT22, RunSPC::Host),
+        ));
+        let order = serde_json::to_value(list.export_order().unwrap()).unwrap();
+        let item = order[0].as_object().unwrap();
+
+        let re = Regex::new(r"\$\{([A-Za-z_]+)(?:\.([A-Za-z_]+))?\}").unwrap();
+        for cap in re.captures_iter(SYS_OPS_GXL) {
+            match (&cap[1], cap.get(2)) {
+                ("ITEM", Some(key)) => assert!(item.contains_key(key.as_str()), "{}", &cap[0]),
+                ("MOD_ORDER" | "ITEM" | "GXL_CHANNEL" | "GXL_PRJ_ROOT", _) => {}
+                (name, _) => assert!(
+                    SYS_OPS_GXL.contains(format!("{name} = ").as_str()),
+                    "{name} is not defined"
+                ),
+            }
+        }
+    }
+}
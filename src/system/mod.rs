@@ -4,20 +4,25 @@ pub mod proj;
 pub mod refs;
 pub mod spec;
 use crate::predule::*;
-use std::{net::Ipv4Addr, path::PathBuf};
+use std::{collections::HashMap, net::Ipv4Addr, path::PathBuf};
 
+use crate::infra::{update_jobs, update_progress};
+use crate::lock::LockFile;
 use crate::types::{Localizable, LocalizeOptions, SysUpdateValue, ValuePath};
 use async_trait::async_trait;
 use derive_more::Deref;
+use futures::StreamExt;
+use orion_variate::types::UpdateUnit;
 use orion_variate::update::UpdateOptions;
 use orion_variate::vars::{ValueDict, ValueType, VarCollection};
 
 use crate::module::refs::ModuleSpecRef;
 use crate::module::spec::ModuleSpec;
+use crate::project::ValueExplain;
 use crate::{
-    error::MainResult,
+    error::{MainReason, MainResult, SysReason, ToErr},
     resource::{ResouceTypes, Vps},
-    software::FileFormat,
+    software::{FileFormat, VarRules},
 };
 
 #[derive(Getters, Clone, Debug, Default, Serialize, Deserialize, Deref)]
@@ -50,6 +55,104 @@ impl ModulesList {
     pub fn find(&self, arg: &str) -> Option<&ModuleSpecRef> {
         self.mods.iter().find(|x| x.name() == arg)
     }
+
+    // 按 requires/after 计算启用模块的拓扑顺序, 无依赖约束时保持声明顺序
+    pub fn ordered(&self) -> MainResult<Vec<&ModuleSpecRef>> {
+        let enabled: Vec<&ModuleSpecRef> = self.mods.iter().filter(|x| x.is_enable()).collect();
+        let index: HashMap<&str, usize> = enabled
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (x.name().as_str(), i))
+            .collect();
+        let mut deps: Vec<Vec<usize>> = vec![Vec::new(); enabled.len()];
+        for (i, m) in enabled.iter().enumerate() {
+            for dep in m.requires() {
+                match index.get(dep.as_str()) {
+                    Some(j) => deps[i].push(*j),
+                    None => {
+                        let state = if self.find(dep).is_some() {
+                            "disabled"
+                        } else {
+                            "not found"
+                        };
+                        return MainReason::from(SysReason::Depend(format!(
+                            "module {} requires {dep}, but it is {state}",
+                            m.name()
+                        )))
+                        .err_result();
+                    }
+                }
+            }
+            for dep in m.after() {
+                if let Some(j) = index.get(dep.as_str()) {
+                    deps[i].push(*j);
+                }
+            }
+        }
+
+        let mut done = vec![false; enabled.len()];
+        let mut order = Vec::new();
+        while let Some(i) =
+            (0..enabled.len()).find(|i| !done[*i] && deps[*i].iter().all(|j| done[*j]))
+        {
+            done[i] = true;
+            order.push(enabled[i]);
+        }
+        if order.len() < enabled.len() {
+            // 剩余节点必然存在环, 沿未完成的依赖走到重复节点即得到环路
+            let mut path: Vec<usize> = Vec::new();
+            let mut cur = (0..enabled.len()).find(|i| !done[*i]).unwrap_or_default();
+            while !path.contains(&cur) {
+                path.push(cur);
+                cur = deps[cur].iter().copied().find(|j| !done[*j]).unwrap_or(cur);
+            }
+            let start = path.iter().position(|x| *x == cur).unwrap_or_default();
+            let mut names: Vec<&str> = path[start..]
+                .iter()
+                .map(|x| enabled[*x].name().as_str())
+                .collect();
+            names.push(enabled[cur].name().as_str());
+            return MainReason::from(SysReason::Depend(format!(
+                "module dependency cycle: {}",
+                names.join(" -> ")
+            )))
+            .err_result();
+        }
+        Ok(order)
+    }
+
+    fn collect_export(mods_value: &mut ValueDict, m: &ModuleSpecRef, exported: ValueDict) {
+        for (key, value) in exported.iter() {
+            mods_value.insert(format!("mods.{}.{key}", m.name()), value.clone());
+        }
+    }
+
+    // 导出给 gxl 使用的模块执行顺序, model/path 取实际使用(可能回退)的 target
+    pub fn export_order(&self) -> MainResult<Vec<ModOrderItem>> {
+        Ok(self
+            .ordered()?
+            .into_iter()
+            .map(|x| {
+                let model = x
+                    .local()
+                    .as_ref()
+                    .and_then(|local| x.resolve_target(&local.join(x.name())))
+                    .unwrap_or(x.model().clone());
+                ModOrderItem {
+                    name: x.name().clone(),
+                    model: model.to_string(),
+                    path: format!("mods/{}/{}", x.name(), model),
+                }
+            })
+            .collect())
+    }
+}
+
+#[derive(Getters, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModOrderItem {
+    name: String,
+    model: String,
+    path: String,
 }
 
 impl ModulesList {
@@ -57,21 +160,90 @@ impl ModulesList {
         &self,
         sys_root: &Path,
         options: &UpdateOptions,
+        pinned: Option<&LockFile>,
+        jobs: usize,
     ) -> MainResult<SysUpdateValue> {
+        let mods = self.ordered()?;
+        let pinned = pinned.map(|x| x.sub("mods"));
+        let pinned = pinned.as_ref();
+        let bar = update_progress(mods.len(), "update mods");
+        let mut results: Vec<(usize, &ModuleSpecRef, MainResult<(UpdateUnit, LockFile)>)> =
+            futures::stream::iter(mods.into_iter().enumerate())
+                .map(|(i, m)| {
+                    let bar = bar.clone();
+                    async move {
+                        let result = m.update(sys_root, options, pinned, jobs).await;
+                        bar.set_message(m.name().clone());
+                        bar.inc(1);
+                        (i, m, result)
+                    }
+                })
+                .buffer_unordered(update_jobs(jobs))
+                .collect()
+                .await;
+        bar.finish_and_clear();
+        // 保持依赖顺序合并变量, 所有失败模块一并报告
+        results.sort_by_key(|(i, _, _)| *i);
         let mut vars = VarCollection::default();
-        for m in &self.mods {
-            if m.is_enable() {
-                let update_v = m.update(sys_root, options).await?;
-                if let Some(v) = update_v.vars {
-                    vars = vars.merge(v);
+        let mut lock = LockFile::default();
+        let mut fails = Vec::new();
+        for (_, m, result) in results {
+            match result {
+                Ok((update_v, mod_lock)) => {
+                    if let Some(v) = update_v.vars {
+                        vars = vars.merge(v);
+                    }
+                    lock.append("mods", mod_lock);
+                }
+                Err(e) => {
+                    error!(target: "sys/mods", "update mod {} fail: {}", m.name(), e);
+                    fails.push(format!("{}: {}", m.name(), e));
                 }
             }
         }
-        Ok(SysUpdateValue::new(vars))
+        if !fails.is_empty() {
+            return MainReason::from(SysReason::UpdateFail(fails.join("\n"))).err_result();
+        }
+        Ok(SysUpdateValue::new(vars).with_lock(lock))
     }
     pub fn value_path(&self, parent: ValuePath) -> ValuePath {
         parent.join_all("mods")
     }
+
+    // 逐个模块说明变量取值来源, module 为空时包含全部启用模块
+    // 各模块 target vars.yml 中的规则合并, 同名 key 以先出现的模块为准
+    pub fn var_rules(&self) -> MainResult<VarRules> {
+        let mut rules = VarRules::default();
+        for m in self.ordered()? {
+            if let Some(spec) = m.get_target_spec()? {
+                rules.merge(spec.rules());
+            }
+        }
+        Ok(rules)
+    }
+    pub fn explain_value(
+        &self,
+        parent: ValuePath,
+        options: LocalizeOptions,
+        module: Option<&str>,
+    ) -> MainResult<Vec<(String, Vec<ValueExplain>)>> {
+        let root = self.value_path(parent);
+        let mut explains = Vec::new();
+        let mut mods_value = ValueDict::new();
+        for m in self.ordered()? {
+            let cur_options = options.clone().with_mods_value(mods_value.clone());
+            if module.is_none_or(|x| x == m.name()) {
+                if let Some(spec) = m.get_target_spec()? {
+                    let items =
+                        spec.explain_value(m.spec_value_path(root.clone()), cur_options.clone())?;
+                    explains.push((m.name().clone(), items));
+                }
+            }
+            let exported = m.export_value(Some(root.clone()), cur_options)?;
+            Self::collect_export(&mut mods_value, m, exported);
+        }
+        Ok(explains)
+    }
 }
 #[async_trait]
 impl Localizable for ModulesList {
@@ -81,10 +253,15 @@ impl Localizable for ModulesList {
         options: LocalizeOptions,
     ) -> MainResult<()> {
         let root = dst_path.map(|x| x.join_all("mods"));
-        for m in &self.mods {
-            if m.is_enable() {
-                m.localize(root.clone(), options.clone()).await?;
-            }
+        let mut mods_value = ValueDict::new();
+        for m in self.ordered()? {
+            let cur_options = options.clone().with_mods_value(mods_value.clone());
+            m.localize(root.clone(), cur_options.clone()).await?;
+            Self::collect_export(
+                &mut mods_value,
+                m,
+                m.export_value(root.clone(), cur_options)?,
+            );
         }
         Ok(())
     }
@@ -182,3 +359,123 @@ impl NetAllocator {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use orion_common::serde::Persistable;
+    use orion_variate::addr::LocalAddr;
+
+    use super::*;
+    use crate::module::{CpuArch, ModelSTD, OsCPE, RunSPC};
+
+    fn mod_ref(name: &str) -> ModuleSpecRef {
+        ModuleSpecRef::from(
+            name,
+            LocalAddr::from(format!("./{name}")),
+            ModelSTD::new(CpuArch::X86, OsCPE::UBT22, RunSPC::Host),
+        )
+    }
+
+    fn names(list: &ModulesList) -> Vec<String> {
+        list.ordered()
+            .unwrap()
+            .iter()
+            .map(|x| x.name().clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_ordered_by_depends() {
+        let mut list = ModulesList::default();
+        list.add_ref(mod_ref("app").with_requires(vec!["mysql", "redis"]));
+        list.add_ref(mod_ref("redis").with_after(vec!["mysql", "nginx"]));
+        list.add_ref(mod_ref("mysql"));
+        list.add_ref(mod_ref("log"));
+        assert_eq!(names(&list), vec!["mysql", "redis", "app", "log"]);
+
+        let order = list.export_order().unwrap();
+        assert_eq!(order[0].name(), "mysql");
+        assert_eq!(order[0].path(), &format!("mods/mysql/{}", order[0].model()));
+    }
+
+    #[test]
+    fn test_export_order_fallback_target() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let ubt22 = ModelSTD::new(CpuArch::X86, OsCPE::UBT22, RunSPC::Host);
+        let ubt24 = ModelSTD::new(CpuArch::X86, OsCPE::UBT24, RunSPC::Host);
+        std::fs::create_dir_all(
+            temp_dir
+                .path()
+                .join("mods")
+                .join("mysql")
+                .join(ubt22.to_string()),
+        )
+        .unwrap();
+        let mut list = ModulesList::default();
+        list.add_ref(ModuleSpecRef::from(
+            "mysql",
+            LocalAddr::from("./mysql"),
+            ubt24,
+        ));
+        list.set_mods_local(temp_dir.path().to_path_buf());
+
+        let order = list.export_order().unwrap();
+        assert_eq!(order[0].model(), &ubt22.to_string());
+        assert_eq!(order[0].path(), &format!("mods/mysql/{ubt22}"));
+    }
+
+    #[tokio::test]
+    async fn test_update_local_mods_concurrently() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src_root = temp_dir.path().join("src");
+        let sys_root = temp_dir.path().join("sys");
+        let model = ModelSTD::x86_ubt22_k8s();
+        let mut list = ModulesList::default();
+        for name in ["mysql", "redis"] {
+            ModuleSpec::make_new(name)
+                .unwrap()
+                .save_to(&src_root, None)
+                .unwrap();
+            list.add_ref(ModuleSpecRef::from(
+                name,
+                LocalAddr::from(src_root.join(name).display().to_string()),
+                model.clone(),
+            ));
+        }
+        list.set_mods_local(sys_root.clone());
+        list.update(&sys_root, &UpdateOptions::for_test(), None, 2)
+            .await
+            .unwrap();
+
+        let mods_root = sys_root.join("mods");
+        for name in ["mysql", "redis"] {
+            assert!(mods_root.join(name).join(model.to_string()).exists());
+        }
+        let left: Vec<String> = std::fs::read_dir(&mods_root)
+            .unwrap()
+            .map(|x| x.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|x| x.starts_with("__mod"))
+            .collect();
+        assert!(left.is_empty(), "{left:?}");
+    }
+
+    #[test]
+    fn test_ordered_miss_requires() {
+        let mut list = ModulesList::default();
+        list.add_ref(mod_ref("app").with_requires(vec!["mysql"]));
+        list.add_ref(mod_ref("mysql").with_enable(false));
+        let err = list.ordered().unwrap_err().to_string();
+        assert!(err.contains("module app requires mysql, but it is disabled"));
+    }
+
+    #[test]
+    fn test_ordered_cycle() {
+        let mut list = ModulesList::default();
+        list.add_ref(mod_ref("log"));
+        list.add_ref(mod_ref("app").with_requires(vec!["mysql"]));
+        list.add_ref(mod_ref("mysql").with_after(vec!["redis"]));
+        list.add_ref(mod_ref("redis").with_requires(vec!["app"]));
+        let err = list.ordered().unwrap_err().to_string();
+        assert!(err.contains("module dependency cycle: app -> mysql -> redis -> app"));
+    }
+}
@@ -1,8 +1,15 @@
-use crate::const_vars::{SYS_PRJ_CONF_FILE_V1, SYS_PRJ_CONF_FILE_V2, VALUE_DIR, VALUE_FILE};
-use crate::error::SysReason;
+use crate::const_vars::{
+    SYS_LOCK_FILE, SYS_PRJ_CONF_FILE_V1, SYS_PRJ_CONF_FILE_V2, VALUE_DIR, VARS_YML,
+};
+use crate::error::{MainReason, SysReason, ToErr};
+use crate::lock::LockFile;
 use crate::module::ModelSTD;
 use crate::predule::*;
 
+use crate::ia_value::{ia_value_setting, var_prompts};
+use crate::project::{ValueExplain, load_value_file, save_value_file};
+use crate::secret::{SecretKey, sensitive_vars};
+use crate::software::VarRules;
 use crate::system::spec::SysDefine;
 use crate::{
     const_vars::SYS_MODEL_SPC_ROOT, error::MainResult, module::depend::DependencySet,
@@ -19,7 +26,7 @@ use orion_common::serde::{Configable, Persistable};
 use orion_infra::auto_exit_log;
 use orion_infra::path::{ensure_path, make_clean_path};
 use orion_variate::update::UpdateOptions;
-use orion_variate::vars::{ValueDict, ValueType};
+use orion_variate::vars::{ValueDict, ValueType, VarCollection};
 
 #[derive(Getters, Clone, Debug, Serialize, Deserialize)]
 struct SysConf {
@@ -77,9 +84,9 @@ impl SysProject {
         let sys_spec = SysModelSpec::load_from(&sys_local)?;
         let project = GxlProject::load_from(&root_local).owe(SysReason::Load.into())?;
         let value_root = ensure_path(root_local.join(VALUE_DIR)).owe_logic()?;
-        let value_file = value_root.join(VALUE_FILE);
+        let value_file = ValuePath::from_root(value_root).value_file();
         let val_dict = if value_file.exists() {
-            ValueDict::from_conf(&value_file).owe_data()?
+            load_value_file(&value_file, None)?
         } else {
             ValueDict::new()
         };
@@ -111,8 +118,8 @@ impl SysProject {
             .owe(SysReason::Save.into())?;
 
         let value_root = ensure_path(self.root_local().join(VALUE_DIR)).owe_logic()?;
-        let value_file = value_root.join(VALUE_FILE);
-        self.val_dict.save_conf(&value_file).owe_res()?;
+        let value_file = ValuePath::from_root(value_root).value_file();
+        save_value_file(&value_file, &self.val_dict)?;
         sys_init_gitignore(self.root_local())?;
         flag.mark_suc();
         Ok(())
@@ -120,18 +127,55 @@ impl SysProject {
 }
 
 impl SysConf {
-    pub async fn update(&self, options: &UpdateOptions) -> MainResult<()> {
-        self.test_envs
-            .update(options)
+    pub async fn update(
+        &self,
+        options: &UpdateOptions,
+        pinned: Option<&LockFile>,
+        jobs: usize,
+    ) -> MainResult<LockFile> {
+        let units = self
+            .test_envs
+            .update_units(options, jobs)
             .await
-            .owe(SysReason::Update.into())
+            .owe(SysReason::Update.into())?;
+        DependencySet::lock_units(units, pinned)
     }
 }
 
 impl SysProject {
     pub async fn update(&self, options: &UpdateOptions) -> MainResult<()> {
-        self.conf.update(options).await?;
-        self.sys_spec().update_local(options).await
+        self.update_lock(options, false, 0).await
+    }
+    // locked 为 true 时按 sys-lock.yml 固定版本拉取且不改写它, 拉取结果与其不一致则失败
+    pub async fn update_lock(
+        &self,
+        options: &UpdateOptions,
+        locked: bool,
+        jobs: usize,
+    ) -> MainResult<()> {
+        let lock_path = self.root_local().join(SYS_LOCK_FILE);
+        let pinned = if locked {
+            let Some(pinned) = LockFile::load(&lock_path)? else {
+                return MainReason::from(SysReason::Locked(format!(
+                    "lock file not found: {}",
+                    lock_path.display()
+                )))
+                .err_result();
+            };
+            Some(pinned)
+        } else {
+            None
+        };
+        let mut lock = self.conf.update(options, pinned.as_ref(), jobs).await?;
+        lock.extend(
+            self.sys_spec()
+                .update_local_pinned(options, pinned.as_ref(), jobs)
+                .await?,
+        );
+        if let Some(report) = lock.apply(&lock_path, locked)? {
+            return MainReason::from(SysReason::Locked(report)).err_result();
+        }
+        Ok(())
     }
 }
 
@@ -164,6 +208,30 @@ impl SysProject {
         let value_root = self.root_local().join(VALUE_DIR);
         ValuePath::from_root(value_root)
     }
+    pub fn explain_value(
+        &self,
+        options: LocalizeOptions,
+        module: Option<&str>,
+    ) -> MainResult<Vec<(String, Vec<ValueExplain>)>> {
+        self.sys_spec
+            .mod_list()
+            .explain_value(self.value_path(), options, module)
+    }
+    // 按变量类型交互设定项目全局 value 文件, 变量取自 sys/vars.yml
+    pub fn ia_setting(&self, all: bool) -> MainResult<()> {
+        let vars_path = self.root_local().join("sys").join(VARS_YML);
+        let vars = VarCollection::from_conf(&vars_path)
+            .owe_res()
+            .with(&vars_path)?;
+        let sensitive = sensitive_vars(&vars_path).owe_res()?;
+        let mut rules = VarRules::from_vars(&vars_path).owe_res()?;
+        rules.merge(&self.sys_spec.mod_list().var_rules()?);
+        let prompts = var_prompts(&vars, &rules, &sensitive)?;
+        let value_file = self.value_path().value_file();
+        let key = SecretKey::load(self.root_local())?;
+        ia_value_setting(&prompts, &value_file, all, key.as_ref())?;
+        Ok(())
+    }
 }
 impl SysProject {
     pub fn make_new(prj_path: &Path, name: &str, model: ModelSTD) -> MainResult<Self> {
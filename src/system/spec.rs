@@ -7,8 +7,11 @@ use crate::{
 use std::path::{Path, PathBuf};
 
 use crate::{
-    const_vars::MODULES_SPC_ROOT, error::ElementReason, module::proj::ModProject,
-    types::Localizable, workflow::act::SysWorkflows,
+    const_vars::{MOD_ORDER_YML, MODULES_SPC_ROOT},
+    error::ElementReason,
+    module::proj::ModProject,
+    types::Localizable,
+    workflow::act::SysWorkflows,
 };
 use async_trait::async_trait;
 use getset::{Getters, WithSetters};
@@ -24,6 +27,7 @@ use super::{
     ModulesList,
     init::{SysIniter, sys_init_gitignore},
 };
+use crate::lock::LockFile;
 use crate::types::LocalizeOptions;
 use crate::{
     error::{MainReason, MainResult, ToErr},
@@ -139,15 +143,29 @@ impl SysModelSpec {
         }
     }
 
-    pub async fn update_local(&self, options: &UpdateOptions) -> MainResult<()> {
+    pub async fn update_local(&self, options: &UpdateOptions) -> MainResult<LockFile> {
+        self.update_local_pinned(options, None, 0).await
+    }
+    // pinned 为 --locked 时的锁, 模块按其中记录的版本拉取; jobs 为并发数
+    pub async fn update_local_pinned(
+        &self,
+        options: &UpdateOptions,
+        pinned: Option<&LockFile>,
+        jobs: usize,
+    ) -> MainResult<LockFile> {
         if let Some(local) = &self.local {
-            let value = self.mod_list.update(local, options).await?;
+            let value = self.mod_list.update(local, options, pinned, jobs).await?;
             let path = local.join("vars.yml");
             if path.exists() {
                 std::fs::remove_file(&path).owe_sys()?;
             }
             value.vars.save_yml(&path).owe_res()?;
-            Ok(())
+            let order_path = local.join(MOD_ORDER_YML);
+            self.mod_list
+                .export_order()?
+                .save_yml(&order_path)
+                .owe_res()?;
+            Ok(value.lock)
         } else {
             MainReason::from(ElementReason::Miss("local path".into())).err_result()
         }
@@ -8,16 +8,27 @@ use orion_variate::{
     vars::{EnvDict, EnvEvalable, ValueDict, VarCollection},
 };
 
-use crate::error::MainResult;
+use crate::{
+    const_vars::USER_VALUE_STEM, error::MainResult, lock::LockFile, project::find_value_file,
+    secret::SecretKey,
+};
 
 pub type AnyResult<T> = anyhow::Result<T>;
 #[derive(Clone)]
 pub struct SysUpdateValue {
     pub vars: VarCollection,
+    pub lock: LockFile,
 }
 impl SysUpdateValue {
     pub fn new(vars: VarCollection) -> Self {
-        Self { vars }
+        Self {
+            vars,
+            lock: LockFile::default(),
+        }
+    }
+    pub fn with_lock(mut self, lock: LockFile) -> Self {
+        self.lock = lock;
+        self
     }
     pub fn vars(&self) -> &VarCollection {
         &self.vars
@@ -35,6 +46,19 @@ pub struct LocalizeOptions {
     eval_dict: ValueDict,
     raw_dict: ValueDict,
     use_default_value: bool,
+    dry_run: bool,
+    // 模板引用未定义变量时报错
+    strict: bool,
+    // 已本地化模块导出的值, key 形如 mods.mysql.MYSQL_PORT
+    mods_value: ValueDict,
+    // 解密 value 中 ENC[..] 加密项的密钥
+    secret: Option<SecretKey>,
+    // 系统值层(系统名, 取值), 优先于 global
+    sys_value: Option<(String, ValueDict)>,
+    // 环境覆盖层(名称, 取值), 优先于系统值
+    env_value: Option<(String, ValueDict)>,
+    // 命令行覆盖层(来源, 取值), 按优先级升序, 优先于 env
+    overrides: Vec<(String, ValueDict)>,
 }
 impl LocalizeOptions {
     pub fn new(raw_dict: ValueDict, mod_user_value: bool) -> Self {
@@ -42,8 +66,48 @@ impl LocalizeOptions {
             eval_dict: raw_dict.clone().env_eval(&EnvDict::default()),
             raw_dict,
             use_default_value: mod_user_value,
+            dry_run: false,
+            strict: false,
+            mods_value: ValueDict::new(),
+            secret: None,
+            sys_value: None,
+            env_value: None,
+            overrides: Vec::new(),
         }
     }
+    pub fn with_raw_value(mut self, raw_dict: ValueDict) -> Self {
+        self.eval_dict = raw_dict.clone().env_eval(&EnvDict::default());
+        self.raw_dict = raw_dict;
+        self
+    }
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+    pub fn with_mods_value(mut self, mods_value: ValueDict) -> Self {
+        self.mods_value = mods_value;
+        self
+    }
+    pub fn with_secret(mut self, secret: Option<SecretKey>) -> Self {
+        self.secret = secret;
+        self
+    }
+    pub fn with_sys_value(mut self, sys_value: Option<(String, ValueDict)>) -> Self {
+        self.sys_value = sys_value;
+        self
+    }
+    pub fn with_env_value(mut self, env_value: Option<(String, ValueDict)>) -> Self {
+        self.env_value = env_value;
+        self
+    }
+    pub fn with_overrides(mut self, overrides: Vec<(String, ValueDict)>) -> Self {
+        self.overrides = overrides;
+        self
+    }
     pub fn evaled_value(&self) -> &ValueDict {
         &self.eval_dict
     }
@@ -53,12 +117,40 @@ impl LocalizeOptions {
     pub fn use_default_value(&self) -> bool {
         self.use_default_value
     }
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+    pub fn mods_value(&self) -> &ValueDict {
+        &self.mods_value
+    }
+    pub fn secret(&self) -> Option<&SecretKey> {
+        self.secret.as_ref()
+    }
+    pub fn sys_value(&self) -> Option<&(String, ValueDict)> {
+        self.sys_value.as_ref()
+    }
+    pub fn env_value(&self) -> Option<&(String, ValueDict)> {
+        self.env_value.as_ref()
+    }
+    pub fn overrides(&self) -> &[(String, ValueDict)] {
+        &self.overrides
+    }
 
     pub fn for_test() -> Self {
         Self {
             eval_dict: ValueDict::new(),
             raw_dict: ValueDict::new(),
             use_default_value: false,
+            dry_run: false,
+            strict: false,
+            mods_value: ValueDict::new(),
+            secret: None,
+            sys_value: None,
+            env_value: None,
+            overrides: Vec::new(),
         }
     }
 }
@@ -100,8 +192,9 @@ impl ValuePath {
             path: self.path.join(&value),
         }
     }
+    // value.<yml|yaml|json|toml|ini>, 都不存在时为 value.yml
     pub fn value_file(&self) -> PathBuf {
-        self.path.join(VALUE_FILE)
+        find_value_file(&self.path, USER_VALUE_STEM).unwrap_or(self.path.join(VALUE_FILE))
     }
     pub fn ensure_exist(self) -> PathResult<Self> {
         ensure_path(&self.path)?;
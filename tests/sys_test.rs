@@ -6,7 +6,7 @@ use galaxy_ops::{
     module::depend::{Dependency, DependencySet},
     ops_prj::proj::OpsProject,
     system::{proj::SysProject, spec::SysModelSpec},
-    types::LocalizeOptions,
+    types::{Localizable, LocalizeOptions},
 };
 use orion_error::{ErrorOwe, TestAssertWithMsg};
 use orion_infra::path::make_clean_path;
@@ -33,6 +33,7 @@ async fn test_full_flow() -> MainResult<()> {
     let sys_proj = SysProject::load(&sys_path)?;
     sys_proj.update(&UpdateOptions::default()).await?;
     sys_proj.localize(LocalizeOptions::for_test()).await?;
+    ops_proj.localize(None, LocalizeOptions::for_test()).await?;
     Ok(())
     //sys_proj.
 }